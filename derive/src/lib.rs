@@ -0,0 +1,213 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Fields, Member, Type};
+
+// Implements 'ComponentStorage' for a struct of storages. Each field is bound to its component
+// either through '#[component(Type)]' or by being declared as '<Type as Component>::Storage',
+// fields marked '#[component(skip)]' are left out. Fields marked '#[resource]' are handed out
+// through 'ResourceStorage' by their own type
+#[proc_macro_derive(ComponentStorage, attributes(component, resource))]
+pub fn derive_component_storage(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new(
+                input.ident.span(),
+                "ComponentStorage can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut members = vec![];
+    let mut components = vec![];
+    let mut resource_members = vec![];
+    let mut resources = vec![];
+
+    let fields = match fields {
+        Fields::Named(named) => named.named.iter().collect(),
+        Fields::Unnamed(unnamed) => unnamed.unnamed.iter().collect(),
+        Fields::Unit => vec![],
+    };
+
+    for (idx, field) in fields.into_iter().enumerate() {
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(idx.into()),
+        };
+
+        if field
+            .attrs
+            .iter()
+            .any(|attr| attr.path().is_ident("resource"))
+        {
+            resource_members.push(member);
+            resources.push(field.ty.clone());
+            continue;
+        }
+
+        let component = match binding(field)? {
+            Some(component) => component,
+            None => continue,
+        };
+
+        members.push(member);
+        components.push(component);
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::storage::any_storage::ComponentStorage for #name #ty_generics
+            #where_clause
+        {
+            fn get<__T>(&self) -> Option<&__T::Storage>
+            where
+                __T: ::std::any::Any + ::storage::any_storage::Component,
+            {
+                use ::std::any::TypeId;
+
+                #(
+                    if TypeId::of::<__T>() == TypeId::of::<#components>() {
+                        return (&self.#members as &dyn ::std::any::Any).downcast_ref();
+                    }
+                )*
+
+                None
+            }
+
+            fn get_mut<'__a, __T>(&'__a mut self) -> Option<&'__a mut __T::Storage>
+            where
+                __T: '__a + ::std::any::Any + ::storage::any_storage::Component,
+            {
+                use ::std::any::TypeId;
+
+                #(
+                    if TypeId::of::<__T>() == TypeId::of::<#components>() {
+                        return (&mut self.#members as &mut dyn ::std::any::Any).downcast_mut();
+                    }
+                )*
+
+                None
+            }
+
+            #[allow(unused_variables)]
+            fn visit(
+                &self,
+                f: &mut dyn FnMut(::storage::any_storage::ComponentInfo, &dyn ::std::any::Any),
+            ) {
+                #(
+                    f(::storage::any_storage::ComponentInfo::of::<#components>(), &self.#members);
+                )*
+            }
+
+            #[allow(unused_variables)]
+            fn visit_mut(
+                &mut self,
+                f: &mut dyn FnMut(::storage::any_storage::ComponentInfo, &mut dyn ::std::any::Any),
+            ) {
+                #(
+                    f(::storage::any_storage::ComponentInfo::of::<#components>(), &mut self.#members);
+                )*
+            }
+        }
+
+        unsafe impl #impl_generics ::storage::any_storage::FetchStorage for #name #ty_generics
+            #where_clause
+        {
+            fn storage_ptrs(
+                &mut self,
+                ids: &[::std::any::TypeId],
+            ) -> Vec<Option<*mut ()>> {
+                use ::std::any::TypeId;
+
+                ids.iter()
+                    .map(|id| {
+                        #(
+                            if *id == TypeId::of::<#components>() {
+                                return Some(&mut self.#members as *mut _ as *mut ());
+                            }
+                        )*
+
+                        None
+                    })
+                    .collect()
+            }
+        }
+
+        impl #impl_generics ::storage::any_storage::ResourceStorage for #name #ty_generics
+            #where_clause
+        {
+            fn get_resource<__R: ::std::any::Any>(&self) -> Option<&__R> {
+                #(
+                    if ::std::any::TypeId::of::<__R>() == ::std::any::TypeId::of::<#resources>() {
+                        return (&self.#resource_members as &dyn ::std::any::Any).downcast_ref();
+                    }
+                )*
+
+                None
+            }
+
+            fn get_resource_mut<__R: ::std::any::Any>(&mut self) -> Option<&mut __R> {
+                #(
+                    if ::std::any::TypeId::of::<__R>() == ::std::any::TypeId::of::<#resources>() {
+                        return (&mut self.#resource_members as &mut dyn ::std::any::Any)
+                            .downcast_mut();
+                    }
+                )*
+
+                None
+            }
+        }
+    })
+}
+
+// The component a field stores, 'None' for skipped fields
+fn binding(field: &syn::Field) -> Result<Option<Type>, Error> {
+    for attr in &field.attrs {
+        if !attr.path().is_ident("component") {
+            continue;
+        }
+
+        let ty: Type = attr.parse_args()?;
+
+        if let Type::Path(path) = &ty {
+            if path.qself.is_none() && path.path.is_ident("skip") {
+                return Ok(None);
+            }
+        }
+
+        return Ok(Some(ty));
+    }
+
+    // '<Type as Component>::Storage'
+    if let Type::Path(path) = &field.ty {
+        if let Some(qself) = &path.qself {
+            let is_storage = path
+                .path
+                .segments
+                .last()
+                .map(|segment| segment.ident == "Storage");
+
+            if is_storage == Some(true) {
+                return Ok(Some((*qself.ty).clone()));
+            }
+        }
+    }
+
+    Err(Error::new(
+        field.ty.span(),
+        "expected '#[component(Type)]', '#[component(skip)]' or a field of type \
+         '<Type as Component>::Storage'",
+    ))
+}
@@ -0,0 +1,188 @@
+use std::{
+    alloc::{self as std_alloc, GlobalAlloc, Layout},
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    slice,
+};
+
+/// Where a storage gets its backing memory from, lets arenas or tracked heaps be plugged in
+/// without the nightly allocator api.
+///
+/// # Safety
+/// 'allocate' must return memory that fits the layout, or null on failure, and stays valid until
+/// it's handed back to 'deallocate' with the same layout
+pub unsafe trait Alloc {
+    fn allocate(&self, layout: Layout) -> *mut u8;
+
+    /// # Safety
+    /// The pointer must have come from 'allocate' on this allocator with the same layout
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout);
+}
+
+// Whatever the program's global allocator is
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Global;
+
+unsafe impl Alloc for Global {
+    fn allocate(&self, layout: Layout) -> *mut u8 {
+        unsafe { std_alloc::alloc(layout) }
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        std_alloc::dealloc(ptr, layout)
+    }
+}
+
+unsafe impl Alloc for std_alloc::System {
+    fn allocate(&self, layout: Layout) -> *mut u8 {
+        unsafe { GlobalAlloc::alloc(self, layout) }
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        GlobalAlloc::dealloc(self, ptr, layout)
+    }
+}
+
+// Lets one arena back several storages
+unsafe impl<A: Alloc> Alloc for &A {
+    fn allocate(&self, layout: Layout) -> *mut u8 {
+        (**self).allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        (**self).deallocate(ptr, layout)
+    }
+}
+
+// A bare bones Vec whose buffer comes from an Alloc
+pub struct AllocVec<T, A: Alloc = Global> {
+    ptr: NonNull<T>,
+    len: usize,
+    capacity: usize,
+    alloc: A,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: Send, A: Alloc + Send> Send for AllocVec<T, A> {}
+unsafe impl<T: Sync, A: Alloc + Sync> Sync for AllocVec<T, A> {}
+
+impl<T> AllocVec<T> {
+    pub fn new() -> Self {
+        Self::new_in(Global)
+    }
+}
+
+impl<T> Default for AllocVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, A: Alloc> AllocVec<T, A> {
+    pub fn new_in(alloc: A) -> Self {
+        // Zero sized types never need any memory
+        let capacity = if mem::size_of::<T>() == 0 { usize::MAX } else { 0 };
+
+        Self { ptr: NonNull::dangling(), len: 0, capacity, alloc, _marker: PhantomData }
+    }
+
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.len == self.capacity {
+            self.reserve(1);
+        }
+
+        unsafe { ptr::write(self.ptr.as_ptr().add(self.len), value) };
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.len -= 1;
+        Some(unsafe { ptr::read(self.ptr.as_ptr().add(self.len)) })
+    }
+
+    pub fn clear(&mut self) {
+        let elements: *mut [T] = &mut self[..];
+
+        // Set first so a panicking drop can't lead to a double drop
+        self.len = 0;
+        unsafe { ptr::drop_in_place(elements) };
+    }
+
+    // Makes room for at least additional more elements, growing to at least double the capacity
+    pub fn reserve(&mut self, additional: usize) {
+        let required = self.len.checked_add(additional).expect("Capacity overflow");
+
+        if required <= self.capacity {
+            return;
+        }
+
+        let capacity = required.max(self.capacity * 2).max(4);
+        let layout = Layout::array::<T>(capacity).expect("Capacity overflow");
+        let new = self.alloc.allocate(layout) as *mut T;
+
+        if new.is_null() {
+            std_alloc::handle_alloc_error(layout);
+        }
+
+        unsafe {
+            ptr::copy_nonoverlapping(self.ptr.as_ptr(), new, self.len);
+            self.free();
+            self.ptr = NonNull::new_unchecked(new);
+        }
+
+        self.capacity = capacity;
+    }
+
+    // Hands the buffer back to the allocator without touching the elements
+    unsafe fn free(&mut self) {
+        if mem::size_of::<T>() == 0 || self.capacity == 0 {
+            return;
+        }
+
+        let layout = Layout::array::<T>(self.capacity).unwrap();
+        self.alloc.deallocate(self.ptr.as_ptr() as *mut u8, layout);
+    }
+}
+
+impl<T, A: Alloc> Drop for AllocVec<T, A> {
+    fn drop(&mut self) {
+        self.clear();
+        unsafe { self.free() };
+    }
+}
+
+impl<T, A: Alloc> Deref for AllocVec<T, A> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T, A: Alloc> DerefMut for AllocVec<T, A> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
@@ -1,13 +1,48 @@
-use std::any::Any;
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+#[cfg(feature = "derive")]
+pub use storage_derive::ComponentStorage;
 
 pub trait Component {
-    type Storage;
+    // 'static so storages can be handed out through 'Any' downcasting
+    type Storage: Any;
+}
+
+// Describes a storage handed to 'visit' without knowing its type
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ComponentInfo {
+    pub id: TypeId,
+    pub name: &'static str,
+}
+
+impl ComponentInfo {
+    pub fn of<T: Any + Component>() -> Self {
+        Self { id: TypeId::of::<T>(), name: std::any::type_name::<T>() }
+    }
 }
 
 pub trait ComponentStorage {
     fn get<T: Any + Component>(&self) -> Option<&T::Storage>;
     fn get_mut<'a, T: 'a + Any + Component>(&'a mut self) -> Option<&'a mut T::Storage>;
 
+    // Visits every storage, they can be downcast to the storage type of the component if needed
+    fn visit(&self, f: &mut dyn FnMut(ComponentInfo, &dyn Any));
+    fn visit_mut(&mut self, f: &mut dyn FnMut(ComponentInfo, &mut dyn Any));
+
+    fn components(&self) -> Vec<ComponentInfo> {
+        let mut components = vec![];
+        self.visit(&mut |info, _| components.push(info));
+
+        components
+    }
+
+    fn contains<T: Any + Component>(&self) -> bool {
+        self.get::<T>().is_some()
+    }
+
     fn get_unchecked<T: Any + Component>(&self) -> &T::Storage {
         self.get::<T>().unwrap()
     }
@@ -17,72 +52,373 @@ pub trait ComponentStorage {
     }
 }
 
+// Unique values that aren't tied to an index, such as the time or input state
+pub trait ResourceStorage {
+    fn get_resource<R: Any>(&self) -> Option<&R>;
+    fn get_resource_mut<R: Any>(&mut self) -> Option<&mut R>;
+
+    fn get_resource_unchecked<R: Any>(&self) -> &R {
+        self.get_resource::<R>().unwrap()
+    }
+
+    fn get_resource_mut_unchecked<R: Any>(&mut self) -> &mut R {
+        self.get_resource_mut::<R>().unwrap()
+    }
+}
+
+/// Hands out pointers to several storages at once so 'fetch' can borrow disjoint storages mutably
+/// at the same time. Implemented by 'create_storage!' and the derive
+///
+/// # Safety
+/// Pointers returned for different components must point to different storages and stay valid
+/// for as long as the '&mut self' borrow
+pub unsafe trait FetchStorage: ComponentStorage {
+    fn storage_ptrs(&mut self, ids: &[TypeId]) -> Vec<Option<*mut ()>>;
+
+    // Borrows the storages of several components at once, 'None' if any of them isn't part of this
+    // storage. Panics if a component is borrowed mutably more than once or both mutably and
+    // immutably, the same way a 'RefCell' would
+    fn fetch<'a, F: Fetch<'a>>(&'a mut self) -> Option<F::Output>
+    where
+        Self: Sized,
+    {
+        let mut borrows = vec![];
+        F::borrows(&mut borrows);
+
+        for (idx, (id, mutable)) in borrows.iter().enumerate() {
+            let conflict = borrows[..idx]
+                .iter()
+                .any(|(other, other_mutable)| other == id && (*mutable || *other_mutable));
+
+            if conflict {
+                panic!("component storage is already borrowed");
+            }
+        }
+
+        // Each storage is only asked for once so shared borrows of the same one get the same pointer
+        let mut ids: Vec<_> = vec![];
+        for (id, _) in &borrows {
+            if !ids.contains(id) {
+                ids.push(*id);
+            }
+        }
+
+        let ptrs = self.storage_ptrs(&ids).into_iter().collect::<Option<Vec<_>>>()?;
+        let mut ptrs = borrows.iter().map(|(id, _)| ptrs[ids.iter().position(|x| x == id).unwrap()]);
+
+        Some(unsafe { F::from_ptrs(&mut ptrs) })
+    }
+}
+
+// '&Component', '&mut Component' and tuples of those
+pub trait Fetch<'a> {
+    type Output;
+
+    // The TypeId of every component borrowed and whether it is borrowed mutably
+    fn borrows(borrows: &mut Vec<(TypeId, bool)>);
+
+    /// # Safety
+    /// 'ptrs' has to yield valid storage pointers in the order given by 'borrows' that don't
+    /// alias when borrowed mutably
+    unsafe fn from_ptrs(ptrs: &mut dyn Iterator<Item = *mut ()>) -> Self::Output;
+}
+
+impl<'a, T> Fetch<'a> for &'a T
+where
+    T: Any + Component,
+{
+    type Output = &'a T::Storage;
+
+    fn borrows(borrows: &mut Vec<(TypeId, bool)>) {
+        borrows.push((TypeId::of::<T>(), false));
+    }
+
+    unsafe fn from_ptrs(ptrs: &mut dyn Iterator<Item = *mut ()>) -> Self::Output {
+        &*(ptrs.next().unwrap() as *const T::Storage)
+    }
+}
+
+impl<'a, T> Fetch<'a> for &'a mut T
+where
+    T: Any + Component,
+{
+    type Output = &'a mut T::Storage;
+
+    fn borrows(borrows: &mut Vec<(TypeId, bool)>) {
+        borrows.push((TypeId::of::<T>(), true));
+    }
+
+    unsafe fn from_ptrs(ptrs: &mut dyn Iterator<Item = *mut ()>) -> Self::Output {
+        &mut *(ptrs.next().unwrap() as *mut T::Storage)
+    }
+}
+
+macro_rules! impl_fetch_tuple {
+    ($($fetch:ident),+) => {
+        impl<'a, $($fetch: Fetch<'a>),+> Fetch<'a> for ($($fetch,)+) {
+            type Output = ($($fetch::Output,)+);
+
+            fn borrows(borrows: &mut Vec<(TypeId, bool)>) {
+                $($fetch::borrows(borrows);)+
+            }
+
+            unsafe fn from_ptrs(ptrs: &mut dyn Iterator<Item = *mut ()>) -> Self::Output {
+                ($($fetch::from_ptrs(ptrs),)+)
+            }
+        }
+    };
+}
+
+impl_fetch_tuple!(A);
+impl_fetch_tuple!(A, B);
+impl_fetch_tuple!(A, B, C);
+impl_fetch_tuple!(A, B, C, D);
+impl_fetch_tuple!(A, B, C, D, E);
+impl_fetch_tuple!(A, B, C, D, E, F);
+
+// fetch!(storage, (&mut Positions, &Velocities))
+#[macro_export]
+macro_rules! fetch {
+    ($storage:expr, $fetch:ty) => {
+        $crate::any_storage::FetchStorage::fetch::<$fetch>(&mut $storage)
+    };
+}
+
+// create_storage!(Name { positions: Position, velocities: Velocity } resources { time: Time })
+//
+// Generic parameters take a single bound each, anything more goes in a where clause:
+// create_storage!(Name<T: Clone, U> where U: Debug + Clone { items: Item<T>, others: Other<U> })
 #[macro_export]
 macro_rules! create_storage {
-    ($name:ident { $($component:ident : $component_type:ty),+ }) => {
-        pub struct $name {
+    // Collects the where clause one token at a time until the fields are reached
+    (@generic $name:ident [$($param:tt)+] [$($generic:ident),+] [$($where:tt)*]
+        { $($component:ident : $component_type:ty),+ $(,)? }
+        $(resources { $($resource:ident : $resource_type:ty),* $(,)? })?
+    ) => {
+        pub struct $name<$($param)+>
+        where
+            $($component_type: $crate::any_storage::Component + 'static,)+
+            $($where)*
+        {
             $(
-                pub $component: <$component_type as Component>::Storage,
+                pub $component: <$component_type as $crate::any_storage::Component>::Storage,
             )+
+            $($(
+                pub $resource: $resource_type,
+            )*)?
         }
 
-        impl ComponentStorage for $name {
-            fn get<T: Any + Component>(&self) -> Option<&T::Storage> {
-                use std::any::TypeId;
-                unsafe {
-                    match TypeId::of::<T>() {
-                        $(
-                            x if x == TypeId::of::<$component_type>() => Some(std::mem::transmute(&self.$component)),
-                        )+
-                        _ => None
-                    }
+        $crate::create_storage!(
+            @impl [<$($param)+>] [$name<$($generic),+>]
+            [
+                $($component_type: $crate::any_storage::Component + 'static,)+
+                $($($resource_type: 'static,)*)?
+                $($where)*
+            ]
+            { $($component : $component_type),+ }
+            { $($($resource : $resource_type),*)? }
+        );
+    };
+    (@generic $name:ident [$($param:tt)+] [$($generic:ident),+] [$($where:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::create_storage!(@generic $name [$($param)+] [$($generic),+] [$($where)* $next] $($rest)*);
+    };
+    (@impl [$($impl_generics:tt)*] [$ty:ty] [$($where:tt)*]
+        { $($component:ident : $component_type:ty),+ }
+        { $($resource:ident : $resource_type:ty),* }
+    ) => {
+        impl $($impl_generics)* $crate::any_storage::ComponentStorage for $ty where $($where)* {
+            fn get<__C>(&self) -> Option<&__C::Storage>
+            where
+                __C: std::any::Any + $crate::any_storage::Component,
+            {
+                use std::any::{Any, TypeId};
+                match TypeId::of::<__C>() {
+                    $(
+                        x if x == TypeId::of::<$component_type>() => (&self.$component as &dyn Any).downcast_ref(),
+                    )+
+                    _ => None
                 }
             }
 
-            fn get_mut<'a, T: 'a + Any + Component>(&'a mut self) -> Option<&'a mut T::Storage> {
-                use std::any::TypeId;
-                unsafe {
-                    match TypeId::of::<T>() {
-                        $(
-                            x if x == TypeId::of::<$component_type>() => Some(std::mem::transmute(&mut self.$component)),
-                        )+
-                        _ => None
-                    }
+            fn get_mut<'__a, __C>(&'__a mut self) -> Option<&'__a mut __C::Storage>
+            where
+                __C: '__a + std::any::Any + $crate::any_storage::Component,
+            {
+                use std::any::{Any, TypeId};
+                match TypeId::of::<__C>() {
+                    $(
+                        x if x == TypeId::of::<$component_type>() => (&mut self.$component as &mut dyn Any).downcast_mut(),
+                    )+
+                    _ => None
                 }
             }
-        }
-    };
-    ($name:ident < $($generic:ident),+ > { $($component:ident : $component_type:ty),+ }) => {
-        pub struct $name <$(generic),+> {
-            $(
-                pub $component: <$component_type as Component>::Storage,
-            )+
+
+            fn visit(&self, f: &mut dyn FnMut($crate::any_storage::ComponentInfo, &dyn std::any::Any)) {
+                $(
+                    f($crate::any_storage::ComponentInfo::of::<$component_type>(), &self.$component);
+                )+
+            }
+
+            fn visit_mut(&mut self, f: &mut dyn FnMut($crate::any_storage::ComponentInfo, &mut dyn std::any::Any)) {
+                $(
+                    f($crate::any_storage::ComponentInfo::of::<$component_type>(), &mut self.$component);
+                )+
+            }
         }
 
-        impl<$(generic),+> ComponentStorage for $name<$(generic),+> {
-            fn get<T: Any + Component>(&self) -> Option<&T::Storage> {
+        unsafe impl $($impl_generics)* $crate::any_storage::FetchStorage for $ty where $($where)* {
+            fn storage_ptrs(&mut self, ids: &[std::any::TypeId]) -> Vec<Option<*mut ()>> {
                 use std::any::TypeId;
-                unsafe {
-                    match TypeId::of::<T>() {
-                        $(
-                            x if x == TypeId::of::<$component_type>() => Some(std::mem::transmute(&self.$component)),
-                        )+
-                        _ => None
-                    }
+                ids.iter().map(|id| match *id {
+                    $(
+                        x if x == TypeId::of::<$component_type>() => Some(&mut self.$component as *mut _ as *mut ()),
+                    )+
+                    _ => None
+                }).collect()
+            }
+        }
+
+        impl $($impl_generics)* $crate::any_storage::ResourceStorage for $ty where $($where)* {
+            #[allow(unused_imports)]
+            fn get_resource<__R: std::any::Any>(&self) -> Option<&__R> {
+                use std::any::{Any, TypeId};
+                match TypeId::of::<__R>() {
+                    $(
+                        x if x == TypeId::of::<$resource_type>() => (&self.$resource as &dyn Any).downcast_ref(),
+                    )*
+                    _ => None
                 }
             }
 
-            fn get_mut<'a, T: 'a + Any + Component>(&'a mut self) -> Option<&'a mut T::Storage> {
-                use std::any::TypeId;
-                unsafe {
-                    match TypeId::of::<T>() {
-                        $(
-                            x if x == TypeId::of::<$component_type>() => Some(std::mem::transmute(&mut self.$component)),
-                        )+
-                        _ => None
-                    }
+            #[allow(unused_imports)]
+            fn get_resource_mut<__R: std::any::Any>(&mut self) -> Option<&mut __R> {
+                use std::any::{Any, TypeId};
+                match TypeId::of::<__R>() {
+                    $(
+                        x if x == TypeId::of::<$resource_type>() => (&mut self.$resource as &mut dyn Any).downcast_mut(),
+                    )*
+                    _ => None
                 }
             }
         }
     };
+    ($name:ident
+        { $($component:ident : $component_type:ty),+ $(,)? }
+        $(resources { $($resource:ident : $resource_type:ty),* $(,)? })?
+    ) => {
+        pub struct $name {
+            $(
+                pub $component: <$component_type as $crate::any_storage::Component>::Storage,
+            )+
+            $($(
+                pub $resource: $resource_type,
+            )*)?
+        }
+
+        $crate::create_storage!(
+            @impl [] [$name] []
+            { $($component : $component_type),+ }
+            { $($($resource : $resource_type),*)? }
+        );
+    };
+    ($name:ident < $($generic:ident $(: $bound:path)?),+ > where $($rest:tt)+) => {
+        $crate::create_storage!(@generic $name [$($generic $(: $bound)?),+] [$($generic),+] [] $($rest)+);
+    };
+    ($name:ident < $($generic:ident $(: $bound:path)?),+ > $($rest:tt)+) => {
+        $crate::create_storage!(@generic $name [$($generic $(: $bound)?),+] [$($generic),+] [] $($rest)+);
+    };
+}
+
+// Storages registered at runtime under the TypeId of their component, for when the set of
+// components isn't known up front (plugins, scripting)
+#[derive(Default)]
+pub struct DynComponentStorage {
+    storages: HashMap<TypeId, (ComponentInfo, Box<dyn Any>)>,
+    resources: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl DynComponentStorage {
+    pub fn new() -> Self {
+        Self { storages: HashMap::new(), resources: HashMap::new() }
+    }
+
+    // Returns the previous value of the resource, if any
+    pub fn insert_resource<R: Any>(&mut self, resource: R) -> Option<R> {
+        self.resources
+            .insert(TypeId::of::<R>(), Box::new(resource))
+            .and_then(|old| old.downcast().ok())
+            .map(|old| *old)
+    }
+
+    pub fn remove_resource<R: Any>(&mut self) -> Option<R> {
+        self.resources
+            .remove(&TypeId::of::<R>())
+            .and_then(|old| old.downcast().ok())
+            .map(|old| *old)
+    }
+
+    // Returns the storage previously registered for 'T', if any
+    pub fn register<T>(&mut self, storage: T::Storage) -> Option<T::Storage>
+    where
+        T: Any + Component,
+    {
+        self.storages
+            .insert(TypeId::of::<T>(), (ComponentInfo::of::<T>(), Box::new(storage)))
+            .and_then(|(_, old)| old.downcast().ok())
+            .map(|old| *old)
+    }
+
+    pub fn unregister<T>(&mut self) -> Option<T::Storage>
+    where
+        T: Any + Component,
+    {
+        self.storages
+            .remove(&TypeId::of::<T>())
+            .and_then(|(_, old)| old.downcast().ok())
+            .map(|old| *old)
+    }
+
+    pub fn is_registered<T: Any + Component>(&self) -> bool {
+        self.storages.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn len(&self) -> usize {
+        self.storages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.storages.is_empty()
+    }
+}
+
+impl ComponentStorage for DynComponentStorage {
+    fn get<T: Any + Component>(&self) -> Option<&T::Storage> {
+        self.storages.get(&TypeId::of::<T>())?.1.downcast_ref()
+    }
+
+    fn get_mut<'a, T: 'a + Any + Component>(&'a mut self) -> Option<&'a mut T::Storage> {
+        self.storages.get_mut(&TypeId::of::<T>())?.1.downcast_mut()
+    }
+
+    fn visit(&self, f: &mut dyn FnMut(ComponentInfo, &dyn Any)) {
+        for (info, storage) in self.storages.values() {
+            f(*info, &**storage);
+        }
+    }
+
+    fn visit_mut(&mut self, f: &mut dyn FnMut(ComponentInfo, &mut dyn Any)) {
+        for (info, storage) in self.storages.values_mut() {
+            f(*info, &mut **storage);
+        }
+    }
+}
+
+impl ResourceStorage for DynComponentStorage {
+    fn get_resource<R: Any>(&self) -> Option<&R> {
+        self.resources.get(&TypeId::of::<R>())?.downcast_ref()
+    }
+
+    fn get_resource_mut<R: Any>(&mut self) -> Option<&mut R> {
+        self.resources.get_mut(&TypeId::of::<R>())?.downcast_mut()
+    }
 }
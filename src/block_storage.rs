@@ -1,3 +1,4 @@
+use crate::alloc::{Alloc, AllocVec, Global};
 use std::{
     cell::UnsafeCell,
     collections::{BTreeSet, HashSet},
@@ -171,16 +172,17 @@ impl<'a, T> IndexMut<usize> for Block<'a, T> {
     }
 }
 
-pub struct BlockStorage<T> {
+/// The values are stored in memory from 'A', which defaults to the global allocator
+pub struct BlockStorage<T, A: Alloc = Global> {
     block_size: usize,
     generation: usize,
     active_keys: HashSet<InternalBlockKey>,
     available_blocks: BTreeSet<usize>,
     blocks: UnsafeCell<Vec<BlockIdx>>,
-    data: UnsafeCell<Vec<MaybeUninit<T>>>,
+    data: UnsafeCell<AllocVec<MaybeUninit<T>, A>>,
 }
 
-impl<T> Drop for BlockStorage<T> {
+impl<T, A: Alloc> Drop for BlockStorage<T, A> {
     fn drop(&mut self) {
         self.clear_data();
     }
@@ -188,16 +190,26 @@ impl<T> Drop for BlockStorage<T> {
 
 impl<T> BlockStorage<T> {
     pub fn new(block_size: usize) -> Self {
+        Self::new_in(block_size, Global)
+    }
+}
+
+impl<T, A: Alloc> BlockStorage<T, A> {
+    pub fn new_in(block_size: usize, alloc: A) -> Self {
         Self {
             block_size,
             generation: 0,
             active_keys: HashSet::new(),
             available_blocks: BTreeSet::new(),
             blocks: UnsafeCell::new(vec![]),
-            data: UnsafeCell::new(vec![]),
+            data: UnsafeCell::new(AllocVec::new_in(alloc)),
         }
     }
 
+    pub fn allocator(&self) -> &A {
+        unsafe { (*self.data.get()).allocator() }
+    }
+
     fn clear_data(&mut self) {
         let blocks = unsafe { &mut *self.blocks.get() };
         let data = unsafe { &mut *self.data.get() };
@@ -728,5 +740,50 @@ mod test {
         assert!(idx4.blocks == 2);
 
         assert!(storage.available_blocks == BTreeSet::new());
-    }  
-}
\ No newline at end of file
+    }
+
+    // Counts the bytes currently handed out
+    #[derive(Default)]
+    struct TrackedHeap {
+        allocated: std::cell::Cell<usize>,
+    }
+
+    unsafe impl crate::alloc::Alloc for TrackedHeap {
+        fn allocate(&self, layout: std::alloc::Layout) -> *mut u8 {
+            self.allocated.set(self.allocated.get() + layout.size());
+            unsafe { std::alloc::alloc(layout) }
+        }
+
+        unsafe fn deallocate(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            self.allocated.set(self.allocated.get() - layout.size());
+            std::alloc::dealloc(ptr, layout)
+        }
+    }
+
+    #[test]
+    fn allocator_test() {
+        let heap = TrackedHeap::default();
+        let value = Arc::new(AtomicI32::new(0));
+
+        {
+            let mut storage = BlockStorage::<DropTest, _>::new_in(4, &heap);
+            let idx1 = storage.create(4);
+            let idx2 = storage.create(8);
+
+            assert!(heap.allocated.get() >= 12 * std::mem::size_of::<DropTest>());
+
+            let mut block1 = storage.get(idx1).unwrap();
+            block1.push(DropTest::new(value.clone()));
+
+            let mut block2 = storage.get(idx2).unwrap();
+            block2.push(DropTest::new(value.clone()));
+            block2.push(DropTest::new(value.clone()));
+
+            assert!(block2.as_slice().len() == 2);
+            assert!(value.load(Ordering::SeqCst) == 3);
+        }
+
+        assert!(value.load(Ordering::SeqCst) == 0);
+        assert!(heap.allocated.get() == 0);
+    }
+}
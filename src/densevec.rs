@@ -0,0 +1,204 @@
+use crate::{novec::NoVec, *};
+
+// Keeps its values packed together for iteration, removals swap the last value into the gap.
+// Indices go through a NoVec of positions so they stay valid while values move around, and
+// freed indices are handed out again the same way a NoVec does
+#[derive(Clone, Debug)]
+pub struct DenseVec<T> {
+    values: Vec<T>,
+    // The index each value is stored under, in the same order as 'values'
+    indices: Vec<usize>,
+    // Where in 'values' each index points
+    positions: NoVec<usize>,
+}
+
+impl<T> Default for DenseVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> DenseVec<T> {
+    pub fn new() -> Self {
+        Self { values: vec![], indices: vec![], positions: NoVec::new() }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            values: Vec::with_capacity(capacity),
+            indices: Vec::with_capacity(capacity),
+            positions: NoVec::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    // The index the next push will use
+    pub fn next_id(&self) -> usize {
+        self.positions.next_id()
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.positions.get(index).is_some()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let position = *self.positions.get(index)?;
+
+        Some(&self.values[position])
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let position = *self.positions.get(index)?;
+
+        Some(&mut self.values[position])
+    }
+
+    pub fn push(&mut self, value: T) -> usize {
+        let index = self.positions.push(self.values.len());
+        self.values.push(value);
+        self.indices.push(index);
+
+        index
+    }
+
+    // Places the value under the index whether or not it's in use, a new value goes on the end
+    pub fn insert_at(&mut self, index: usize, value: T) -> Option<T> {
+        if let Some(&position) = self.positions.get(index) {
+            return Some(std::mem::replace(&mut self.values[position], value));
+        }
+
+        self.positions.insert_at(index, self.values.len());
+        self.values.push(value);
+        self.indices.push(index);
+
+        None
+    }
+
+    // The last value is moved into the gap, its index is repointed so it stays valid
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        let position = self.positions.remove(index)?;
+        let value = self.values.swap_remove(position);
+        self.indices.swap_remove(position);
+
+        if let Some(&moved) = self.indices.get(position) {
+            *self.positions.get_mut(moved).unwrap() = position;
+        }
+
+        Some(value)
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.indices.clear();
+        self.positions.clear();
+    }
+
+    // In storage order, which changes as values are removed
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.indices.iter().copied().zip(self.values.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> {
+        self.indices.iter().copied().zip(self.values.iter_mut())
+    }
+
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    pub fn values_mut(&mut self) -> &mut [T] {
+        &mut self.values
+    }
+
+    // The indices of the values, lined up with 'values'
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+}
+
+#[cfg(feature = "testing")]
+impl<T> crate::testing::CheckInvariants for DenseVec<T> {
+    fn check_invariants(&self) -> Result<(), String> {
+        self.positions.check_invariants()?;
+
+        if self.indices.len() != self.values.len() || self.positions.len() != self.values.len() {
+            return Err(format!(
+                "Holds {} values but {} indices and {} positions",
+                self.values.len(),
+                self.indices.len(),
+                self.positions.len()
+            ));
+        }
+
+        for (position, index) in self.indices.iter().enumerate() {
+            if self.positions.get(*index) != Some(&position) {
+                return Err(format!("Index {} doesn't point back to position {}", index, position));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T> crate::par::ParStorage for DenseVec<T> {
+    fn par_iter<'a>(&'a self) -> impl rayon::iter::ParallelIterator<Item = (usize, &'a T)> + 'a
+    where
+        T: Sync + 'a,
+    {
+        use rayon::prelude::*;
+
+        self.indices.par_iter().copied().zip(self.values.par_iter())
+    }
+
+    fn par_iter_mut<'a>(
+        &'a mut self,
+    ) -> impl rayon::iter::ParallelIterator<Item = (usize, &'a mut T)> + 'a
+    where
+        T: Send + 'a,
+    {
+        use rayon::prelude::*;
+
+        self.indices.par_iter().copied().zip(self.values.par_iter_mut())
+    }
+}
+
+impl<T> UnorderedStorage for DenseVec<T> {
+    type Index = usize;
+    type Item = T;
+
+    fn insert(&mut self, index: usize, value: T) -> Option<T> {
+        self.insert_at(index, value)
+    }
+
+    fn remove(&mut self, index: &usize) -> Option<T> {
+        <DenseVec<T>>::remove(self, *index)
+    }
+
+    fn get(&self, index: &usize) -> Option<&T> {
+        <DenseVec<T>>::get(self, *index)
+    }
+
+    fn get_mut(&mut self, index: &usize) -> Option<&mut T> {
+        <DenseVec<T>>::get_mut(self, *index)
+    }
+}
+
+impl<T> ExpandableStorage for DenseVec<T> {
+    fn push(&mut self, value: T) -> usize {
+        self.push(value)
+    }
+}
+
+impl<T> WithCapacity for DenseVec<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        DenseVec::with_capacity(capacity)
+    }
+}
@@ -1,9 +1,33 @@
 use crate::{idvec::IdVecIndex, *};
+use std::{
+    collections::HashSet,
+    io::{self, Read, Write},
+    iter::Enumerate,
+    convert::TryFrom,
+    num::{NonZeroU32, NonZeroU64},
+    vec,
+};
+
+// Generations start at 1 so they can be non zero, which lets Option<StorageId> be the same size
+// as a StorageId
+pub const FIRST_GENERATION: NonZeroU64 = NonZeroU64::MIN;
 
 #[derive(Copy, Clone, Debug, PartialEq, Hash, Eq)]
 pub struct StorageId {
     pub index: usize,
-    pub generation: u64,
+    pub generation: NonZeroU64,
+}
+
+impl StorageId {
+    // The id the first value stored at the index is given, in a storage that's never been shrunk
+    pub fn first(index: usize) -> StorageId {
+        StorageId { index, generation: FIRST_GENERATION }
+    }
+}
+
+// Wraps back around to the first generation rather than to zero
+fn next_generation(generation: NonZeroU64) -> NonZeroU64 {
+    NonZeroU64::new(generation.get().wrapping_add(1)).unwrap_or(FIRST_GENERATION)
 }
 
 impl Into<IdVecIndex> for StorageId {
@@ -14,25 +38,25 @@ impl Into<IdVecIndex> for StorageId {
 
 #[derive(Copy, Clone, Debug)]
 pub struct StorageObject<T> {
-    generation: u64,
+    generation: NonZeroU64,
     item: Option<T>,
 }
 
 impl<T> StorageObject<T> {
     pub fn new(item: T) -> StorageObject<T> {
-        StorageObject { item: Some(item), generation: 0 }
+        StorageObject { item: Some(item), generation: FIRST_GENERATION }
     }
 
-    pub fn empty(generation: u64) -> StorageObject<T> {
+    pub fn empty(generation: NonZeroU64) -> StorageObject<T> {
         StorageObject { generation, item: None }
     }
 
-    pub fn generation(&self) -> u64 {
+    pub fn generation(&self) -> NonZeroU64 {
         self.generation
     }
 
     pub fn increase_generation(&mut self) {
-        self.generation = self.generation.wrapping_add(1);
+        self.generation = next_generation(self.generation);
     }
 
     pub fn is_some(&self) -> bool {
@@ -82,10 +106,126 @@ impl<T> StorageObject<T> {
     }
 }
 
+const NOT_FREE: usize = usize::MAX;
+
+// The free slots as a stack with the most recently freed on top. Every slot's place in the stack
+// is kept alongside it so any free slot can be taken out in constant time, not just the top one
+#[derive(Clone, Debug, Default)]
+struct FreeList {
+    stack: Vec<usize>,
+    // Indexed by slot, 'NOT_FREE' for slots that aren't in the stack
+    positions: Vec<usize>,
+}
+
+impl std::ops::Deref for FreeList {
+    type Target = [usize];
+
+    fn deref(&self) -> &[usize] {
+        &self.stack
+    }
+}
+
+impl FreeList {
+    // None if a slot is listed twice
+    fn from_stack(stack: Vec<usize>) -> Option<FreeList> {
+        let mut free = FreeList { stack: Vec::with_capacity(stack.len()), positions: vec![] };
+
+        for index in stack {
+            if free.contains(index) {
+                return None;
+            }
+
+            free.push(index);
+        }
+
+        Some(free)
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        matches!(self.positions.get(index), Some(position) if *position != NOT_FREE)
+    }
+
+    fn push(&mut self, index: usize) {
+        if index >= self.positions.len() {
+            self.positions.resize(index + 1, NOT_FREE);
+        }
+
+        self.positions[index] = self.stack.len();
+        self.stack.push(index);
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        let index = self.stack.pop()?;
+        self.positions[index] = NOT_FREE;
+
+        Some(index)
+    }
+
+    // The top of the stack is moved into the gap, returns whether the slot was free
+    fn remove(&mut self, index: usize) -> bool {
+        if !self.contains(index) {
+            return false;
+        }
+
+        let position = std::mem::replace(&mut self.positions[index], NOT_FREE);
+        self.stack.swap_remove(position);
+
+        if let Some(moved) = self.stack.get(position) {
+            self.positions[*moved] = position;
+        }
+
+        true
+    }
+
+    // Drops every slot at or past len
+    fn truncate(&mut self, len: usize) {
+        self.stack.retain(|index| *index < len);
+        self.positions.truncate(len);
+
+        for (position, index) in self.stack.iter().enumerate() {
+            self.positions[*index] = position;
+        }
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.stack.reserve(additional);
+        self.positions.reserve(additional);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.stack.shrink_to_fit();
+        self.positions.shrink_to_fit();
+    }
+
+    fn heap_bytes(&self) -> usize {
+        (self.stack.capacity() + self.positions.capacity()) * std::mem::size_of::<usize>()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GenerationStats {
+    // Slots holding an item
+    pub live: usize,
+    // Empty slots, whether they're free or reserved
+    pub tombstones: usize,
+    pub reserved: usize,
+    // None until a slot has been created
+    pub highest_generation: Option<NonZeroU64>,
+    // What the storage's own allocations take up, anything the items point to isn't counted
+    pub heap_bytes: usize,
+}
+
 #[derive(Clone, Debug)]
 pub struct GenerationStorage<T> {
     objects: Vec<StorageObject<T>>,
-    available: Vec<usize>,
+    available: FreeList,
+    // The number of slots holding an item
+    len: usize,
+    // Empty slots that have been handed out by reserve_id and are waiting for their value
+    reserved: HashSet<usize>,
+    // The generation new slots start at, kept past that of any slot shrink_to_fit dropped so
+    // the ids it handed out can't become valid again when the slot is recreated
+    fresh_generation: NonZeroU64,
 }
 
 impl<T> Default for GenerationStorage<T> {
@@ -96,96 +236,260 @@ impl<T> Default for GenerationStorage<T> {
 
 impl<T> GenerationStorage<T> {
     pub fn new() -> GenerationStorage<T> {
-        GenerationStorage { objects: vec![], available: vec![] }
+        GenerationStorage::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> GenerationStorage<T> {
+        GenerationStorage {
+            objects: Vec::with_capacity(capacity),
+            available: FreeList::default(),
+            len: 0,
+            reserved: HashSet::new(),
+            fresh_generation: FIRST_GENERATION,
+        }
+    }
+
+    // Room for n more slots without reallocating, the free list is grown to match so removing
+    // them again doesn't allocate either
+    pub fn reserve(&mut self, n: usize) {
+        self.objects.reserve(n);
+        self.available.reserve(n);
+    }
+
+    // Drops the empty slots at the back before shrinking the allocations, reserved slots are
+    // kept. Ids of items still held don't change
+    pub fn shrink_to_fit(&mut self) {
+        while let Some(object) = self.objects.last() {
+            let index = self.objects.len() - 1;
+
+            if object.is_some() || self.reserved.contains(&index) {
+                break;
+            }
+
+            // Emptying the slot moved it on a generation that hasn't been handed out yet
+            self.fresh_generation = self.fresh_generation.max(object.generation);
+            self.objects.pop();
+        }
+
+        self.available.truncate(self.objects.len());
+
+        self.objects.shrink_to_fit();
+        self.available.shrink_to_fit();
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Walks every slot to find the highest generation
+    pub fn stats(&self) -> GenerationStats {
+        use std::mem::size_of;
+
+        let heap_bytes = self.objects.capacity() * size_of::<StorageObject<T>>()
+            + self.available.heap_bytes()
+            + self.reserved.capacity() * (size_of::<usize>() + 1);
+
+        GenerationStats {
+            live: self.len,
+            tombstones: self.objects.len() - self.len,
+            reserved: self.reserved.len(),
+            highest_generation: self.objects.iter().map(|object| object.generation).max(),
+            heap_bytes,
+        }
+    }
+
+    // Slots waiting to be reused by a push, reserved slots aren't counted
+    pub fn free_slots(&self) -> usize {
+        self.available.len()
     }
 
     // Returns what index would be given to an object after n insertions if no deletion occur
     pub fn nth_available(&self, n: usize) -> StorageId {
         if n < self.available.len() {
             let index = self.available[self.available.len() - 1 - n];
-            let generation = self.objects[index].generation + 1;
 
-            return StorageId { index, generation };
+            return StorageId { index, generation: self.objects[index].generation };
         }
 
         let overflow = n - self.available.len();
         let index = self.objects.len() + overflow;
 
-        StorageId { index, generation: 0 }
+        StorageId { index, generation: self.fresh_generation }
     }
 
     pub fn clear(&mut self) {
-        for (i, item) in self.objects.iter_mut().filter(|item| item.is_some()).enumerate() {
+        for (i, item) in self.objects.iter_mut().enumerate().filter(|(_, item)| item.is_some()) {
             item.remove();
+            item.increase_generation();
             self.available.push(i);
         }
+
+        self.len = 0;
     }
 
+    // Returns the value that was displaced. An id older than the generation already in its slot
+    // is stale and the item is handed straight back instead of being inserted, use 'try_insert'
+    // to tell the two apart
     pub fn insert(&mut self, id: StorageId, item: T) -> Option<T> {
+        self.try_insert(id, item).unwrap_or_else(Some)
+    }
+
+    // Err holds the item when the id is stale. Removing moves the slot on a generation, so the id
+    // of a removed item is stale too and can't bring it back
+    pub fn try_insert(&mut self, id: StorageId, item: T) -> Result<Option<T>, T> {
+        if self.is_stale(id) {
+            return Err(item);
+        }
+
+        Ok(self.insert_force(id, item))
+    }
+
+    // Whether the id is older than the generation in its slot, insert turns those away. Past the
+    // end it's compared with the generation new slots start at, shrink_to_fit moves that past
+    // every slot it drops
+    pub fn is_stale(&self, id: StorageId) -> bool {
+        match self.objects.get(id.index) {
+            Some(object) => id.generation < object.generation,
+            None => id.generation < self.fresh_generation,
+        }
+    }
+
+    // Inserts no matter what generation is in the slot, returning the value that was there
+    pub fn insert_force(&mut self, id: StorageId, item: T) -> Option<T> {
         if id.index >= self.objects.len() {
             self.fill_to(id.index + 1);
-            self.available.pop();
+            self.available.remove(id.index);
             let object = &mut self.objects[id.index];
             object.item = Some(item);
             object.generation = id.generation;
+            self.len += 1;
 
             return None;
         }
 
         let object = &mut self.objects[id.index];
 
-        if object.is_none() {
-            if let Some(position) = self.available.iter().position(|a| *a == id.index) {
-                self.available.swap_remove(position);
-            }
+        if object.is_none() && !self.reserved.remove(&id.index) {
+            self.available.remove(id.index);
         }
 
-        object.item = Some(item);
         object.generation = id.generation;
 
-        None
+        let replaced = object.item.replace(item);
+        if replaced.is_none() {
+            self.len += 1;
+        }
+
+        replaced
     }
 
     pub fn push(&mut self, item: T) -> StorageId {
+        self.len += 1;
+
         match self.available.pop() {
             Some(id) => {
-                self.objects[id].increase_generation();
                 self.objects[id].insert(item);
 
                 StorageId { index: id, generation: self.objects[id].generation() }
             }
             None => {
                 let id = self.objects.len();
-                let object = StorageObject::new(item);
+                let object = StorageObject { generation: self.fresh_generation, item: Some(item) };
                 self.objects.push(object);
 
-                StorageId { index: id, generation: 0 }
+                StorageId { index: id, generation: self.fresh_generation }
             }
         }
     }
 
-    pub fn remove(&mut self, id: usize) -> Option<T> {
-        if id < self.objects.len() {
-            if self.objects[id].is_some() {
-                self.available.push(id);
+    // Claims the id the next push would get without storing anything, get returns None for it
+    // until it's filled with insert_reserved
+    pub fn reserve_id(&mut self) -> StorageId {
+        let index = match self.available.pop() {
+            Some(index) => index,
+            None => {
+                self.objects.push(StorageObject::empty(self.fresh_generation));
+                self.objects.len() - 1
             }
+        };
+
+        self.reserved.insert(index);
+
+        StorageId { index, generation: self.objects[index].generation() }
+    }
+
+    // Claims the ids the next n pushes would get, the same ones nth_available(0..n) predicts.
+    // Recycled slots come first, then fresh ones
+    pub fn reserve_ids(&mut self, n: usize) -> Vec<StorageId> {
+        self.objects.reserve(n.saturating_sub(self.available.len()));
+        self.reserved.reserve(n);
+
+        (0..n).map(|_| self.reserve_id()).collect()
+    }
 
-            return self.objects[id].remove();
+    // Err hands the item back if the id isn't a current reservation
+    pub fn insert_reserved(&mut self, id: StorageId, item: T) -> Result<(), T> {
+        if !self.is_reserved(id) {
+            return Err(item);
         }
 
-        None
+        self.reserved.remove(&id.index);
+        self.objects[id.index].insert(item);
+        self.len += 1;
+
+        Ok(())
+    }
+
+    pub fn is_reserved(&self, id: StorageId) -> bool {
+        self.reserved.contains(&id.index) && self.objects[id.index].generation() == id.generation
+    }
+
+    // Gives a reserved slot back to be reused, returning whether the id was reserved
+    pub fn cancel_reservation(&mut self, id: StorageId) -> bool {
+        if !self.is_reserved(id) {
+            return false;
+        }
+
+        self.reserved.remove(&id.index);
+        self.objects[id.index].increase_generation();
+        self.available.push(id.index);
+
+        true
+    }
+
+    // The slot moves on a generation so the id of the removed item goes stale straight away
+    pub fn remove(&mut self, id: usize) -> Option<T> {
+        let object = self.objects.get_mut(id)?;
+        let removed = object.remove()?;
+
+        object.increase_generation();
+        self.available.push(id);
+        self.len -= 1;
+
+        Some(removed)
     }
 
     pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
-        for (id, object) in self.objects.iter_mut().enumerate() {
-            match &object.item {
-                Some(item) => {
-                    if !f(item) {
-                        object.remove();
-                        self.available.push(id);
-                    }
+        self.retain_with_ids(|_, item| f(item))
+    }
+
+    // Same as retain but hands over the id of every item, so the ids of the removed ones can be
+    // passed on. Those ids go stale straight away, just like with remove_id
+    pub fn retain_with_ids<F: FnMut(StorageId, &mut T) -> bool>(&mut self, mut f: F) {
+        for (index, object) in self.objects.iter_mut().enumerate() {
+            let id = StorageId { index, generation: object.generation };
+
+            if let Some(item) = &mut object.item {
+                if !f(id, item) {
+                    object.remove();
+                    object.increase_generation();
+                    self.available.push(index);
+                    self.len -= 1;
                 }
-                None => {}
             }
         }
     }
@@ -198,6 +502,48 @@ impl<T> GenerationStorage<T> {
         None
     }
 
+    // Moves the items in the highest slots down into the lowest free ones, so the empty slots end
+    // up at the back. Every move is reported with the item's old and new id, the old id is stale
+    // afterwards. Reserved slots are left where they are
+    pub fn compact<F: FnMut(StorageId, StorageId)>(&mut self, mut on_move: F) {
+        let mut low = 0;
+        let mut high = self.objects.len();
+
+        loop {
+            while low < high && (self.objects[low].is_some() || self.reserved.contains(&low)) {
+                low += 1;
+            }
+
+            while high > low && self.objects[high - 1].is_none() {
+                high -= 1;
+            }
+
+            if low >= high {
+                break;
+            }
+
+            let from = high - 1;
+            let item = self.objects[from].remove().unwrap();
+            let old = StorageId { index: from, generation: self.objects[from].generation };
+            self.objects[from].increase_generation();
+
+            // The free slot already moved on a generation when it was emptied
+            let object = &mut self.objects[low];
+            object.insert(item);
+
+            on_move(old, StorageId { index: low, generation: object.generation });
+        }
+
+        // Rebuilt so the lowest free slot is reused first
+        let objects = &self.objects;
+        let reserved = &self.reserved;
+        let free = (0..objects.len())
+            .rev()
+            .filter(|index| objects[*index].is_none() && !reserved.contains(index))
+            .collect();
+        self.available = FreeList::from_stack(free).unwrap();
+    }
+
     pub fn contains(&self, id: StorageId) -> bool {
         self.get(id).is_some()
     }
@@ -230,17 +576,67 @@ impl<T> GenerationStorage<T> {
         None
     }
 
-    pub fn get_unchecked(&self, idx: usize) -> Option<&T> {
+    // Exchanges the items of two ids in place, the slots keep their generations. False if either
+    // id isn't current
+    pub fn swap_values(&mut self, a: StorageId, b: StorageId) -> bool {
+        if a.index == b.index {
+            return self.contains(a) && self.contains(b);
+        }
+
+        match self.get_disjoint_mut([a, b]) {
+            Some([a, b]) => {
+                std::mem::swap(a, b);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Mutable references to several items at once, None if any id is stale or two share a slot
+    pub fn get_disjoint_mut<const N: usize>(
+        &mut self,
+        ids: [StorageId; N],
+    ) -> Option<[&mut T; N]> {
+        if !ids.iter().all(|id| self.contains(*id)) {
+            return None;
+        }
+
+        let objects = self.objects.get_disjoint_mut(ids.map(|id| id.index)).ok()?;
+
+        Some(objects.map(|object| object.unwrap_mut()))
+    }
+
+    // Gets whatever is stored at idx regardless of its generation
+    pub fn get_any_generation(&self, idx: usize) -> Option<&T> {
         self.objects.get(idx).map(|value| value.item.as_ref()).flatten()
     }
 
-    pub fn get_mut_unchecked(&mut self, idx: usize) -> Option<&mut T> {
+    pub fn get_any_generation_mut(&mut self, idx: usize) -> Option<&mut T> {
         self.objects.get_mut(idx).map(|value| value.item.as_mut()).flatten()
     }
 
+    /// # Safety
+    /// The id must refer to an item that is currently stored, no bounds or generation checks are
+    /// made outside of debug builds
+    pub unsafe fn get_unchecked(&self, id: StorageId) -> &T {
+        debug_assert!(self.get(id).is_some(), "Invalid id {:?}", id);
+
+        let object = self.objects.get_unchecked(id.index);
+        object.item.as_ref().unwrap_unchecked()
+    }
+
+    /// # Safety
+    /// Same as get_unchecked
+    pub unsafe fn get_unchecked_mut(&mut self, id: StorageId) -> &mut T {
+        debug_assert!(self.get(id).is_some(), "Invalid id {:?}", id);
+
+        let object = self.objects.get_unchecked_mut(id.index);
+        object.item.as_mut().unwrap_unchecked()
+    }
+
     pub fn fill_to(&mut self, size: usize) {
         for i in self.objects.len()..size {
-            self.objects.push(StorageObject::empty(0));
+            self.objects.push(StorageObject::empty(self.fresh_generation));
             self.available.push(i);
         }
     }
@@ -282,6 +678,286 @@ impl<T> GenerationStorage<T> {
     }
 }
 
+// Moves the items out along with their ids, so they can be carried over to another storage
+pub struct IntoIter<T> {
+    objects: Enumerate<vec::IntoIter<StorageObject<T>>>,
+    remaining: usize,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = (StorageId, T);
+
+    fn next(&mut self) -> Option<(StorageId, T)> {
+        let next = self.objects.find_map(|(index, object)| {
+            let id = StorageId { index, generation: object.generation };
+            object.item.map(|item| (id, item))
+        })?;
+        self.remaining -= 1;
+
+        Some(next)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for GenerationStorage<T> {
+    type Item = (StorageId, T);
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { objects: self.objects.into_iter().enumerate(), remaining: self.len }
+    }
+}
+
+// Snapshot layout, all integers little endian:
+// magic "GENS", version u32, generation new slots start at u64, slot count u64,
+// free slot count u64, free slots u64 each, then per slot its generation u64 and a u8 that is 1
+// when it's followed by an item
+const SNAPSHOT_MAGIC: &[u8; 4] = b"GENS";
+const SNAPSHOT_VERSION: u32 = 1;
+// The counts in a snapshot's header aren't trusted, past this many slots the storage grows as
+// they're read instead
+const SNAPSHOT_PREALLOCATED_SLOTS: usize = 4096;
+
+impl<T> GenerationStorage<T> {
+    // Writes every slot with its generation along with the free slots in the order they'll be
+    // reused, so a restored storage hands out the same StorageIds as this one
+    pub fn write_snapshot<W, F>(&self, mut writer: W, mut write_item: F) -> io::Result<()>
+    where
+        W: Write,
+        F: FnMut(&mut W, &T) -> io::Result<()>,
+    {
+        writer.write_all(SNAPSHOT_MAGIC)?;
+        writer.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+        writer.write_all(&self.fresh_generation.get().to_le_bytes())?;
+        writer.write_all(&(self.objects.len() as u64).to_le_bytes())?;
+        writer.write_all(&(self.available.len() as u64).to_le_bytes())?;
+
+        for index in self.available.iter() {
+            writer.write_all(&(*index as u64).to_le_bytes())?;
+        }
+
+        for object in &self.objects {
+            writer.write_all(&object.generation.get().to_le_bytes())?;
+
+            match &object.item {
+                Some(item) => {
+                    writer.write_all(&[1])?;
+                    write_item(&mut writer, item)?;
+                }
+                None => writer.write_all(&[0])?,
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn read_snapshot<R, F>(mut reader: R, mut read_item: F) -> io::Result<Self>
+    where
+        R: Read,
+        F: FnMut(&mut R) -> io::Result<T>,
+    {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+
+        if &magic != SNAPSHOT_MAGIC {
+            return Err(invalid("not a GenerationStorage snapshot"));
+        }
+
+        let version = read_u32(&mut reader)?;
+
+        if version != SNAPSHOT_VERSION {
+            return Err(invalid("unsupported snapshot version"));
+        }
+
+        let fresh_generation =
+            NonZeroU64::new(read_u64(&mut reader)?).ok_or_else(|| invalid("zero generation"))?;
+
+        let slots = read_u64(&mut reader)? as usize;
+        let free = read_u64(&mut reader)? as usize;
+
+        let mut available = Vec::with_capacity(free.min(slots).min(SNAPSHOT_PREALLOCATED_SLOTS));
+        for _ in 0..free {
+            let index = read_u64(&mut reader)? as usize;
+
+            if index >= slots {
+                return Err(invalid("free slot out of bounds"));
+            }
+
+            available.push(index);
+        }
+
+        let mut objects = Vec::with_capacity(slots.min(SNAPSHOT_PREALLOCATED_SLOTS));
+        for _ in 0..slots {
+            let generation =
+                NonZeroU64::new(read_u64(&mut reader)?).ok_or_else(|| invalid("zero generation"))?;
+
+            let mut present = [0];
+            reader.read_exact(&mut present)?;
+
+            let item = match present[0] {
+                0 => None,
+                1 => Some(read_item(&mut reader)?),
+                _ => return Err(invalid("invalid slot marker")),
+            };
+
+            objects.push(StorageObject { generation, item });
+        }
+
+        if available.iter().any(|index| objects[*index].is_some()) {
+            return Err(invalid("occupied slot listed as free"));
+        }
+
+        let available =
+            FreeList::from_stack(available).ok_or_else(|| invalid("slot listed as free twice"))?;
+
+        // Empty slots that aren't free were reserved when the snapshot was taken
+        let reserved =
+            (0..slots).filter(|i| objects[*i].is_none() && !available.contains(*i)).collect();
+
+        let len = objects.iter().filter(|object| object.is_some()).count();
+
+        Ok(GenerationStorage { objects, available, len, reserved, fresh_generation })
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u64::from_le_bytes(bytes))
+}
+
+// Every empty slot has to be either free or reserved, and never both
+#[cfg(feature = "testing")]
+impl<T> crate::testing::CheckInvariants for GenerationStorage<T> {
+    fn check_invariants(&self) -> Result<(), String> {
+        let occupied = self.objects.iter().filter(|object| object.is_some()).count();
+
+        if self.len != occupied {
+            return Err(format!("Counted {} items but {} slots hold one", self.len, occupied));
+        }
+
+        let positions = &self.available.positions;
+        let listed = positions.iter().filter(|position| **position != NOT_FREE).count();
+
+        for (position, index) in self.available.iter().enumerate() {
+            if positions.get(*index) != Some(&position) {
+                let message = format!("Free slot {} doesn't know its place {} in the list", index, position);
+                return Err(message);
+            }
+        }
+
+        if listed != self.available.len() {
+            let free = self.available.len();
+            return Err(format!("{} slots think they're free but {} are", listed, free));
+        }
+
+        let mut accounted = vec![false; self.objects.len()];
+
+        for index in self.available.iter().chain(self.reserved.iter()) {
+            match self.objects.get(*index) {
+                Some(object) if object.is_some() => {
+                    return Err(format!("Slot {} is free or reserved but holds an item", index))
+                }
+                Some(_) if accounted[*index] => {
+                    return Err(format!("Slot {} is listed as free or reserved twice", index))
+                }
+                Some(_) => accounted[*index] = true,
+                None => return Err(format!("Free or reserved slot {} is out of bounds", index)),
+            }
+        }
+
+        match self.objects.iter().zip(accounted).position(|(object, free)| object.is_none() && !free) {
+            Some(index) => Err(format!("Slot {} is empty but can never be reused", index)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T> GenerationStorage<T> {
+    pub fn par_iter_with_ids(&self) -> impl rayon::iter::ParallelIterator<Item = (StorageId, &T)>
+    where
+        T: Sync,
+    {
+        use rayon::prelude::*;
+
+        self.objects.par_iter().enumerate().filter(|(_, x)| x.is_some()).map(|(i, x)| {
+            let id = StorageId { index: i, generation: x.generation() };
+
+            (id, x.unwrap_ref())
+        })
+    }
+
+    pub fn par_iter_with_ids_mut(
+        &mut self,
+    ) -> impl rayon::iter::ParallelIterator<Item = (StorageId, &mut T)>
+    where
+        T: Send,
+    {
+        use rayon::prelude::*;
+
+        self.objects.par_iter_mut().enumerate().filter(|(_, x)| x.is_some()).map(|(i, x)| {
+            let id = StorageId { index: i, generation: x.generation() };
+
+            (id, x.unwrap_mut())
+        })
+    }
+
+    pub fn par_values(&self) -> impl rayon::iter::ParallelIterator<Item = &T>
+    where
+        T: Sync,
+    {
+        use rayon::prelude::*;
+
+        self.objects.par_iter().filter_map(|x| x.item())
+    }
+
+    pub fn par_values_mut(&mut self) -> impl rayon::iter::ParallelIterator<Item = &mut T>
+    where
+        T: Send,
+    {
+        use rayon::prelude::*;
+
+        self.objects.par_iter_mut().filter_map(|x| x.item_mut())
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T> crate::par::ParStorage for GenerationStorage<T> {
+    fn par_iter<'a>(
+        &'a self,
+    ) -> impl rayon::iter::ParallelIterator<Item = (StorageId, &'a T)> + 'a
+    where
+        T: Sync + 'a,
+    {
+        self.par_iter_with_ids()
+    }
+
+    fn par_iter_mut<'a>(
+        &'a mut self,
+    ) -> impl rayon::iter::ParallelIterator<Item = (StorageId, &'a mut T)> + 'a
+    where
+        T: Send + 'a,
+    {
+        self.par_iter_with_ids_mut()
+    }
+}
+
 impl<T> UnorderedStorage for GenerationStorage<T> {
     type Index = StorageId;
     type Item = T;
@@ -290,6 +966,14 @@ impl<T> UnorderedStorage for GenerationStorage<T> {
         <GenerationStorage<T>>::insert(self, index, value)
     }
 
+    fn accepts(&self, index: &StorageId) -> bool {
+        !self.is_stale(*index)
+    }
+
+    fn swap_values(&mut self, a: &StorageId, b: &StorageId) -> bool {
+        <GenerationStorage<T>>::swap_values(self, *a, *b)
+    }
+
     fn remove(&mut self, index: &StorageId) -> Option<T> {
         self.remove_id(*index)
     }
@@ -307,4 +991,376 @@ impl<T> ExpandableStorage for GenerationStorage<T> {
     fn push(&mut self, value: T) -> StorageId {
         self.push(value)
     }
-}
\ No newline at end of file
+}
+
+impl<T> WithCapacity for GenerationStorage<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        GenerationStorage::with_capacity(capacity)
+    }
+}
+
+// A StorageId that fits in 8 bytes, the generation is in the high 32 bits and the index in the
+// low ones. It's never zero so an Option of one is 8 bytes too
+#[derive(Copy, Clone, Debug, PartialEq, Hash, Eq)]
+pub struct PackedStorageId(NonZeroU64);
+
+impl PackedStorageId {
+    pub fn new(index: u32, generation: NonZeroU32) -> PackedStorageId {
+        let bits = (u64::from(generation.get()) << 32) | u64::from(index);
+
+        PackedStorageId(NonZeroU64::new(bits).unwrap())
+    }
+
+    // None if the bits have a zero generation
+    pub fn from_bits(bits: u64) -> Option<PackedStorageId> {
+        match bits >> 32 {
+            0 => None,
+            _ => NonZeroU64::new(bits).map(PackedStorageId),
+        }
+    }
+
+    pub fn to_bits(self) -> u64 {
+        self.0.get()
+    }
+
+    pub fn index(self) -> usize {
+        self.0.get() as u32 as usize
+    }
+
+    pub fn generation(self) -> NonZeroU32 {
+        NonZeroU32::new((self.0.get() >> 32) as u32).unwrap()
+    }
+}
+
+impl From<PackedStorageId> for StorageId {
+    fn from(id: PackedStorageId) -> StorageId {
+        let generation = NonZeroU64::from(id.generation());
+
+        StorageId { index: id.index(), generation }
+    }
+}
+
+// Fails when the index or generation doesn't fit in 32 bits
+impl TryFrom<StorageId> for PackedStorageId {
+    type Error = StorageId;
+
+    fn try_from(id: StorageId) -> Result<PackedStorageId, StorageId> {
+        let index = u32::try_from(id.index).map_err(|_| id)?;
+        let generation = NonZeroU32::try_from(id.generation).map_err(|_| id)?;
+
+        Ok(PackedStorageId::new(index, generation))
+    }
+}
+
+impl From<PackedStorageId> for IdVecIndex {
+    fn from(id: PackedStorageId) -> IdVecIndex {
+        IdVecIndex(id.index())
+    }
+}
+
+// A GenerationStorage handing out PackedStorageIds, for use where ids have to stay small. Panics
+// if it grows past u32::MAX slots or a slot is reused u32::MAX times
+#[derive(Clone, Debug)]
+pub struct PackedGenerationStorage<T> {
+    storage: GenerationStorage<T>,
+}
+
+impl<T> Default for PackedGenerationStorage<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> std::ops::Deref for PackedGenerationStorage<T> {
+    type Target = GenerationStorage<T>;
+
+    fn deref(&self) -> &GenerationStorage<T> {
+        &self.storage
+    }
+}
+
+impl<T> PackedGenerationStorage<T> {
+    pub fn new() -> Self {
+        Self { storage: GenerationStorage::new() }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { storage: GenerationStorage::with_capacity(capacity) }
+    }
+
+    pub fn push(&mut self, item: T) -> PackedStorageId {
+        Self::pack(self.storage.push(item))
+    }
+
+    pub fn insert(&mut self, id: PackedStorageId, item: T) -> Option<T> {
+        self.storage.insert(id.into(), item)
+    }
+
+    pub fn get(&self, id: PackedStorageId) -> Option<&T> {
+        self.storage.get(id.into())
+    }
+
+    pub fn get_mut(&mut self, id: PackedStorageId) -> Option<&mut T> {
+        self.storage.get_mut(id.into())
+    }
+
+    pub fn contains(&self, id: PackedStorageId) -> bool {
+        self.storage.contains(id.into())
+    }
+
+    pub fn remove_id(&mut self, id: PackedStorageId) -> Option<T> {
+        self.storage.remove_id(id.into())
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.storage.values_mut()
+    }
+
+    pub fn iter_with_ids(&self) -> impl Iterator<Item = (PackedStorageId, &T)> {
+        self.storage.iter_with_ids().map(|(id, item)| (Self::pack(id), item))
+    }
+
+    pub fn iter_with_ids_mut(&mut self) -> impl Iterator<Item = (PackedStorageId, &mut T)> {
+        self.storage.iter_with_ids_mut().map(|(id, item)| (Self::pack(id), item))
+    }
+
+    pub fn into_inner(self) -> GenerationStorage<T> {
+        self.storage
+    }
+
+    fn pack(id: StorageId) -> PackedStorageId {
+        PackedStorageId::try_from(id).expect("StorageId doesn't fit in a PackedStorageId")
+    }
+}
+
+impl<T> UnorderedStorage for PackedGenerationStorage<T> {
+    type Index = PackedStorageId;
+    type Item = T;
+
+    fn insert(&mut self, index: PackedStorageId, value: T) -> Option<T> {
+        <PackedGenerationStorage<T>>::insert(self, index, value)
+    }
+
+    fn accepts(&self, index: &PackedStorageId) -> bool {
+        !self.storage.is_stale((*index).into())
+    }
+
+    fn swap_values(&mut self, a: &PackedStorageId, b: &PackedStorageId) -> bool {
+        self.storage.swap_values((*a).into(), (*b).into())
+    }
+
+    fn remove(&mut self, index: &PackedStorageId) -> Option<T> {
+        self.remove_id(*index)
+    }
+
+    fn get(&self, index: &PackedStorageId) -> Option<&T> {
+        <PackedGenerationStorage<T>>::get(self, *index)
+    }
+
+    fn get_mut(&mut self, index: &PackedStorageId) -> Option<&mut T> {
+        <PackedGenerationStorage<T>>::get_mut(self, *index)
+    }
+}
+
+impl<T> ExpandableStorage for PackedGenerationStorage<T> {
+    fn push(&mut self, value: T) -> PackedStorageId {
+        self.push(value)
+    }
+}
+
+impl<T> WithCapacity for PackedGenerationStorage<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        PackedGenerationStorage::with_capacity(capacity)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct OrderLink {
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+// A GenerationStorage that remembers the order items were added in. iter_ordered walks them
+// oldest first no matter which slots they were given, replacing a value keeps its place
+#[derive(Clone, Debug)]
+pub struct OrderedGenerationStorage<T> {
+    storage: GenerationStorage<T>,
+    // One link per occupied slot
+    links: Vec<Option<OrderLink>>,
+    first: Option<usize>,
+    last: Option<usize>,
+}
+
+impl<T> Default for OrderedGenerationStorage<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> std::ops::Deref for OrderedGenerationStorage<T> {
+    type Target = GenerationStorage<T>;
+
+    fn deref(&self) -> &GenerationStorage<T> {
+        &self.storage
+    }
+}
+
+impl<T> OrderedGenerationStorage<T> {
+    pub fn new() -> Self {
+        Self { storage: GenerationStorage::new(), links: vec![], first: None, last: None }
+    }
+
+    pub fn push(&mut self, item: T) -> StorageId {
+        let id = self.storage.push(item);
+        self.link_last(id.index);
+
+        id
+    }
+
+    pub fn insert(&mut self, id: StorageId, item: T) -> Option<T> {
+        self.try_insert(id, item).unwrap_or_else(Some)
+    }
+
+    // Same rules as GenerationStorage::try_insert, a new value goes to the end of the order
+    pub fn try_insert(&mut self, id: StorageId, item: T) -> Result<Option<T>, T> {
+        let replaced = self.storage.try_insert(id, item)?;
+
+        if !self.is_linked(id.index) {
+            self.link_last(id.index);
+        }
+
+        Ok(replaced)
+    }
+
+    pub fn get(&self, id: StorageId) -> Option<&T> {
+        self.storage.get(id)
+    }
+
+    pub fn get_mut(&mut self, id: StorageId) -> Option<&mut T> {
+        self.storage.get_mut(id)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        let removed = self.storage.remove(index)?;
+        self.unlink(index);
+
+        Some(removed)
+    }
+
+    pub fn remove_id(&mut self, id: StorageId) -> Option<T> {
+        if self.storage.contains(id) {
+            return self.remove(id.index);
+        }
+
+        None
+    }
+
+    pub fn clear(&mut self) {
+        self.storage.clear();
+        self.links.clear();
+        self.first = None;
+        self.last = None;
+    }
+
+    pub fn into_inner(self) -> GenerationStorage<T> {
+        self.storage
+    }
+
+    // Iterates in the order the values were added, oldest first
+    pub fn iter_ordered(&self) -> impl Iterator<Item = (StorageId, &T)> + '_ {
+        let mut current = self.first;
+
+        std::iter::from_fn(move || {
+            let index = current?;
+            current = self.links[index].unwrap().next;
+
+            let object = &self.storage.objects[index];
+            let id = StorageId { index, generation: object.generation() };
+
+            Some((id, object.unwrap_ref()))
+        })
+    }
+
+    pub fn values_ordered(&self) -> impl Iterator<Item = &T> + '_ {
+        self.iter_ordered().map(|(_, value)| value)
+    }
+
+    fn is_linked(&self, index: usize) -> bool {
+        matches!(self.links.get(index), Some(Some(_)))
+    }
+
+    fn link_last(&mut self, index: usize) {
+        if index >= self.links.len() {
+            self.links.resize(index + 1, None);
+        }
+
+        self.links[index] = Some(OrderLink { prev: self.last, next: None });
+
+        match self.last {
+            Some(last) => self.links[last].as_mut().unwrap().next = Some(index),
+            None => self.first = Some(index),
+        }
+
+        self.last = Some(index);
+    }
+
+    fn unlink(&mut self, index: usize) {
+        let link = match self.links.get_mut(index).and_then(Option::take) {
+            Some(link) => link,
+            None => return,
+        };
+
+        match link.prev {
+            Some(prev) => self.links[prev].as_mut().unwrap().next = link.next,
+            None => self.first = link.next,
+        }
+
+        match link.next {
+            Some(next) => self.links[next].as_mut().unwrap().prev = link.prev,
+            None => self.last = link.prev,
+        }
+    }
+}
+
+impl<T> UnorderedStorage for OrderedGenerationStorage<T> {
+    type Index = StorageId;
+    type Item = T;
+
+    fn insert(&mut self, index: StorageId, value: T) -> Option<T> {
+        <OrderedGenerationStorage<T>>::insert(self, index, value)
+    }
+
+    fn accepts(&self, index: &StorageId) -> bool {
+        !self.storage.is_stale(*index)
+    }
+
+    fn swap_values(&mut self, a: &StorageId, b: &StorageId) -> bool {
+        self.storage.swap_values(*a, *b)
+    }
+
+    fn remove(&mut self, index: &StorageId) -> Option<T> {
+        self.remove_id(*index)
+    }
+
+    fn get(&self, index: &StorageId) -> Option<&T> {
+        self.storage.get(*index)
+    }
+
+    fn get_mut(&mut self, index: &StorageId) -> Option<&mut T> {
+        self.storage.get_mut(*index)
+    }
+}
+
+impl<T> ExpandableStorage for OrderedGenerationStorage<T> {
+    fn push(&mut self, value: T) -> StorageId {
+        self.push(value)
+    }
+}
+
+impl<T> WithCapacity for OrderedGenerationStorage<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        let storage = GenerationStorage::with_capacity(capacity);
+
+        Self { storage, links: Vec::with_capacity(capacity), first: None, last: None }
+    }
+}
@@ -0,0 +1,62 @@
+use std::hash::{BuildHasherDefault, Hasher};
+
+pub type BuildIdentityHasher = BuildHasherDefault<IdentityHasher>;
+
+// Uses integer keys as their own hash rather than running them through SipHash. Only worth it for
+// keys that are already spread out, like entity or asset ids
+#[derive(Copy, Clone, Debug, Default)]
+pub struct IdentityHasher(u64);
+
+impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    // Anything other than a single integer is folded in byte by byte, it works but defeats the
+    // point of the hasher
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ u64::from(*byte);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.0 = u64::from(i);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.0 = u64::from(i);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.0 = u64::from(i);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0 = i;
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.0 = i as u64;
+    }
+
+    fn write_i8(&mut self, i: i8) {
+        self.0 = i as u64;
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.0 = i as u64;
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.0 = i as u64;
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.0 = i as u64;
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.0 = i as u64;
+    }
+}
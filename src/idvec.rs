@@ -1,5 +1,6 @@
-use crate::UnorderedStorage;
+use crate::{UnorderedStorage, WithCapacity};
 use derive_deref::{Deref, DerefMut};
+use std::iter::FromIterator;
 
 #[derive(Copy, Clone, Deref, DerefMut, Debug)]
 pub struct IdVecIndex(pub usize);
@@ -13,23 +14,148 @@ impl From<usize> for IdVecIndex {
 #[derive(Clone, Debug)]
 pub struct IdVec<T> {
     container: Vec<Option<T>>,
+    // The number of slots holding a value
+    len: usize,
+    occupied: Occupancy,
+}
+
+// One bit per slot, set while the slot holds a value. Lets iteration jump over 64 empty slots at
+// a time instead of checking each one, which matters once the ids in use are spread thin
+#[derive(Clone, Debug, Default)]
+struct Occupancy {
+    words: Vec<u64>,
+}
+
+impl Occupancy {
+    fn set(&mut self, index: usize) {
+        let word = index / 64;
+
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+
+        self.words[word] |= 1 << (index % 64);
+    }
+
+    fn unset(&mut self, index: usize) {
+        if let Some(word) = self.words.get_mut(index / 64) {
+            *word &= !(1 << (index % 64));
+        }
+    }
+
+    fn assign(&mut self, index: usize, occupied: bool) {
+        if occupied {
+            self.set(index);
+        } else {
+            self.unset(index);
+        }
+    }
+
+    // Only drops whole words, the slots past 'len' have to be empty already
+    fn truncate(&mut self, len: usize) {
+        self.words.truncate(len.div_ceil(64));
+    }
+
+    fn clear(&mut self) {
+        self.words.clear();
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.words.shrink_to_fit();
+    }
+}
+
+// Walks the set bits of an Occupancy, stepping the slots straight to each one
+struct Occupied<'a, I> {
+    words: &'a [u64],
+    word: usize,
+    bits: u64,
+    slots: I,
+    // The index of the slot 'slots' will hand out next
+    position: usize,
+}
+
+impl<'a, I> Occupied<'a, I> {
+    fn new(occupancy: &'a Occupancy, slots: I) -> Self {
+        Self {
+            words: &occupancy.words,
+            word: 0,
+            bits: occupancy.words.first().copied().unwrap_or(0),
+            slots,
+            position: 0,
+        }
+    }
+}
+
+impl<'a, I> Iterator for Occupied<'a, I>
+where
+    I: Iterator,
+{
+    type Item = (usize, I::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.bits == 0 {
+            self.word += 1;
+            self.bits = *self.words.get(self.word)?;
+        }
+
+        let index = self.word * 64 + self.bits.trailing_zeros() as usize;
+        self.bits &= self.bits - 1;
+
+        // Slice iterators skip ahead without visiting the slots in between
+        let slot = self.slots.nth(index - self.position)?;
+        self.position = index + 1;
+
+        Some((index, slot))
+    }
 }
 
 impl<T> IdVec<T> {
     pub fn new() -> Self {
-        Self { container: vec![] }
+        Self { container: vec![], len: 0, occupied: Occupancy::default() }
     }
 
     pub fn clear(&mut self) {
         self.container.clear();
+        self.len = 0;
+        self.occupied.clear();
     }
 
     pub fn with_capacity(cap: usize) -> Self {
         Self {
             container: Vec::with_capacity(cap),
+            len: 0,
+            occupied: Occupancy::default(),
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Counts empty slots too
+    pub fn capacity(&self) -> usize {
+        self.container.capacity()
+    }
+
+    // Drops the empty slots at the end, the allocation is kept
+    pub fn shrink(&mut self) {
+        let len = self.container.iter().rposition(Option::is_some).map_or(0, |last| last + 1);
+        self.container.truncate(len);
+        self.occupied.truncate(len);
+    }
+
+    // Drops the empty slots at the end and gives back the memory they took up
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink();
+        self.container.shrink_to_fit();
+        self.occupied.shrink_to_fit();
+    }
+
     pub fn fill_to(&mut self, size: usize) {
         for _ in self.container.len()..size {
             self.container.push(None)
@@ -39,13 +165,17 @@ impl<T> IdVec<T> {
     pub fn insert(&mut self, index: impl Into<IdVecIndex>, value: T) -> Option<T> {
         let index = *index.into();
 
-        if index < self.container.len() {
-            return std::mem::replace(&mut self.container[index], Some(value));
+        if index >= self.container.len() {
+            self.fill_to(index + 1);
         }
 
-        self.fill_to(index + 1);
+        let replaced = self.container[index].replace(value);
+        if replaced.is_none() {
+            self.len += 1;
+            self.occupied.set(index);
+        }
 
-        std::mem::replace(&mut self.container[index], Some(value))
+        replaced
     }
 
     pub fn remove(&mut self, index: impl Into<IdVecIndex>) -> Option<T> {
@@ -55,7 +185,56 @@ impl<T> IdVec<T> {
             return None;
         }
 
-        std::mem::replace(&mut self.container[index], None)
+        let removed = self.container[index].take();
+        if removed.is_some() {
+            self.len -= 1;
+            self.occupied.unset(index);
+        }
+
+        removed
+    }
+
+    // The same as remove, never grows the container
+    pub fn take(&mut self, index: impl Into<IdVecIndex>) -> Option<T> {
+        self.remove(index)
+    }
+
+    // Swaps the value into a slot that's already there, empty or not, returning what it held.
+    // Past the end nothing is stored and Err hands the value back
+    pub fn replace(&mut self, index: impl Into<IdVecIndex>, value: T) -> Result<Option<T>, T> {
+        let index = *index.into();
+
+        let slot = match self.container.get_mut(index) {
+            Some(slot) => slot,
+            None => return Err(value),
+        };
+
+        let replaced = slot.replace(value);
+        if replaced.is_none() {
+            self.len += 1;
+            self.occupied.set(index);
+        }
+
+        Ok(replaced)
+    }
+
+    // Exchanges whatever the two slots hold, only grows the container when a value has to be
+    // moved past the end
+    pub fn swap(&mut self, a: impl Into<IdVecIndex>, b: impl Into<IdVecIndex>) {
+        let (a, b) = (*a.into(), *b.into());
+        let furthest = a.max(b);
+
+        if furthest >= self.container.len() {
+            if self.get(a.min(b)).is_none() {
+                return;
+            }
+
+            self.fill_to(furthest + 1);
+        }
+
+        self.container.swap(a, b);
+        self.occupied.assign(a, self.container[a].is_some());
+        self.occupied.assign(b, self.container[b].is_some());
     }
 
     pub fn get(&self, index: impl Into<IdVecIndex>) -> Option<&T> {
@@ -78,20 +257,46 @@ impl<T> IdVec<T> {
         self.container[index].as_mut()
     }
 
+    // Only visits occupied slots, so a sparse IdVec costs about as much to walk as a dense one
     pub fn iter(&'_ self) -> impl Iterator<Item = (usize, &'_ T)> + '_ {
-        self.container
-            .iter()
-            .enumerate()
-            .filter(|(_, value)| value.is_some())
+        Occupied::new(&self.occupied, self.container.iter())
             .map(|(idx, value)| (idx, value.as_ref().unwrap()))
     }
 
     pub fn iter_mut(&'_ mut self) -> impl Iterator<Item = (usize, &'_ mut T)> + '_ {
+        Occupied::new(&self.occupied, self.container.iter_mut())
+            .map(|(idx, value)| (idx, value.as_mut().unwrap()))
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T> crate::par::ParStorage for IdVec<T> {
+    fn par_iter<'a>(
+        &'a self,
+    ) -> impl rayon::iter::ParallelIterator<Item = (IdVecIndex, &'a T)> + 'a
+    where
+        T: Sync + 'a,
+    {
+        use rayon::prelude::*;
+
         self.container
-            .iter_mut()
+            .par_iter()
             .enumerate()
-            .filter(|(_, value)| value.is_some())
-            .map(|(idx, value)| (idx, value.as_mut().unwrap()))
+            .filter_map(|(idx, value)| value.as_ref().map(|value| (IdVecIndex(idx), value)))
+    }
+
+    fn par_iter_mut<'a>(
+        &'a mut self,
+    ) -> impl rayon::iter::ParallelIterator<Item = (IdVecIndex, &'a mut T)> + 'a
+    where
+        T: Send + 'a,
+    {
+        use rayon::prelude::*;
+
+        self.container
+            .par_iter_mut()
+            .enumerate()
+            .filter_map(|(idx, value)| value.as_mut().map(|value| (IdVecIndex(idx), value)))
     }
 }
 
@@ -116,8 +321,31 @@ impl<T> UnorderedStorage for IdVec<T> {
     }
 }
 
+// Later pairs replace earlier ones with the same index
+impl<T> Extend<(usize, T)> for IdVec<T> {
+    fn extend<I: IntoIterator<Item = (usize, T)>>(&mut self, iter: I) {
+        for (index, value) in iter {
+            self.insert(index, value);
+        }
+    }
+}
+
+impl<T> FromIterator<(usize, T)> for IdVec<T> {
+    fn from_iter<I: IntoIterator<Item = (usize, T)>>(iter: I) -> Self {
+        let mut ids = IdVec::new();
+        ids.extend(iter);
+        ids
+    }
+}
+
 impl<T> Default for IdVec<T> {
     fn default() -> Self {
         IdVec::new()
     }
 }
+
+impl<T> WithCapacity for IdVec<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        IdVec::with_capacity(capacity)
+    }
+}
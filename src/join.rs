@@ -0,0 +1,156 @@
+use crate::{densevec::DenseVec, generation::*, idvec::*, novec::NoVec, paged_idvec::PagedIdVec, *};
+use std::vec::IntoIter;
+
+// Storages that can list the indices of everything they hold so they can drive a join
+pub trait JoinStorage: UnorderedStorage {
+    fn indices(&self) -> Vec<Self::Index>;
+}
+
+impl<T> JoinStorage for GenerationStorage<T> {
+    fn indices(&self) -> Vec<StorageId> {
+        self.iter_with_ids().map(|(id, _)| id).collect()
+    }
+}
+
+impl<T> JoinStorage for IdVec<T> {
+    fn indices(&self) -> Vec<IdVecIndex> {
+        self.iter().map(|(idx, _)| IdVecIndex(idx)).collect()
+    }
+}
+
+impl<T> JoinStorage for PagedIdVec<T> {
+    fn indices(&self) -> Vec<IdVecIndex> {
+        self.iter().map(|(idx, _)| IdVecIndex(idx)).collect()
+    }
+}
+
+impl<T> JoinStorage for NoVec<T> {
+    fn indices(&self) -> Vec<usize> {
+        self.iter().map(|(idx, _)| idx).collect()
+    }
+}
+
+impl<T> JoinStorage for DenseVec<T> {
+    fn indices(&self) -> Vec<usize> {
+        self.indices().to_vec()
+    }
+}
+
+impl<K, T> JoinStorage for HashMap<K, T>
+where
+    K: Hash + Eq + Clone,
+{
+    fn indices(&self) -> Vec<K> {
+        self.keys().cloned().collect()
+    }
+}
+
+// Implemented for '&S', '&mut S' and tuples of those sharing an index type. The first storage in
+// a tuple decides which indices are visited, indices missing from any other storage are skipped
+pub trait Join: Sized {
+    type Index;
+    type Item;
+
+    fn indices(&self) -> Vec<Self::Index>;
+
+    /// # Safety
+    /// Every index must only be fetched once while the previously fetched items are alive, which
+    /// holds for the distinct indices handed out by 'indices'
+    unsafe fn fetch(&mut self, index: &Self::Index) -> Option<Self::Item>;
+
+    fn join(self) -> JoinIter<Self> {
+        let indices = self.indices().into_iter();
+
+        JoinIter { join: self, indices }
+    }
+}
+
+impl<'a, S> Join for &'a S
+where
+    S: JoinStorage,
+{
+    type Index = S::Index;
+    type Item = &'a S::Item;
+
+    fn indices(&self) -> Vec<S::Index> {
+        JoinStorage::indices(*self)
+    }
+
+    unsafe fn fetch(&mut self, index: &S::Index) -> Option<&'a S::Item> {
+        let storage: &'a S = self;
+
+        storage.get(index)
+    }
+}
+
+impl<'a, S> Join for &'a mut S
+where
+    S: JoinStorage,
+{
+    type Index = S::Index;
+    type Item = &'a mut S::Item;
+
+    fn indices(&self) -> Vec<S::Index> {
+        JoinStorage::indices(&**self)
+    }
+
+    unsafe fn fetch(&mut self, index: &S::Index) -> Option<&'a mut S::Item> {
+        let storage = &mut **self as *mut S;
+
+        (*storage).get_mut(index)
+    }
+}
+
+macro_rules! impl_join_tuple {
+    ($first:ident $(, $rest:ident)*) => {
+        #[allow(non_snake_case)]
+        impl<$first: Join, $($rest: Join<Index = $first::Index>),*> Join for ($first, $($rest),*) {
+            type Index = $first::Index;
+            type Item = ($first::Item, $($rest::Item),*);
+
+            fn indices(&self) -> Vec<Self::Index> {
+                self.0.indices()
+            }
+
+            unsafe fn fetch(&mut self, index: &Self::Index) -> Option<Self::Item> {
+                let ($first, $($rest),*) = self;
+
+                Some(($first.fetch(index)?, $($rest.fetch(index)?),*))
+            }
+        }
+    };
+}
+
+impl_join_tuple!(A, B);
+impl_join_tuple!(A, B, C);
+impl_join_tuple!(A, B, C, D);
+impl_join_tuple!(A, B, C, D, E);
+impl_join_tuple!(A, B, C, D, E, F);
+
+pub struct JoinIter<J: Join> {
+    join: J,
+    indices: IntoIter<J::Index>,
+}
+
+impl<J: Join> Iterator for JoinIter<J> {
+    type Item = (J::Index, J::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for index in &mut self.indices {
+            // Safe as each index is only yielded once by 'indices'
+            if let Some(item) = unsafe { self.join.fetch(&index) } {
+                return Some((index, item));
+            }
+        }
+
+        None
+    }
+}
+
+// join!(&positions, &mut velocities) iterates (index, (&position, &mut velocity))
+#[macro_export]
+macro_rules! join {
+    ($($storage:expr),+ $(,)?) => {
+        $crate::join::Join::join(($($storage),+))
+    };
+}
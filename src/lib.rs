@@ -1,14 +1,28 @@
 use std::{hash::Hash, collections::HashMap};
 
+// Lets code generated by 'storage-derive' refer to '::storage' from inside this crate too
+extern crate self as storage;
+
+pub mod alloc;
 pub mod generation;
+pub mod hasher;
 pub mod idvec;
+pub mod paged_idvec;
 pub mod loader;
 pub mod novec;
+pub mod novec32;
+pub mod densevec;
 pub mod oom;
 pub mod map;
 pub mod one_way_map;
 pub mod block_storage;
 pub mod any_storage;
+pub mod join;
+pub mod versioned;
+#[cfg(feature = "rayon")]
+pub mod par;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 #[cfg(test)]
 mod test;
@@ -111,6 +125,36 @@ pub trait UnorderedStorage {
 
     fn insert(&mut self, index: Self::Index, value: Self::Item) -> Option<Self::Item>;
     fn remove(&mut self, index: &Self::Index) -> Option<Self::Item>;
+    // Whether insert would store a value at the index rather than hand it straight back, only
+    // storages that turn away stale indices need to override it
+    fn accepts(&self, _index: &Self::Index) -> bool {
+        true
+    }
+    // Exchanges the values at the two indices, false if either is empty. Goes through remove and
+    // insert, so storages that turn away the index of a removed value have to override it
+    fn swap_values(&mut self, a: &Self::Index, b: &Self::Index) -> bool
+    where
+        Self::Index: Clone,
+    {
+        if self.get(a).is_none() || self.get(b).is_none() {
+            return false;
+        }
+
+        let a_value = self.remove(a).unwrap();
+
+        let b_value = match self.get_mut(b) {
+            Some(slot) => std::mem::replace(slot, a_value),
+            // a and b are the same index
+            None => {
+                self.insert(a.clone(), a_value);
+                return true;
+            }
+        };
+
+        self.insert(a.clone(), b_value);
+
+        true
+    }
     fn get(&self, index: &Self::Index) -> Option<&Self::Item>;
     fn get_mut<'a, 'b>(&'a mut self, index: &'b Self::Index) -> Option<&'a mut Self::Item>;
 }
@@ -138,3 +182,14 @@ where
 pub trait ExpandableStorage: UnorderedStorage {
     fn push(&mut self, value: Self::Item) -> Self::Index;
 }
+
+// Storages that can be created with room for a number of items up front
+pub trait WithCapacity {
+    fn with_capacity(capacity: usize) -> Self;
+}
+
+impl<K, T> WithCapacity for HashMap<K, T> {
+    fn with_capacity(capacity: usize) -> Self {
+        <HashMap<K, T>>::with_capacity(capacity)
+    }
+}
@@ -0,0 +1,93 @@
+use super::manager::Counter;
+use cbc::{unbounded, Receiver, RecvTimeoutError, Sender, TryIter};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+// A counter table shared with a background thread that increments every counter once per
+// interval. Entries whose counters pass the threshold are removed from the table and queued for
+// the owning thread to evict
+pub struct BackgroundCounters<I, C> {
+    counters: Arc<Mutex<HashMap<I, C>>>,
+    evictions: Receiver<I>,
+    shutdown: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<I, C> BackgroundCounters<I, C>
+where
+    I: Hash + Eq + Copy + Send + 'static,
+    C: Counter + Send + 'static,
+{
+    pub fn spawn_with(
+        initial: HashMap<I, C>,
+        interval: Duration,
+        increment: C,
+        threshold: C,
+    ) -> Self {
+        let counters = Arc::new(Mutex::new(initial));
+        let (eviction_sender, evictions) = unbounded();
+        let (shutdown, shutdown_receiver) = unbounded::<()>();
+
+        let shared = counters.clone();
+        let handle = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = shutdown_receiver.recv_timeout(interval) {
+                let mut counters = shared.lock().unwrap();
+
+                counters.retain(|idx, counter| {
+                    counter.increment(&increment);
+
+                    if counter.is_valid(&threshold) {
+                        return true;
+                    }
+
+                    // The receiving side only goes away when this is being shut down
+                    let _ = eviction_sender.send(*idx);
+                    false
+                });
+            }
+        });
+
+        Self { counters, evictions, shutdown: Some(shutdown), handle: Some(handle) }
+    }
+}
+
+impl<I, C> BackgroundCounters<I, C>
+where
+    I: Hash + Eq,
+    C: Counter,
+{
+    pub fn insert(&self, idx: I) {
+        self.counters.lock().unwrap().insert(idx, C::zero());
+    }
+
+    pub fn reset(&self, idx: &I) {
+        if let Some(counter) = self.counters.lock().unwrap().get_mut(idx) {
+            counter.reset();
+        }
+    }
+
+    pub fn remove(&self, idx: &I) -> Option<C> {
+        self.counters.lock().unwrap().remove(idx)
+    }
+
+    // Indices that the background thread has found to be out of date
+    pub fn evictions(&self) -> TryIter<'_, I> {
+        self.evictions.try_iter()
+    }
+}
+
+impl<I, C> Drop for BackgroundCounters<I, C> {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the thread up and stops it
+        self.shutdown.take();
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
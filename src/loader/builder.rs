@@ -0,0 +1,332 @@
+use super::{
+    manager::{Counter, EvictionPolicy, ManagedStorage},
+    *,
+};
+use crate::WithCapacity;
+use std::marker::PhantomData;
+
+type ChannelSystem<K, S, T> = StorageSystem<K, S, GenericSender<<K as UnorderedStorage>::Item>, T>;
+type ChannelManaged<K, S, T, C> =
+    ManagedStorage<K, S, GenericSender<<K as UnorderedStorage>::Item>, T, C>;
+
+// Sets up a StorageSystem in one chain, the generic parameters are usually inferred from the
+// alias the result is assigned to
+//
+// let (system, receiver): (GenLoader<String, Mesh>, _) = StorageSystem::builder()
+//     .channel_capacity(64)
+//     .capacity(256)
+//     .retry_policy(RetryPolicy::Times(2))
+//     .build_channel();
+pub struct StorageSystemBuilder<K, S, L, T>
+where
+    S: ExpandableStorage<Item = Promise<T, L::Item>>,
+    K: UnorderedStorage,
+    K::Item: Hash + Eq,
+    L: Loader<Key = K::Item>,
+{
+    storage: Option<MappedStorage<K, S>>,
+    loader: Option<L>,
+    channel_capacity: Option<usize>,
+    duplicate_policy: DuplicatePolicy,
+    retry_policy: RetryPolicy,
+    _marker: PhantomData<T>,
+}
+
+impl<K, S, L, T> Default for StorageSystemBuilder<K, S, L, T>
+where
+    S: ExpandableStorage<Item = Promise<T, L::Item>>,
+    K: UnorderedStorage,
+    K::Item: Hash + Eq,
+    L: Loader<Key = K::Item>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, S, L, T> StorageSystemBuilder<K, S, L, T>
+where
+    S: ExpandableStorage<Item = Promise<T, L::Item>>,
+    K: UnorderedStorage,
+    K::Item: Hash + Eq,
+    L: Loader<Key = K::Item>,
+{
+    pub fn new() -> Self {
+        Self {
+            storage: None,
+            loader: None,
+            channel_capacity: None,
+            duplicate_policy: DuplicatePolicy::KeepExisting,
+            retry_policy: RetryPolicy::Never,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn loader(mut self, loader: L) -> Self {
+        self.loader = Some(loader);
+        self
+    }
+
+    // Replaces any capacity given before
+    pub fn storage(mut self, storage: MappedStorage<K, S>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    // Room for this many entries up front, replaces any storage given before
+    pub fn capacity(mut self, capacity: usize) -> Self
+    where
+        K: WithCapacity,
+        S: WithCapacity,
+    {
+        self.storage = Some(MappedStorage::with_capacity(capacity));
+        self
+    }
+
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = policy;
+        self
+    }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    // Panics if no loader was given, use build_channel to have one created
+    pub fn build(mut self) -> StorageSystem<K, S, L, T>
+    where
+        T: 'static,
+        S::Index: Into<K::Index> + Copy,
+        K: Default,
+        S: Default,
+        K::Item: Clone,
+        K::Index: Copy,
+        L: Loader<Meta = TypeId>,
+        L::Item: Convert<T>,
+    {
+        let loader = self.loader.take().expect("StorageSystemBuilder was given no loader");
+        self.finish(loader)
+    }
+
+    fn finish(self, loader: L) -> StorageSystem<K, S, L, T>
+    where
+        T: 'static,
+        S::Index: Into<K::Index> + Copy,
+        K: Default,
+        S: Default,
+        K::Item: Clone,
+        K::Index: Copy,
+        L: Loader<Meta = TypeId>,
+        L::Item: Convert<T>,
+    {
+        let storage = self.storage.unwrap_or_default();
+
+        StorageSystem::with_storage(storage, loader)
+            .with_duplicate_policy(self.duplicate_policy)
+            .with_retry_policy(self.retry_policy)
+    }
+}
+
+impl<K, S, T> StorageSystemBuilder<K, S, GenericSender<K::Item>, T>
+where
+    S: ExpandableStorage<Item = GenericPromise<T>>,
+    K: UnorderedStorage,
+    K::Item: Hash + Eq,
+{
+    // Bounds the channel build_channel creates, it's unbounded otherwise
+    pub fn channel_capacity(mut self, capacity: usize) -> Self {
+        self.channel_capacity = Some(capacity);
+        self
+    }
+
+    // Creates the channel the system sends its loads down, any loader given before is dropped.
+    // The receiver goes to whatever does the loading
+    pub fn build_channel(mut self) -> (ChannelSystem<K, S, T>, GenericReceiver<K::Item>)
+    where
+        T: 'static,
+        S::Index: Into<K::Index> + Copy,
+        K: Default,
+        S: Default,
+        K::Item: Clone,
+        K::Index: Copy,
+    {
+        let (sender, receiver) = match self.channel_capacity {
+            Some(capacity) => bounded(capacity),
+            None => unbounded(),
+        };
+
+        self.loader = None;
+        (self.finish(sender), receiver)
+    }
+}
+
+// Sets up a ManagedStorage in one chain, takes everything StorageSystemBuilder does along with
+// the eviction settings
+//
+// let (manager, receiver): (ManagedGen<String, Mesh, u32>, _) = ManagedStorage::builder(10)
+//     .max_entries(128)
+//     .eviction_policy(EvictionPolicy::Lru)
+//     .build_channel();
+pub struct ManagedStorageBuilder<K, S, L, T, C>
+where
+    S: ExpandableStorage<Item = Promise<T, L::Item>>,
+    K: UnorderedStorage,
+    K::Item: Hash + Eq,
+    C: UnorderedStorage,
+    L: Loader<Key = K::Item>,
+{
+    system: StorageSystemBuilder<K, S, L, T>,
+    threshold: C::Item,
+    max_entries: Option<usize>,
+    byte_budget: Option<usize>,
+    policy: EvictionPolicy,
+}
+
+impl<K, S, L, T, C> ManagedStorageBuilder<K, S, L, T, C>
+where
+    S: ExpandableStorage<Item = Promise<T, L::Item>>,
+    K: UnorderedStorage,
+    K::Item: Hash + Eq,
+    C: UnorderedStorage,
+    C::Item: Counter,
+    L: Loader<Key = K::Item>,
+{
+    pub fn new(threshold: C::Item) -> Self {
+        Self {
+            system: StorageSystemBuilder::new(),
+            threshold,
+            max_entries: None,
+            byte_budget: None,
+            policy: EvictionPolicy::Counter,
+        }
+    }
+
+    pub fn loader(mut self, loader: L) -> Self {
+        self.system = self.system.loader(loader);
+        self
+    }
+
+    pub fn storage(mut self, storage: MappedStorage<K, S>) -> Self {
+        self.system = self.system.storage(storage);
+        self
+    }
+
+    pub fn capacity(mut self, capacity: usize) -> Self
+    where
+        K: WithCapacity,
+        S: WithCapacity,
+    {
+        self.system = self.system.capacity(capacity);
+        self
+    }
+
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.system = self.system.duplicate_policy(policy);
+        self
+    }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.system = self.system.retry_policy(policy);
+        self
+    }
+
+    pub fn threshold(mut self, threshold: C::Item) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    // Only counts the sizes reported by the loader, see ManagedStorage::with_reported_byte_budget
+    pub fn byte_budget(mut self, budget: usize) -> Self {
+        self.byte_budget = Some(budget);
+        self
+    }
+
+    pub fn eviction_policy(mut self, policy: EvictionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    // Panics if no loader was given, use build_channel to have one created
+    pub fn build(self) -> ManagedStorage<K, S, L, T, C>
+    where
+        T: 'static,
+        S::Index: Into<K::Index> + Copy + Hash + Eq,
+        K: Default,
+        S: Default,
+        K::Item: Clone,
+        K::Index: Copy,
+        C: UnorderedStorage<Index = K::Index> + Default,
+        L: Loader<Meta = TypeId>,
+        L::Item: Convert<T>,
+    {
+        let system = self.system.build();
+
+        Self::manage(system, self.threshold, self.max_entries, self.byte_budget, self.policy)
+    }
+
+    fn manage(
+        system: StorageSystem<K, S, L, T>,
+        threshold: C::Item,
+        max_entries: Option<usize>,
+        byte_budget: Option<usize>,
+        policy: EvictionPolicy,
+    ) -> ManagedStorage<K, S, L, T, C>
+    where
+        T: 'static,
+        S::Index: Into<K::Index> + Copy + Hash + Eq,
+        K::Item: Clone,
+        K::Index: Copy,
+        C: UnorderedStorage<Index = K::Index> + Default,
+        L: Loader<Meta = TypeId>,
+        L::Item: Convert<T>,
+    {
+        let mut managed = ManagedStorage::new(system, threshold).with_policy(policy);
+
+        if let Some(max_entries) = max_entries {
+            managed = managed.with_max_entries(max_entries);
+        }
+
+        if let Some(budget) = byte_budget {
+            managed = managed.with_reported_byte_budget(budget);
+        }
+
+        managed
+    }
+}
+
+impl<K, S, T, C> ManagedStorageBuilder<K, S, GenericSender<K::Item>, T, C>
+where
+    S: ExpandableStorage<Item = GenericPromise<T>>,
+    K: UnorderedStorage,
+    K::Item: Hash + Eq,
+    C: UnorderedStorage,
+    C::Item: Counter,
+{
+    pub fn channel_capacity(mut self, capacity: usize) -> Self {
+        self.system = self.system.channel_capacity(capacity);
+        self
+    }
+
+    pub fn build_channel(self) -> (ChannelManaged<K, S, T, C>, GenericReceiver<K::Item>)
+    where
+        T: 'static,
+        S::Index: Into<K::Index> + Copy + Hash + Eq,
+        K: Default,
+        S: Default,
+        K::Item: Clone,
+        K::Index: Copy,
+        C: UnorderedStorage<Index = K::Index> + Default,
+    {
+        let (system, receiver) = self.system.build_channel();
+        let managed =
+            Self::manage(system, self.threshold, self.max_entries, self.byte_budget, self.policy);
+
+        (managed, receiver)
+    }
+}
@@ -1,12 +1,13 @@
 use super::*;
 use std::{
     any::TypeId,
+    borrow::Cow,
     collections::HashMap,
     error::Error,
     fmt::{self, Debug, Display, Formatter},
     fs::File,
     hash::Hash,
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader},
     path::{Path, PathBuf},
     str::FromStr,
 };
@@ -28,9 +29,80 @@ impl Display for MappingError {
 
 impl Error for MappingError {}
 
+// Import settings that follow the path of a mapping, 'key => path | srgb, mips=4'
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MappingOptions {
+    options: Vec<(String, Option<String>)>,
+}
+
+impl MappingOptions {
+    pub fn new() -> Self {
+        Self { options: vec![] }
+    }
+
+    pub fn with(mut self, name: impl Into<String>, value: Option<String>) -> Self {
+        self.options.push((name.into(), value));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.options.is_empty()
+    }
+
+    // True for both flags and options with values
+    pub fn has(&self, name: &str) -> bool {
+        self.options.iter().any(|(option, _)| option == name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(option, _)| option == name)
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    // Parses the value of an option, None if it is missing or fails to parse
+    pub fn parse<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.get(name)?.parse().ok()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&str>)> + '_ {
+        self.options.iter().map(|(name, value)| (name.as_str(), value.as_deref()))
+    }
+}
+
+impl FromStr for MappingOptions {
+    type Err = std::convert::Infallible;
+
+    fn from_str(options: &str) -> Result<Self, Self::Err> {
+        let mut parsed = MappingOptions::new();
+
+        for option in options.split(',').map(str::trim).filter(|option| !option.is_empty()) {
+            parsed = match option.find('=') {
+                Some(split) => {
+                    let name = option[..split].trim();
+                    let value = option[split + 1..].trim();
+                    parsed.with(name, Some(value.to_string()))
+                }
+                None => parsed.with(option, None),
+            };
+        }
+
+        Ok(parsed)
+    }
+}
+
+struct Mapping {
+    path: PathBuf,
+    options: MappingOptions,
+}
+
+// The parent directory on the first line, followed by every key with its path and options
+type MappingFile<K> = (PathBuf, Vec<(K, PathBuf, MappingOptions)>);
+
 fn load_mappings_from_file<K: FromStr>(
     path: impl AsRef<Path>,
-) -> Result<(PathBuf, Vec<(K, PathBuf)>), Box<dyn Error>> {
+) -> Result<MappingFile<K>, Box<dyn Error>> {
     let file = std::fs::File::open(path)?;
     let reader = std::io::BufReader::new(file);
     let mut lines = reader.lines();
@@ -58,16 +130,23 @@ fn load_mappings_from_file<K: FromStr>(
             None => return Err(Box::new(MappingError::MissingMapping(i))),
         };
 
-        let path = match split.next() {
-            Some(path) => {
+        let (path, options) = match split.next() {
+            Some(target) => {
+                let mut target = target.splitn(2, '|');
                 let mut pbuf = parent.clone();
-                pbuf.push(path.trim());
-                pbuf
+                pbuf.push(target.next().unwrap_or("").trim());
+
+                let options = match target.next() {
+                    Some(options) => options.parse()?,
+                    None => MappingOptions::new(),
+                };
+
+                (pbuf, options)
             }
             None => return Err(Box::new(MappingError::MissingMapping(i))),
         };
 
-        mappings.push((key, path));
+        mappings.push((key, path, options));
     }
 
     Ok((parent, mappings))
@@ -76,7 +155,11 @@ fn load_mappings_from_file<K: FromStr>(
 pub struct MappedObject<'a, K> {
     pub type_id: TypeId,
     pub key: K,
-    pub path: &'a Path,
+    // The file that was opened, which has a fallback extension if the mapped path was missing
+    pub path: Cow<'a, Path>,
+    pub options: &'a MappingOptions,
+    // Whatever was passed to 'load_with' for this particular request
+    pub load_options: LoadOptions,
     pub reader: BufReader<File>,
 }
 
@@ -87,7 +170,9 @@ pub enum MapError {
 
 pub struct FileMapper<K: Hash> {
     parent: PathBuf,
-    mapping: HashMap<K, PathBuf>,
+    mapping: HashMap<K, Mapping>,
+    // Tried in order when a mapped file doesn't exist
+    fallback_extensions: Vec<String>,
     receiver: GenericReceiver<K>,
     shutdown: Option<Receiver<()>>,
 }
@@ -97,6 +182,7 @@ impl<K: Hash + Clone + Eq> FileMapper<K> {
         Self {
             parent: PathBuf::new(),
             mapping: HashMap::new(),
+            fallback_extensions: vec![],
             receiver,
             shutdown,
         }
@@ -112,7 +198,7 @@ impl<K: Hash + Clone + Eq> FileMapper<K> {
     {
         let (parent, mappings) = load_mappings_from_file(path)?;
 
-        Ok(Self::from_mappings(
+        Ok(Self::from_mappings_with_options(
             receiver,
             shutdown,
             parent,
@@ -125,21 +211,90 @@ impl<K: Hash + Clone + Eq> FileMapper<K> {
         shutdown: Option<Receiver<()>>,
         parent: PathBuf,
         mappings: impl Iterator<Item = (K, PathBuf)>,
+    ) -> Self {
+        let mappings = mappings.map(|(key, path)| (key, path, MappingOptions::new()));
+
+        Self::from_mappings_with_options(receiver, shutdown, parent, mappings)
+    }
+
+    pub fn from_mappings_with_options(
+        receiver: GenericReceiver<K>,
+        shutdown: Option<Receiver<()>>,
+        parent: PathBuf,
+        mappings: impl Iterator<Item = (K, PathBuf, MappingOptions)>,
     ) -> Self {
         let mut mapping = HashMap::new();
 
-        for (key, path) in mappings {
-            mapping.insert(key, path);
+        for (key, path, options) in mappings {
+            mapping.insert(key, Mapping { path, options });
         }
 
         Self {
             parent,
             mapping,
+            fallback_extensions: vec![],
             receiver,
             shutdown,
         }
     }
 
+    // When a mapped file is missing the same path is tried with each of these extensions in
+    // turn, so 'level => level.ron' can also find 'level.json' or 'level.bin'
+    pub fn with_fallback_extensions<E: Into<String>>(
+        mut self,
+        extensions: impl IntoIterator<Item = E>,
+    ) -> Self {
+        self.set_fallback_extensions(extensions);
+        self
+    }
+
+    pub fn set_fallback_extensions<E: Into<String>>(
+        &mut self,
+        extensions: impl IntoIterator<Item = E>,
+    ) {
+        self.fallback_extensions = extensions
+            .into_iter()
+            .map(|extension| extension.into().trim_start_matches('.').to_string())
+            .collect();
+    }
+
+    pub fn fallback_extensions(&self) -> &[String] {
+        &self.fallback_extensions
+    }
+
+    // Opens the mapped path or the first fallback that exists, the error is always for the
+    // mapped path
+    fn open<'a>(&self, path: &'a Path) -> Result<(Cow<'a, Path>, BufReader<File>), MapError> {
+        let error = match File::open(path) {
+            Ok(file) => return Ok((Cow::Borrowed(path), BufReader::new(file))),
+            Err(e) => e,
+        };
+
+        if error.kind() == io::ErrorKind::NotFound {
+            for extension in &self.fallback_extensions {
+                let fallback = path.with_extension(extension);
+
+                if fallback == path {
+                    continue;
+                }
+
+                if let Ok(file) = File::open(&fallback) {
+                    return Ok((Cow::Owned(fallback), BufReader::new(file)));
+                }
+            }
+        }
+
+        Err(MapError::FileError(path.to_path_buf(), error))
+    }
+
+    pub fn options<Q>(&self, key: &Q) -> Option<&MappingOptions>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.mapping.get(key).map(|mapping| &mapping.options)
+    }
+
     pub fn receive_non_blocking(
         &self,
         mut success: impl FnMut(MappedObject<K>) -> GenericResult,
@@ -154,7 +309,7 @@ impl<K: Hash + Clone + Eq> FileMapper<K> {
         }
 
         for (key, into) in self.receiver.try_iter() {
-            let path = match self.mapping.get(&key) {
+            let Mapping { path, options } = match self.mapping.get(&key) {
                 Some(value) => value,
                 None => {
                     fail(key, MapError::MissingMapping);
@@ -162,20 +317,20 @@ impl<K: Hash + Clone + Eq> FileMapper<K> {
                 }
             };
 
-            let file = match std::fs::File::open(&path) {
-                Ok(file) => file,
+            let (path, reader) = match self.open(path) {
+                Ok(opened) => opened,
                 Err(e) => {
-                    fail(key, MapError::FileError(path.clone(), e));
+                    fail(key, e);
                     return Ok(());
                 }
             };
 
-            let reader = std::io::BufReader::new(file);
-
             let mapped = MappedObject {
                 type_id: into.meta_data,
                 key,
-                path: path.as_path(),
+                path,
+                options,
+                load_options: into.options.clone(),
                 reader,
             };
 
@@ -198,7 +353,7 @@ impl<K: Hash + Clone + Eq> FileMapper<K> {
                 recv(self.shutdown.as_ref().unwrap_or(&cbc::never())) -> _ => break,
                 recv(self.receiver) -> msg => match msg {
                     Ok((key, into)) => {
-                        let path = match self.mapping.get(&key) {
+                        let Mapping { path, options } = match self.mapping.get(&key) {
                             Some(value) => value,
                             None => {
                                 fail(key, MapError::MissingMapping);
@@ -206,20 +361,20 @@ impl<K: Hash + Clone + Eq> FileMapper<K> {
                             },
                         };
 
-                        let file = match std::fs::File::open(&path) {
-                            Ok(file) => file,
+                        let (path, reader) = match self.open(path) {
+                            Ok(opened) => opened,
                             Err(e) => {
-                                fail(key, MapError::FileError(path.clone(), e));
+                                fail(key, e);
                                 continue;
                             }
                         };
 
-                        let reader = std::io::BufReader::new(file);
-
                         let mapped = MappedObject {
                             type_id: into.meta_data,
                             key,
-                            path: path.as_path(),
+                            path,
+                            options,
+                            load_options: into.options.clone(),
                             reader,
                         };
 
@@ -0,0 +1,77 @@
+use std::sync::{Arc, Mutex};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GroupStatus {
+    pub loaded: usize,
+    pub pending: usize,
+    pub failed: usize,
+}
+
+impl GroupStatus {
+    pub fn total(&self) -> usize {
+        self.loaded + self.pending + self.failed
+    }
+
+    // Failed loads count as done, check failed to tell them apart
+    pub fn is_complete(&self) -> bool {
+        self.pending == 0
+    }
+
+    // The fraction of loads that are done, an empty group is fully done
+    pub fn progress(&self) -> f32 {
+        match self.total() {
+            0 => 1.0,
+            total => (self.loaded + self.failed) as f32 / total as f32,
+        }
+    }
+}
+
+// A named set of loads whose progress can be followed as a whole. Clones share their counts so
+// one group can be handed to several storage systems, each attributing its own loads to it
+#[derive(Clone, Debug)]
+pub struct LoadGroup {
+    name: Arc<str>,
+    status: Arc<Mutex<GroupStatus>>,
+}
+
+impl LoadGroup {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into().into(), status: Arc::default() }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn status(&self) -> GroupStatus {
+        *self.status.lock().unwrap()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.status().is_complete()
+    }
+
+    // Whether both handles refer to the same group rather than just sharing a name
+    pub fn same_group(&self, other: &LoadGroup) -> bool {
+        Arc::ptr_eq(&self.status, &other.status)
+    }
+
+    pub(crate) fn add_loaded(&self) {
+        self.status.lock().unwrap().loaded += 1;
+    }
+
+    pub(crate) fn add_pending(&self) {
+        self.status.lock().unwrap().pending += 1;
+    }
+
+    // Moves a pending load over to loaded or failed
+    pub(crate) fn settle(&self, loaded: bool) {
+        let mut status = self.status.lock().unwrap();
+        status.pending -= 1;
+
+        match loaded {
+            true => status.loaded += 1,
+            false => status.failed += 1,
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::{collections::HashMap, hash::Hash};
+
+#[derive(Copy, Clone, Debug)]
+struct Link<I> {
+    prev: Option<I>,
+    next: Option<I>,
+}
+
+// An intrusive doubly linked recency list. The head is the most recently touched index and the
+// tail the least recently touched, all operations are O(1)
+#[derive(Clone, Debug)]
+pub struct LruList<I> {
+    links: HashMap<I, Link<I>>,
+    head: Option<I>,
+    tail: Option<I>,
+}
+
+impl<I> Default for LruList<I>
+where
+    I: Hash + Eq + Copy,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I> LruList<I>
+where
+    I: Hash + Eq + Copy,
+{
+    pub fn new() -> Self {
+        Self { links: HashMap::new(), head: None, tail: None }
+    }
+
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    pub fn contains(&self, idx: &I) -> bool {
+        self.links.contains_key(idx)
+    }
+
+    pub fn clear(&mut self) {
+        self.links.clear();
+        self.head = None;
+        self.tail = None;
+    }
+
+    // Moves idx to the front of the list, inserting it if it isn't already present
+    pub fn touch(&mut self, idx: I) {
+        if self.head == Some(idx) {
+            return;
+        }
+
+        self.unlink(&idx);
+
+        let link = Link { prev: None, next: self.head };
+
+        match self.head {
+            Some(head) => self.links.get_mut(&head).unwrap().prev = Some(idx),
+            None => self.tail = Some(idx),
+        }
+
+        self.links.insert(idx, link);
+        self.head = Some(idx);
+    }
+
+    // Returns true if idx was in the list
+    pub fn remove(&mut self, idx: &I) -> bool {
+        self.unlink(idx).is_some()
+    }
+
+    pub fn least_recent(&self) -> Option<&I> {
+        self.tail.as_ref()
+    }
+
+    pub fn pop_least_recent(&mut self) -> Option<I> {
+        let tail = self.tail?;
+        self.unlink(&tail);
+
+        Some(tail)
+    }
+
+    // Iterates from the least recently touched index to the most recent
+    pub fn iter(&self) -> impl Iterator<Item = I> + '_ {
+        let mut current = self.tail;
+
+        std::iter::from_fn(move || {
+            let idx = current?;
+            current = self.links[&idx].prev;
+
+            Some(idx)
+        })
+    }
+
+    fn unlink(&mut self, idx: &I) -> Option<Link<I>> {
+        let link = self.links.remove(idx)?;
+
+        match link.prev {
+            Some(prev) => self.links.get_mut(&prev).unwrap().next = link.next,
+            None => self.head = link.next,
+        }
+
+        match link.next {
+            Some(next) => self.links.get_mut(&next).unwrap().prev = link.prev,
+            None => self.tail = link.prev,
+        }
+
+        Some(link)
+    }
+}
@@ -1,44 +1,258 @@
-use super::*;
-use std::any::TypeId;
+use super::{background::BackgroundCounters, lru::LruList, *};
+use std::{
+    any::TypeId,
+    cmp::Ordering,
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering as AtomicOrdering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 pub type ManangedGenSystem<K, L, T, C> =
     ManagedStorage<IdVec<K>, GenerationStorage<GenericPromise<T>>, L, T, IdVec<C>>;
 pub type ManagedGen<K, T, C> = ManangedGenSystem<K, GenericSender<K>, T, C>;
 
-pub trait Counter {
+pub type ManagedNoVecSystem<K, L, T, C> =
+    ManagedStorage<IdVec<K>, NoVec<GenericPromise<T>>, L, T, IdVec<C>>;
+pub type ManagedNoVec<K, T, C> = ManagedNoVecSystem<K, GenericSender<K>, T, C>;
+
+pub type SizeOf<T> = Box<dyn Fn(&T) -> usize>;
+
+// Counters that compare greater are treated as older and are evicted first
+pub trait Counter: PartialOrd {
     fn zero() -> Self;
     fn is_valid(&self, other: &Self) -> bool;
     fn increment(&mut self, value: &Self);
+
+    // Called whenever the entry is used
+    fn reset(&mut self)
+    where
+        Self: Sized,
+    {
+        *self = Self::zero();
+    }
+}
+
+macro_rules! impl_float_counter {
+    ($($float:ty),+) => {
+        $(
+            impl Counter for $float {
+                fn zero() -> Self {
+                    0.0
+                }
+
+                fn is_valid(&self, other: &$float) -> bool {
+                    self < other
+                }
+
+                fn increment(&mut self, value: &$float) {
+                    *self += *value;
+                }
+            }
+        )+
+    };
+}
+
+// Integer counters saturate rather than wrapping back round to look freshly used
+macro_rules! impl_int_counter {
+    ($($int:ty),+) => {
+        $(
+            impl Counter for $int {
+                fn zero() -> Self {
+                    0
+                }
+
+                fn is_valid(&self, other: &$int) -> bool {
+                    self < other
+                }
+
+                fn increment(&mut self, value: &$int) {
+                    *self = self.saturating_add(*value);
+                }
+            }
+        )+
+    };
+}
+
+impl_float_counter!(f32, f64);
+impl_int_counter!(u8, u16, u32, u64, usize);
+
+// Declares a newtype counter that behaves like the counter it wraps
+//
+// newtype_counter!(pub struct Ticks(u16));
+//
+// or implements Counter for an existing single field tuple struct that's already PartialOrd
+//
+// newtype_counter!(impl Ticks);
+#[macro_export]
+macro_rules! newtype_counter {
+    ($(#[$meta:meta])* $vis:vis struct $name:ident($inner_vis:vis $inner:ty);) => {
+        $crate::newtype_counter!($(#[$meta])* $vis struct $name($inner_vis $inner));
+    };
+    ($(#[$meta:meta])* $vis:vis struct $name:ident($inner_vis:vis $inner:ty)) => {
+        $(#[$meta])*
+        #[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+        $vis struct $name($inner_vis $inner);
+
+        $crate::newtype_counter!(impl $name);
+    };
+    (impl $name:ty) => {
+        impl $crate::loader::manager::Counter for $name {
+            fn zero() -> Self {
+                Self($crate::loader::manager::Counter::zero())
+            }
+
+            fn is_valid(&self, other: &Self) -> bool {
+                $crate::loader::manager::Counter::is_valid(&self.0, &other.0)
+            }
+
+            fn increment(&mut self, value: &Self) {
+                $crate::loader::manager::Counter::increment(&mut self.0, &value.0)
+            }
+
+            fn reset(&mut self) {
+                $crate::loader::manager::Counter::reset(&mut self.0)
+            }
+        }
+    };
+}
+
+// A counter measuring the real time since it was last reset. Increments ignore the value given
+// and just sample the clock, so an entry expires once it has gone unused for the threshold's
+// duration
+#[derive(Copy, Clone, Debug)]
+pub struct WallClock {
+    since: Instant,
+    elapsed: Duration,
+}
+
+impl WallClock {
+    pub fn threshold(duration: Duration) -> Self {
+        Self { since: Instant::now(), elapsed: duration }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.since.elapsed()
+    }
+}
+
+impl PartialEq for WallClock {
+    fn eq(&self, other: &Self) -> bool {
+        self.elapsed == other.elapsed
+    }
+}
+
+impl PartialOrd for WallClock {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.elapsed.partial_cmp(&other.elapsed)
+    }
 }
 
-impl Counter for f32 {
+impl Counter for WallClock {
     fn zero() -> Self {
-        0.0
+        Self { since: Instant::now(), elapsed: Duration::from_secs(0) }
     }
 
-    fn is_valid(&self, other: &f32) -> bool {
-        self < other
+    fn is_valid(&self, other: &WallClock) -> bool {
+        self.since.elapsed() < other.elapsed
     }
 
-    fn increment(&mut self, value: &f32) {
-        *self += *value;
+    fn increment(&mut self, _: &WallClock) {
+        self.elapsed = self.since.elapsed();
     }
 }
 
-impl Counter for u32 {
+// A counter tracking how often an entry is used. Every use adds a hit and every increment decays
+// the hits by the given factor, so entries are kept for as long as they are used regularly
+// rather than only if they were used recently
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frequency {
+    hits: f32,
+}
+
+impl Frequency {
+    // Entries are evicted once their decayed hits fall below min_hits
+    pub fn threshold(min_hits: f32) -> Self {
+        Self { hits: min_hits }
+    }
+
+    // The value to pass to increment, each increment multiplies the hits by factor
+    pub fn decay(factor: f32) -> Self {
+        Self { hits: factor }
+    }
+
+    pub fn hits(&self) -> f32 {
+        self.hits
+    }
+}
+
+// Less frequently used entries compare greater so they are evicted first
+impl PartialOrd for Frequency {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        other.hits.partial_cmp(&self.hits)
+    }
+}
+
+impl Counter for Frequency {
     fn zero() -> Self {
-        0
+        Self { hits: 1.0 }
+    }
+
+    fn is_valid(&self, other: &Frequency) -> bool {
+        self.hits >= other.hits
     }
 
-    fn is_valid(&self, other: &u32) -> bool {
-        self < other
+    fn increment(&mut self, value: &Frequency) {
+        self.hits *= value.hits;
     }
 
-    fn increment(&mut self, value: &u32) {
-        *self += *value;
+    fn reset(&mut self) {
+        self.hits += 1.0;
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    // Every loaded entry's counter is incremented by increment and entries are evicted once their
+    // counter passes the threshold
+    Counter,
+    // Entries are kept in a recency list that is updated on access. increment does no work and
+    // eviction always takes the least recently used entries
+    Lru,
+}
+
+// Keeps an entry resident for as long as the guard is alive. Leased entries are skipped by every
+// automatic eviction and have their counters held at zero, explicitly evicting them still works
+#[derive(Debug)]
+pub struct LeaseGuard {
+    count: Arc<AtomicUsize>,
+}
+
+impl Clone for LeaseGuard {
+    fn clone(&self) -> Self {
+        self.count.fetch_add(1, AtomicOrdering::SeqCst);
+        Self { count: self.count.clone() }
+    }
+}
+
+impl Drop for LeaseGuard {
+    fn drop(&mut self) {
+        self.count.fetch_sub(1, AtomicOrdering::SeqCst);
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct EvictionStats {
+    // Entries removed by the eviction policy, explicit calls to evict aren't counted
+    pub total_evictions: usize,
+    // Evictions since the last call to increment
+    pub recent_evictions: usize,
+    // Entries that have finished loading
+    pub resident: usize,
+}
+
 pub struct ManagedStorage<K, S, L, T, C>
 where
     S: ExpandableStorage<Item = Promise<T, L::Item>>,
@@ -54,6 +268,17 @@ where
     storage: StorageSystem<K, S, L, T>,
     counters: C,
     threshold: C::Item,
+    max_entries: Option<usize>,
+    byte_budget: Option<usize>,
+    size_of: Option<SizeOf<T>>,
+    resident_bytes: usize,
+    evicted: VecDeque<(K::Item, S::Index, T)>,
+    evicted_limit: usize,
+    stats: EvictionStats,
+    policy: EvictionPolicy,
+    lru: LruList<S::Index>,
+    background: Option<BackgroundCounters<S::Index, C::Item>>,
+    leases: HashMap<S::Index, Arc<AtomicUsize>>,
 }
 
 impl<K, S, L, T, C> ManagedStorage<K, S, L, T, C>
@@ -73,7 +298,22 @@ where
     where
         C: Default,
     {
-        Self { storage, counters: C::default(), threshold }
+        Self {
+            resident_bytes: storage.resident_bytes(),
+            storage,
+            counters: C::default(),
+            threshold,
+            max_entries: None,
+            byte_budget: None,
+            size_of: None,
+            evicted: VecDeque::new(),
+            evicted_limit: 0,
+            stats: EvictionStats::default(),
+            policy: EvictionPolicy::Counter,
+            lru: LruList::new(),
+            background: None,
+            leases: HashMap::new(),
+        }
     }
 
     pub fn new_with_loader(loader: L, threshold: C::Item) -> Self
@@ -82,7 +322,165 @@ where
         K: Default,
         C: Default,
     {
-        Self { storage: StorageSystem::new_with_loader(loader), counters: C::default(), threshold }
+        Self::new(StorageSystem::new_with_loader(loader), threshold)
+    }
+
+    pub fn builder(threshold: C::Item) -> ManagedStorageBuilder<K, S, L, T, C> {
+        ManagedStorageBuilder::new(threshold)
+    }
+
+    // Caps the number of entries, evicting the oldest loaded entries when a new load would exceed
+    // it. Entries that are still loading can't be evicted so the cap may briefly be exceeded. The
+    // evicted values are dropped unless with_evicted_limit keeps some of them for drain_evicted
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: EvictionPolicy) -> Self {
+        self.set_policy(policy);
+        self
+    }
+
+    // Switching to Lru seeds the recency list with the loaded entries in counter order
+    pub fn set_policy(&mut self, policy: EvictionPolicy) {
+        if self.policy == policy {
+            return;
+        }
+
+        if policy == EvictionPolicy::Lru {
+            for idx in self.eviction_order() {
+                self.lru.touch(idx);
+            }
+        }
+        else {
+            self.lru.clear();
+        }
+
+        self.policy = policy;
+    }
+
+    pub fn policy(&self) -> EvictionPolicy {
+        self.policy
+    }
+
+    // Moves the counter bookkeeping onto a background thread that increments every counter once
+    // per interval. While it runs increment does nothing and the out of date methods evict
+    // whatever the thread has queued. Counter ordered eviction and counters() keep seeing the
+    // values from when the thread was started
+    pub fn spawn_background_eviction(&mut self, interval: Duration, increment: C::Item)
+    where
+        S::Index: Send + 'static,
+        C::Item: Clone + Send + 'static,
+    {
+        let counters = &self.counters;
+
+        let initial = self
+            .storage
+            .storage
+            .indices()
+            .filter_map(|(_, idx)| counters.get(&(*idx).into()).map(|value| (*idx, value.clone())))
+            .collect();
+
+        self.background =
+            Some(BackgroundCounters::spawn_with(initial, interval, increment, self.threshold.clone()));
+    }
+
+    pub fn stop_background_eviction(&mut self) {
+        self.background = None;
+    }
+
+    pub fn set_max_entries(&mut self, max_entries: Option<usize>) {
+        self.max_entries = max_entries;
+    }
+
+    pub fn max_entries(&self) -> Option<usize> {
+        self.max_entries
+    }
+
+    // Keeps up to limit of the values evicted by the entry cap or the byte budget for
+    // drain_evicted, past that the oldest are dropped. Nothing is kept by default so the memory
+    // is freed as soon as they're evicted
+    pub fn with_evicted_limit(mut self, limit: usize) -> Self {
+        self.set_evicted_limit(limit);
+        self
+    }
+
+    pub fn set_evicted_limit(&mut self, limit: usize) {
+        self.evicted_limit = limit;
+
+        while self.evicted.len() > limit {
+            self.evicted.pop_front();
+        }
+    }
+
+    pub fn evicted_limit(&self) -> usize {
+        self.evicted_limit
+    }
+
+    // Tracks the total size of the loaded values and evicts the oldest entries whenever a load
+    // pushes the total over budget. Sizes reported by the loader are used where available, the
+    // rest are measured with size_of
+    pub fn with_byte_budget(mut self, budget: usize, size_of: impl Fn(&T) -> usize + 'static) -> Self {
+        self.size_of = Some(Box::new(size_of));
+        self.byte_budget = Some(budget);
+        self.resident_bytes = self.loaded_indices().iter().map(|idx| self.entry_size(idx)).sum();
+        self
+    }
+
+    // A budget that only counts the sizes reported by the loader
+    pub fn with_reported_byte_budget(mut self, budget: usize) -> Self {
+        self.byte_budget = Some(budget);
+        self
+    }
+
+    pub fn set_byte_budget(&mut self, budget: Option<usize>) {
+        self.byte_budget = budget;
+        self.enforce_byte_budget();
+    }
+
+    pub fn byte_budget(&self) -> Option<usize> {
+        self.byte_budget
+    }
+
+    pub fn resident_bytes(&self) -> usize {
+        self.resident_bytes
+    }
+
+    pub fn stats(&self) -> EvictionStats {
+        EvictionStats { resident: self.storage.iter().count(), ..self.stats }
+    }
+
+    // Iterates over every loaded entry along with its current counter
+    pub fn counters(&self) -> impl Iterator<Item = (&K::Item, &S::Index, &C::Item)> + '_ {
+        let counters = &self.counters;
+
+        self.storage
+            .storage
+            .indices()
+            .filter_map(move |(key, idx)| counters.get(&(*idx).into()).map(|value| (key, idx, value)))
+    }
+
+    pub fn storage(&self) -> &StorageSystem<K, S, L, T> {
+        &self.storage
+    }
+
+    // Changes made directly to the storage system bypass the counters, eviction stats and byte
+    // accounting of the manager
+    pub fn storage_mut(&mut self) -> &mut StorageSystem<K, S, L, T> {
+        &mut self.storage
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &'_ T> + '_ {
+        self.storage.values()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'_ K::Item, &'_ S::Index, &'_ T)> + '_ {
+        self.storage.iter()
+    }
+
+    pub fn statuses(&self) -> impl Iterator<Item = (&'_ K::Item, &'_ S::Index, LoadStatus)> + '_ {
+        self.storage.statuses()
     }
 
     pub fn get(&self, ki: &KeyIdx<K::Item, S::Index>) -> Option<&T> {
@@ -111,39 +509,127 @@ where
 
     pub fn reset_counter(&mut self, idx: S::Index) {
         if let Some(value) = self.counters.get_mut(&idx.into()) {
-            *value = C::Item::zero();
+            value.reset();
+
+            if let Some(background) = &self.background {
+                background.reset(&idx);
+            }
+
+            if self.policy == EvictionPolicy::Lru {
+                self.lru.touch(idx);
+            }
         }
     }
 
+    // Pins the entry so it isn't evicted until the returned guard, and any clones of it, are
+    // dropped. Returns None if the key isn't present
+    pub fn lease(&mut self, ki: &mut KeyIdx<K::Item, S::Index>) -> Option<LeaseGuard> {
+        if !self.storage.set_idx(ki) {
+            return None;
+        }
+
+        let idx = ki.index.unwrap();
+        self.reset_counter(idx);
+
+        let count = self.leases.entry(idx).or_insert_with(|| Arc::new(AtomicUsize::new(0)));
+        count.fetch_add(1, AtomicOrdering::SeqCst);
+
+        Some(LeaseGuard { count: count.clone() })
+    }
+
+    pub fn is_leased(&self, idx: &S::Index) -> bool {
+        match self.leases.get(idx) {
+            Some(count) => count.load(AtomicOrdering::SeqCst) > 0,
+            None => false,
+        }
+    }
+
+    // Gets the value while marking it as used, resetting its counter or moving it to the front of
+    // the recency list
+    pub fn access(&mut self, ki: &mut KeyIdx<K::Item, S::Index>) -> Option<&T> {
+        if !self.storage.set_idx(ki) {
+            return None;
+        }
+
+        let idx = ki.index.unwrap();
+        self.reset_counter(idx);
+        self.storage.get_by_index(&idx)
+    }
+
     pub fn load(&mut self, ki: &mut KeyIdx<K::Item, S::Index>) -> LoadStatus
     where
         K::Item: Clone,
     {
+        if let Some(max) = self.max_entries {
+            if !self.storage.set_idx(ki) {
+                let len = self.storage.storage.len();
+
+                if len >= max {
+                    for evicted in self.evict_oldest(len + 1 - max) {
+                        self.queue_evicted(evicted);
+                    }
+                }
+            }
+        }
+
         self.storage.load(ki)
     }
 
+    // Returns the keys of every loaded entry, most recently used first, so the hot set can be
+    // saved and passed to preload_from on the next run
+    pub fn export_resident_keys(&self) -> Vec<K::Item> {
+        let storage = &self.storage.storage;
+
+        self.eviction_order()
+            .iter()
+            .rev()
+            .filter_map(|idx| storage.get_key(idx))
+            .cloned()
+            .collect()
+    }
+
+    // Same as export_resident_keys with each key tagged with the managed type
+    pub fn export_manifest(&self) -> Vec<ManifestEntry<K::Item>> {
+        self.export_resident_keys().into_iter().map(ManifestEntry::new::<T>).collect()
+    }
+
+    // Preloads the entries of a manifest that were saved for the managed type
+    pub fn preload_manifest(
+        &mut self,
+        manifest: impl IntoIterator<Item = ManifestEntry<K::Item>>,
+    ) -> usize {
+        let keys = manifest.into_iter().filter(|entry| entry.is::<T>()).map(|entry| entry.key);
+        self.preload_from(keys)
+    }
+
+    // Starts loading every key that isn't already present, returning how many loads were started
+    pub fn preload_from(&mut self, keys: impl IntoIterator<Item = K::Item>) -> usize {
+        let mut started = 0;
+
+        for key in keys {
+            let mut ki = KeyIdx::new(key);
+
+            if !self.storage.set_idx(&mut ki) {
+                self.load(&mut ki);
+                started += 1;
+            }
+        }
+
+        started
+    }
+
     pub fn update_loaded(&mut self)
     where
         L::Item: Convert<T>,
     {
-        let storage = &mut self.storage;
-        let counters = &mut self.counters;
-
-        storage.on_update_loaded(|_, idx, _| {
-            counters.insert((*idx).into(), C::Item::zero());
-        });
+        self.on_update_loaded(|_, _, _| {});
     }
 
     pub fn update_loaded_blocking(&mut self)
     where
         L::Item: Convert<T>,
     {
-        let storage = &mut self.storage;
-        let counters = &mut self.counters;
-
-        storage.on_update_loaded_blocking(|_, idx, _| {
-            counters.insert((*idx).into(), C::Item::zero());
-        });
+        self.on_update_loaded_blocking(|_, _, _| {});
     }
 
     pub fn on_update_loaded(&mut self, mut f: impl FnMut(&K::Item, &S::Index, &T))
@@ -152,11 +638,33 @@ where
     {
         let storage = &mut self.storage;
         let counters = &mut self.counters;
+        let background = &self.background;
+        let mut loaded = vec![];
+        let mut lru = match self.policy {
+            EvictionPolicy::Lru => Some(&mut self.lru),
+            EvictionPolicy::Counter => None,
+        };
 
         storage.on_update_loaded(|key, idx, value| {
             counters.insert((*idx).into(), C::Item::zero());
+
+            if let Some(background) = background {
+                background.insert(*idx);
+            }
+
+            if let Some(lru) = lru.as_mut() {
+                lru.touch(*idx);
+            }
+
+            loaded.push(*idx);
             f(key, idx, value);
         });
+
+        for idx in loaded {
+            self.resident_bytes += self.entry_size(&idx);
+        }
+
+        self.enforce_byte_budget();
     }
 
     pub fn on_update_loaded_blocking(&mut self, mut f: impl FnMut(&K::Item, &S::Index, &T))
@@ -165,11 +673,33 @@ where
     {
         let storage = &mut self.storage;
         let counters = &mut self.counters;
+        let background = &self.background;
+        let mut loaded = vec![];
+        let mut lru = match self.policy {
+            EvictionPolicy::Lru => Some(&mut self.lru),
+            EvictionPolicy::Counter => None,
+        };
 
         storage.on_update_loaded_blocking(|key, idx, value| {
             counters.insert((*idx).into(), C::Item::zero());
+
+            if let Some(background) = background {
+                background.insert(*idx);
+            }
+
+            if let Some(lru) = lru.as_mut() {
+                lru.touch(*idx);
+            }
+
+            loaded.push(*idx);
             f(key, idx, value);
         });
+
+        for idx in loaded {
+            self.resident_bytes += self.entry_size(&idx);
+        }
+
+        self.enforce_byte_budget();
     }
 
     pub fn remove_failed<'a>(
@@ -180,57 +710,241 @@ where
     }
 
     pub fn increment(&mut self, inc: &C::Item) {
+        self.stats.recent_evictions = 0;
+        self.leases.retain(|_, count| count.load(AtomicOrdering::SeqCst) > 0);
+
+        if self.policy == EvictionPolicy::Lru || self.background.is_some() {
+            return;
+        }
+
         let storage = &mut self.storage.storage;
         let counters = &mut self.counters;
+        let leases = &self.leases;
 
         for (_, idx, _) in storage.iter_mut() {
             let counter_idx = (*idx).into();
 
             if let Some(value) = counters.get_mut(&counter_idx) {
-                value.increment(inc);
+                if leases.contains_key(idx) {
+                    *value = C::Item::zero();
+                }
+                else {
+                    value.increment(inc);
+                }
             }
         }
     }
 
     pub fn remove_out_of_date(&mut self) {
-        let storage = &mut self.storage.storage;
-        let counters = &mut self.counters;
-        let threshold = &self.threshold;
+        self.drain_out_of_date();
+    }
 
-        storage.retain(|_, idx, _| {
-            let c_idx = (*idx).into();
-            let mut result = true;
-            if let Some(value) = counters.get(&c_idx) {
-                result = value.is_valid(threshold);
-            }
+    // Removes every entry whose counter has passed the threshold, handing back ownership of the
+    // loaded values
+    pub fn drain_out_of_date(&mut self) -> Vec<(K::Item, S::Index, T)> {
+        let out_of_date = self.out_of_date();
+        let mut drained = Vec::with_capacity(out_of_date.len());
 
-            if !result {
-                counters.remove(&c_idx);
+        for idx in out_of_date {
+            if let Some((key, value)) = self.evict_loaded(&idx) {
+                drained.push((key, idx, value));
             }
+        }
 
-            result
-        });
+        drained
     }
 
-    pub fn on_remove_out_of_date(&mut self, mut f: impl FnMut(&K::Item, &S::Index, &mut S::Item)) {
-        let storage = &mut self.storage.storage;
-        let counters = &mut self.counters;
+    fn out_of_date(&self) -> Vec<S::Index> {
+        if let Some(background) = &self.background {
+            let (kept, out_of_date) = background
+                .evictions()
+                .partition(|idx| self.is_leased(idx) || !self.is_loaded(idx));
+
+            // The background thread has already dropped these counters so they start over
+            for idx in kept {
+                background.insert(idx);
+            }
+
+            return out_of_date;
+        }
+
+        let counters = &self.counters;
         let threshold = &self.threshold;
 
-        storage.retain(|key, idx, item| {
-            let c_idx = (*idx).into();
-            let mut result = true;
-            if let Some(value) = counters.get(&c_idx) {
-                result = value.is_valid(threshold);
+        self.storage
+            .storage
+            .indices()
+            .filter(|(_, idx)| match counters.get(&(**idx).into()) {
+                Some(value) => {
+                    !value.is_valid(threshold) && !self.is_leased(idx) && self.is_loaded(idx)
+                }
+                None => false,
+            })
+            .map(|(_, idx)| *idx)
+            .collect()
+    }
+
+    // The eviction order without any leased entries or entries that are being reloaded, evicting
+    // those would cancel the load without the value ever reaching the caller
+    fn evictable(&self) -> Vec<S::Index> {
+        let mut order = self.eviction_order();
+        order.retain(|idx| !self.is_leased(idx) && self.is_loaded(idx));
+        order
+    }
+
+    fn is_loaded(&self, idx: &S::Index) -> bool {
+        matches!(self.storage.storage.get_by_index(idx), Some(Promise::Owned(_)))
+    }
+
+    // Returns the indices of every loaded entry, least recently used or highest counter first
+    fn eviction_order(&self) -> Vec<S::Index> {
+        if self.policy == EvictionPolicy::Lru {
+            return self.lru.iter().collect();
+        }
+
+        let counters = &self.counters;
+
+        let mut candidates: Vec<(S::Index, &C::Item)> = self
+            .storage
+            .storage
+            .indices()
+            .filter_map(|(_, idx)| counters.get(&(*idx).into()).map(|value| (*idx, value)))
+            .collect();
+
+        candidates.sort_by(|a, b| b.1.partial_cmp(a.1).unwrap_or(Ordering::Equal));
+        candidates.into_iter().map(|(idx, _)| idx).collect()
+    }
+
+    // Evicts up to count loaded entries, starting with the highest counters, and returns
+    // ownership of their values
+    pub fn evict_oldest(&mut self, count: usize) -> Vec<(K::Item, S::Index, T)> {
+        let mut evicted = Vec::with_capacity(count);
+
+        for idx in self.evictable().into_iter().take(count) {
+            if let Some((key, value)) = self.evict_loaded(&idx) {
+                evicted.push((key, idx, value));
             }
+        }
+
+        evicted
+    }
+
+    // Removes a single entry along with its counter and any pending load, returning the value if
+    // it had finished loading
+    pub fn evict(&mut self, ki: &KeyIdx<K::Item, S::Index>) -> Option<T> {
+        let idx = self.storage.storage.index_of(ki)?;
+        self.remove_loaded(&idx).map(|(_, value)| value)
+    }
 
-            if !result {
-                counters.remove(&c_idx);
+    // Removes the n least recently used entries and returns ownership of their values. Under the
+    // Counter policy the entries with the highest counters are removed instead
+    pub fn evict_lru(&mut self, n: usize) -> Vec<(K::Item, S::Index, T)> {
+        let mut evicted = Vec::with_capacity(n);
 
-                f(key, idx, item);
+        for idx in self.evictable().into_iter().take(n) {
+            if let Some((key, value)) = self.evict_loaded(&idx) {
+                evicted.push((key, idx, value));
             }
+        }
 
-            result
-        });
+        evicted
+    }
+
+    fn enforce_byte_budget(&mut self) {
+        let budget = match self.byte_budget {
+            Some(budget) if self.resident_bytes > budget => budget,
+            _ => return,
+        };
+
+        for idx in self.evictable() {
+            if self.resident_bytes <= budget {
+                break;
+            }
+
+            if let Some((key, value)) = self.evict_loaded(&idx) {
+                self.queue_evicted((key, idx, value));
+            }
+        }
+    }
+
+    // Takes ownership of what was evicted to make room for new loads, up to the evicted limit
+    pub fn drain_evicted(&mut self) -> impl Iterator<Item = (K::Item, S::Index, T)> + '_ {
+        self.evicted.drain(..)
+    }
+
+    fn queue_evicted(&mut self, evicted: (K::Item, S::Index, T)) {
+        if self.evicted_limit == 0 {
+            return;
+        }
+
+        if self.evicted.len() == self.evicted_limit {
+            self.evicted.pop_front();
+        }
+
+        self.evicted.push_back(evicted);
+    }
+
+    // Removes a loaded entry on behalf of the eviction policy, counting it towards the stats
+    fn evict_loaded(&mut self, idx: &S::Index) -> Option<(K::Item, T)> {
+        let evicted = self.remove_loaded(idx)?;
+        self.count_eviction();
+        Some(evicted)
+    }
+
+    fn count_eviction(&mut self) {
+        self.stats.total_evictions += 1;
+        self.stats.recent_evictions += 1;
+    }
+
+    // Removes the entry at idx along with its counter, returning the key and value if the value
+    // had finished loading
+    fn remove_loaded(&mut self, idx: &S::Index) -> Option<(K::Item, T)> {
+        match self.remove_entry(idx)? {
+            (key, Promise::Owned(value)) => Some((key, value)),
+            (_, Promise::Waiting(_)) => None,
+        }
+    }
+
+    // The size reported by the loader, falling back to size_of. Entries still loading are 0
+    fn entry_size(&self, idx: &S::Index) -> usize {
+        if let Some(size) = self.storage.reported_size_by_index(idx) {
+            return size;
+        }
+
+        match (&self.size_of, self.storage.get_by_index(idx)) {
+            (Some(size_of), Some(value)) => size_of(value),
+            _ => 0,
+        }
+    }
+
+    fn loaded_indices(&self) -> Vec<S::Index> {
+        self.storage.iter().map(|(_, idx, _)| *idx).collect()
+    }
+
+    fn remove_entry(&mut self, idx: &S::Index) -> Option<(K::Item, S::Item)> {
+        let key = self.storage.storage.get_key(idx)?.clone();
+        let size = self.entry_size(idx);
+        let item = self.storage.remove_with_index(idx)?;
+
+        self.counters.remove(&(*idx).into());
+        self.lru.remove(idx);
+        self.leases.remove(idx);
+
+        if let Some(background) = &self.background {
+            background.remove(idx);
+        }
+
+        self.resident_bytes = self.resident_bytes.saturating_sub(size);
+
+        Some((key, item))
+    }
+
+    pub fn on_remove_out_of_date(&mut self, mut f: impl FnMut(&K::Item, &S::Index, &mut S::Item)) {
+        for idx in self.out_of_date() {
+            if let Some((key, mut item)) = self.remove_entry(&idx) {
+                self.count_eviction();
+                f(&key, &idx, &mut item);
+            }
+        }
     }
 }
@@ -1,19 +1,31 @@
+pub mod background;
+pub mod builder;
 pub mod file_mapper;
+pub mod group;
+pub mod lru;
 pub mod manager;
 pub mod promised;
+pub mod routing;
 
 use crate::{
-    generation::GenerationStorage, idvec::IdVec, map::MappedStorage, novec::NoVec,
+    densevec::DenseVec,
+    generation::GenerationStorage,
+    idvec::IdVec,
+    map::{DuplicateKey, DuplicatePolicy, MappedStorage},
+    novec::NoVec,
     ExpandableStorage, KeyIdx, UnorderedStorage,
 };
 use cbc::*;
 use std::{
     any::{Any, TypeId},
+    collections::HashMap,
     error::Error,
     fmt::{self, Display, Formatter},
     hash::Hash,
 };
 
+pub use builder::*;
+pub use group::*;
 pub use promised::*;
 
 pub type GenericSender<K> = Sender<(K, PromiseSender<GenericResult, TypeId>)>;
@@ -23,12 +35,42 @@ pub type GenericPromise<T> = Promise<T, GenericResult>;
 pub type NoVecSystem<K, L, T> = StorageSystem<IdVec<K>, NoVec<GenericPromise<T>>, L, T>;
 pub type NoVecLoader<K, T> = NoVecSystem<K, GenericSender<K>, T>;
 
+pub type DenseVecSystem<K, L, T> = StorageSystem<IdVec<K>, DenseVec<GenericPromise<T>>, L, T>;
+pub type DenseVecLoader<K, T> = DenseVecSystem<K, GenericSender<K>, T>;
+
 pub type GenSystem<K, L, T> = StorageSystem<IdVec<K>, GenerationStorage<GenericPromise<T>>, L, T>;
 pub type GenLoader<K, T> = GenSystem<K, GenericSender<K>, T>;
 
 pub trait Convert<T> {
     type Error;
     fn convert(self) -> Result<T, Self::Error>;
+
+    // The size in bytes of the decoded value if the loader reported one
+    fn size(&self) -> Option<usize> {
+        None
+    }
+}
+
+// A key that was loaded along with the type it was loaded as, so one saved manifest can be handed
+// to several storage systems and each only picks up its own keys
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManifestEntry<K> {
+    pub key: K,
+    pub type_name: String,
+}
+
+impl<K> ManifestEntry<K> {
+    pub fn new<T: ?Sized>(key: K) -> Self {
+        Self {
+            key,
+            type_name: std::any::type_name::<T>().to_string(),
+        }
+    }
+
+    pub fn is<T: ?Sized>(&self) -> bool {
+        self.type_name == std::any::type_name::<T>()
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -38,6 +80,62 @@ pub enum LoadStatus {
     StartedLoading,
 }
 
+// Values that can be built up piece by piece from a streaming load, so the whole asset never has
+// to sit in one message as well as in storage
+pub trait Assemble {
+    type Chunk;
+
+    // What's available before the first chunk arrives
+    fn empty() -> Self;
+    fn append(&mut self, chunk: Self::Chunk);
+}
+
+impl<T> Assemble for Vec<T> {
+    type Chunk = Vec<T>;
+
+    fn empty() -> Self {
+        vec![]
+    }
+
+    fn append(&mut self, mut chunk: Vec<T>) {
+        Vec::append(self, &mut chunk);
+    }
+}
+
+impl Assemble for String {
+    type Chunk = String;
+
+    fn empty() -> Self {
+        String::new()
+    }
+
+    fn append(&mut self, chunk: String) {
+        self.push_str(&chunk);
+    }
+}
+
+// How far along a streaming load is, bytes only counts chunks the loader reported a size for
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StreamStatus {
+    pub chunks: usize,
+    pub bytes: usize,
+}
+
+struct Stream<T, U> {
+    chunks: Receiver<StreamMessage<U>>,
+    partial: Option<T>,
+    status: StreamStatus,
+}
+
+// How often a load that failed is requested again before its error is reported
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RetryPolicy {
+    #[default]
+    Never,
+    // Retries on top of the first attempt, each one goes out with the original load's options
+    Times(u32),
+}
+
 pub trait Loader {
     type Key;
     type Item;
@@ -65,6 +163,8 @@ impl Error for GenericError {}
 
 pub enum GenericResult {
     Ok(Box<dyn Any + Send + Sync>),
+    // A value along with its size in bytes, used for budget accounting
+    Sized(Box<dyn Any + Send + Sync>, usize),
     Err(Box<dyn Error + Send + Sync>),
 }
 
@@ -73,6 +173,10 @@ impl GenericResult {
         Self::Ok(Box::new(item) as Box<dyn Any + Send + Sync>)
     }
 
+    pub fn new_sized<T: 'static + Send + Sync>(item: T, bytes: usize) -> Self {
+        Self::Sized(Box::new(item) as Box<dyn Any + Send + Sync>, bytes)
+    }
+
     pub fn new_error<T: 'static + Error + Send + Sync>(error: T) -> Self {
         Self::Err(Box::new(error) as Box<dyn Error + Send + Sync>)
     }
@@ -83,13 +187,22 @@ impl<T: 'static> Convert<T> for GenericResult {
 
     fn convert(self) -> Result<T, Self::Error> {
         match self {
-            GenericResult::Ok(value) => match (value as Box<dyn Any>).downcast::<T>() {
-                Ok(value) => Ok(*value),
-                Err(_) => Err(GenericError::InvalidType),
-            },
+            GenericResult::Ok(value) | GenericResult::Sized(value, _) => {
+                match (value as Box<dyn Any>).downcast::<T>() {
+                    Ok(value) => Ok(*value),
+                    Err(_) => Err(GenericError::InvalidType),
+                }
+            }
             GenericResult::Err(e) => Err(GenericError::Error(e)),
         }
     }
+
+    fn size(&self) -> Option<usize> {
+        match self {
+            GenericResult::Sized(_, bytes) => Some(*bytes),
+            _ => None,
+        }
+    }
 }
 
 impl<K> Loader for GenericSender<K> {
@@ -126,6 +239,19 @@ where
         PromiseError<<L::Item as Convert<T>>::Error>,
     )>,
     loader: L,
+    duplicate_policy: DuplicatePolicy,
+    retry_policy: RetryPolicy,
+    // How many times each load that can still be retried has been, with the options to retry it with
+    retries: HashMap<K::Item, (u32, LoadOptions)>,
+    // Streaming loads that haven't finished yet along with what has arrived of them
+    streams: HashMap<K::Item, Stream<T, L::Item>>,
+    groups: HashMap<String, LoadGroup>,
+    active_group: Option<LoadGroup>,
+    // The groups waiting on each pending load
+    group_members: HashMap<K::Item, Vec<LoadGroup>>,
+    // Sizes reported by the loader for the loaded entries
+    sizes: HashMap<K::Item, usize>,
+    resident_bytes: usize,
 }
 
 impl<K, S, L, T> StorageSystem<K, S, L, T>
@@ -145,12 +271,7 @@ where
         K: Default,
         L: Default,
     {
-        Self {
-            storage: MappedStorage::new(),
-            pending_load: Vec::new(),
-            load_errors: vec![],
-            loader: L::default(),
-        }
+        Self::new_with_loader(L::default())
     }
 
     pub fn new_with_loader(loader: L) -> Self
@@ -158,11 +279,181 @@ where
         S: Default,
         K: Default,
     {
+        Self::with_storage(MappedStorage::new(), loader)
+    }
+
+    // Starts from an existing, possibly preallocated, storage. Anything already in it is treated
+    // as loaded or loading by whoever put it there
+    pub fn with_storage(storage: MappedStorage<K, S>, loader: L) -> Self {
         Self {
-            storage: MappedStorage::new(),
+            storage,
             pending_load: Vec::new(),
             load_errors: vec![],
             loader,
+            duplicate_policy: DuplicatePolicy::KeepExisting,
+            retry_policy: RetryPolicy::Never,
+            retries: HashMap::new(),
+            streams: HashMap::new(),
+            groups: HashMap::new(),
+            active_group: None,
+            group_members: HashMap::new(),
+            sizes: HashMap::new(),
+            resident_bytes: 0,
+        }
+    }
+
+    pub fn builder() -> StorageSystemBuilder<K, S, L, T> {
+        StorageSystemBuilder::new()
+    }
+
+    // Decides what 'load' does with a key that's already loaded or loading, by default the
+    // existing entry is kept
+    pub fn with_duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = policy;
+        self
+    }
+
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy;
+    }
+
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.duplicate_policy
+    }
+
+    // Failed loads are requested again up to the policy's limit before they're reported through
+    // were_errors and remove_failed
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    // Only affects loads started after the change
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+
+        if policy == RetryPolicy::Never {
+            self.retries.clear();
+        }
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    // Loads from now until end_group count towards the named group, beginning a group that
+    // already exists carries on counting into it. Keys that are already loaded count as loaded
+    pub fn begin_group(&mut self, name: &str) -> LoadGroup {
+        let group = self.groups.entry(name.to_string()).or_insert_with(|| LoadGroup::new(name));
+        self.active_group = Some(group.clone());
+
+        group.clone()
+    }
+
+    // Same as begin_group but with a group from elsewhere, such as another storage system, so
+    // both count towards the same totals
+    pub fn join_group(&mut self, group: &LoadGroup) {
+        self.groups.insert(group.name().to_string(), group.clone());
+        self.active_group = Some(group.clone());
+    }
+
+    pub fn end_group(&mut self) -> Option<LoadGroup> {
+        self.active_group.take()
+    }
+
+    pub fn active_group(&self) -> Option<&LoadGroup> {
+        self.active_group.as_ref()
+    }
+
+    pub fn group(&self, name: &str) -> Option<&LoadGroup> {
+        self.groups.get(name)
+    }
+
+    pub fn group_status(&self, name: &str) -> Option<GroupStatus> {
+        self.groups.get(name).map(LoadGroup::status)
+    }
+
+    // Forgets the group, loads already attributed to it still update its handles
+    pub fn remove_group(&mut self, name: &str) -> Option<LoadGroup> {
+        if self.active_group.as_ref().is_some_and(|group| group.name() == name) {
+            self.active_group = None;
+        }
+
+        self.groups.remove(name)
+    }
+
+    // The total of every size reported by the loader for the entries currently loaded
+    pub fn resident_bytes(&self) -> usize {
+        self.resident_bytes
+    }
+
+    pub fn reported_size(&self, ki: &KeyIdx<K::Item, S::Index>) -> Option<usize> {
+        let idx = self.storage.index_of(ki)?;
+        self.reported_size_by_index(&idx)
+    }
+
+    pub fn reported_size_by_index(&self, idx: &S::Index) -> Option<usize> {
+        let key = self.storage.get_key(idx)?;
+        self.sizes.get(key).copied()
+    }
+
+    fn record_size(
+        sizes: &mut HashMap<K::Item, usize>,
+        resident_bytes: &mut usize,
+        key: &K::Item,
+        size: Option<usize>,
+    ) {
+        if let Some(size) = size {
+            *resident_bytes += size;
+
+            if let Some(old) = sizes.insert(key.clone(), size) {
+                *resident_bytes -= old;
+            }
+        }
+    }
+
+    // Requests a failed load again if the retry policy allows it, returning whether it did
+    fn retry(
+        loader: &L,
+        retries: &mut HashMap<K::Item, (u32, LoadOptions)>,
+        policy: RetryPolicy,
+        key: &K::Item,
+        promise: &mut Promise<T, L::Item>,
+    ) -> bool {
+        let max = match policy {
+            RetryPolicy::Never => return false,
+            RetryPolicy::Times(max) => max,
+        };
+
+        let (attempts, options) = match retries.get_mut(key) {
+            Some(retry) => retry,
+            None => return false,
+        };
+
+        if *attempts >= max {
+            retries.remove(key);
+            return false;
+        }
+
+        *attempts += 1;
+
+        let (waiting, lock) = Promise::new_waiting_with(TypeId::of::<T>(), options.clone());
+        *promise = waiting;
+        loader.load(key.clone(), lock);
+
+        true
+    }
+
+    // Lets the groups waiting on the key know how its load went
+    fn settle_groups(members: &mut HashMap<K::Item, Vec<LoadGroup>>, key: &K::Item, loaded: bool) {
+        for group in members.remove(key).into_iter().flatten() {
+            group.settle(loaded);
+        }
+    }
+
+    fn forget_size(&mut self, key: &K::Item) {
+        if let Some(size) = self.sizes.remove(key) {
+            self.resident_bytes -= size;
         }
     }
 
@@ -211,18 +502,244 @@ where
     }
 
     pub fn load(&mut self, ki: &mut KeyIdx<K::Item, S::Index>) -> LoadStatus {
-        match self.storage.set_idx_get(ki) {
-            Some(Promise::Owned(_)) => return LoadStatus::Loaded,
-            Some(Promise::Waiting(_)) => return LoadStatus::Loading,
-            _ => (),
+        self.load_with(ki, LoadOptions::none())
+    }
+
+    // The options are handed to the loader along with the request, they're ignored if the
+    // existing entry is kept
+    pub fn load_with(
+        &mut self,
+        ki: &mut KeyIdx<K::Item, S::Index>,
+        options: LoadOptions,
+    ) -> LoadStatus {
+        match self.try_load_with(ki, options) {
+            Ok(status) => status,
+            Err(_) => self.get_status(ki).unwrap(),
+        }
+    }
+
+    // Same as load_with but fails with the unused options when the key is already loaded or
+    // loading under 'DuplicatePolicy::Error'
+    pub fn try_load_with(
+        &mut self,
+        ki: &mut KeyIdx<K::Item, S::Index>,
+        options: LoadOptions,
+    ) -> Result<LoadStatus, DuplicateKey<S::Index, LoadOptions>> {
+        self.start_load(ki, options, false)
+    }
+
+    // Same as load but the loader may send the value in chunks, see PromiseSender::send_chunk.
+    // Streaming loads are only advanced by update_streaming and aren't retried
+    pub fn load_streaming(&mut self, ki: &mut KeyIdx<K::Item, S::Index>) -> LoadStatus {
+        self.load_streaming_with(ki, LoadOptions::none())
+    }
+
+    pub fn load_streaming_with(
+        &mut self,
+        ki: &mut KeyIdx<K::Item, S::Index>,
+        options: LoadOptions,
+    ) -> LoadStatus {
+        match self.start_load(ki, options, true) {
+            Ok(status) => status,
+            Err(_) => self.get_status(ki).unwrap(),
+        }
+    }
+
+    fn start_load(
+        &mut self,
+        ki: &mut KeyIdx<K::Item, S::Index>,
+        options: LoadOptions,
+        streaming: bool,
+    ) -> Result<LoadStatus, DuplicateKey<S::Index, LoadOptions>> {
+        let result = self.request_load(ki, options, streaming);
+
+        if let Some(group) = &self.active_group {
+            match self.get_status(ki) {
+                Some(LoadStatus::Loaded) => group.add_loaded(),
+                Some(_) => {
+                    let members = self.group_members.entry(ki.key.clone()).or_default();
+
+                    if !members.iter().any(|member| member.same_group(group)) {
+                        group.add_pending();
+                        members.push(group.clone());
+                    }
+                }
+                None => (),
+            }
+        }
+
+        result
+    }
+
+    fn request_load(
+        &mut self,
+        ki: &mut KeyIdx<K::Item, S::Index>,
+        options: LoadOptions,
+        streaming: bool,
+    ) -> Result<LoadStatus, DuplicateKey<S::Index, LoadOptions>> {
+        let status = match self.storage.set_idx_get(ki) {
+            Some(Promise::Owned(_)) => Some(LoadStatus::Loaded),
+            Some(Promise::Waiting(_)) => Some(LoadStatus::Loading),
+            None => None,
+        };
+
+        let policy = match (status, self.duplicate_policy) {
+            (None, _) | (_, DuplicatePolicy::ReplaceKeepIndex) => {
+                DuplicatePolicy::ReplaceKeepIndex
+            }
+            (_, DuplicatePolicy::ReplaceNewIndex) => DuplicatePolicy::ReplaceNewIndex,
+            (Some(status), DuplicatePolicy::KeepExisting) => return Ok(status),
+            (Some(_), DuplicatePolicy::Error) => {
+                let index = ki.index.unwrap();
+                return Err(DuplicateKey { index, value: options });
+            }
+        };
+
+        if status.is_some() {
+            let key = ki.key.clone();
+            self.forget_size(&key);
+            self.streams.remove(&key);
+        }
+
+        if self.retry_policy != RetryPolicy::Never && !streaming {
+            self.retries.insert(ki.key.clone(), (0, options.clone()));
+        }
+
+        let (promise, lock) = if streaming {
+            let (promise, lock, chunks) = Promise::new_streaming_with(TypeId::of::<T>(), options);
+            let stream = Stream { chunks, partial: None, status: StreamStatus::default() };
+            self.streams.insert(ki.key.clone(), stream);
+
+            (promise, lock)
         }
+        else {
+            Promise::new_waiting_with(TypeId::of::<T>(), options)
+        };
+        let index = match self.storage.insert_with_policy(ki.key.clone(), promise, policy) {
+            Ok((index, _)) => index,
+            Err(_) => unreachable!("Replacing never fails"),
+        };
 
-        let (promise, lock) = Promise::new_waiting(TypeId::of::<T>());
-        self.storage.insert_replace_idx(ki, promise);
+        ki.index = Some(index);
         self.loader.load(ki.key.clone(), lock);
-        self.pending_load.push(ki.index.unwrap());
 
-        LoadStatus::Loading
+        // A load replaced in place is already pending
+        if status != Some(LoadStatus::Loading) || policy != DuplicatePolicy::ReplaceKeepIndex {
+            self.pending_load.push(index);
+        }
+
+        Ok(LoadStatus::Loading)
+    }
+
+    // Loads the entry if needed and waits for it. A failed load is removed so it can be retried
+    pub fn load_blocking(
+        &mut self,
+        ki: &mut KeyIdx<K::Item, S::Index>,
+    ) -> Result<&T, PromiseError<<L::Item as Convert<T>>::Error>>
+    where
+        S::Index: PartialEq,
+    {
+        self.load(ki);
+
+        let idx = ki.index.unwrap();
+        self.pending_load.retain(|pending| *pending != idx);
+
+        let result = loop {
+            let promise = self.storage.get_by_index_mut(&idx).unwrap();
+
+            match promise.update_blocking_sized() {
+                Err(e) => {
+                    let policy = self.retry_policy;
+
+                    if !Self::retry(&self.loader, &mut self.retries, policy, &ki.key, promise) {
+                        break Err(e);
+                    }
+                }
+                result => break result,
+            }
+        };
+
+        match result {
+            Ok((_, size)) => {
+                self.retries.remove(&ki.key);
+                Self::settle_groups(&mut self.group_members, &ki.key, true);
+                Self::record_size(&mut self.sizes, &mut self.resident_bytes, &ki.key, size)
+            }
+            Err(e) => {
+                Self::settle_groups(&mut self.group_members, &ki.key, false);
+                self.storage.remove_with_index(&idx);
+                return Err(e);
+            }
+        }
+
+        Ok(self.storage.get_by_index(&idx).unwrap().unwrap_ref())
+    }
+
+    // Fills the entry with a locally computed value unless it's already loaded, without going
+    // through the loader. A load still in flight for the key is dropped
+    pub fn get_or_insert_with(
+        &mut self,
+        ki: &mut KeyIdx<K::Item, S::Index>,
+        f: impl FnOnce() -> T,
+    ) -> &T
+    where
+        S::Index: PartialEq,
+    {
+        let loaded = match self.storage.set_idx_get(ki) {
+            Some(promise) => promise.is_owned(),
+            None => {
+                ki.index = None;
+                false
+            }
+        };
+
+        if !loaded {
+            if let Some(idx) = ki.index {
+                self.pending_load.retain(|pending| *pending != idx);
+            }
+
+            let key = ki.key.clone();
+            let promise = Promise::Owned(f());
+            self.retries.remove(&key);
+            self.streams.remove(&key);
+            Self::settle_groups(&mut self.group_members, &key, true);
+
+            let policy = DuplicatePolicy::ReplaceKeepIndex;
+
+            ki.index = match self.storage.insert_with_policy(key, promise, policy) {
+                Ok((index, _)) => Some(index),
+                Err(_) => unreachable!("Replacing never fails"),
+            };
+        }
+
+        self.storage.get_by_index(ki.index_ref().unwrap()).unwrap().unwrap_ref()
+    }
+
+    // Removes the entry along with any load that is still pending for it
+    pub fn remove(&mut self, ki: &KeyIdx<K::Item, S::Index>) -> Option<S::Item>
+    where
+        S::Index: PartialEq,
+    {
+        let idx = self.storage.index_of(ki)?;
+        self.remove_with_index(&idx)
+    }
+
+    pub fn remove_with_index(&mut self, idx: &S::Index) -> Option<S::Item>
+    where
+        S::Index: PartialEq,
+    {
+        self.pending_load.retain(|pending| pending != idx);
+
+        if let Some(key) = self.storage.get_key(idx).cloned() {
+            self.retries.remove(&key);
+            self.streams.remove(&key);
+            self.forget_size(&key);
+
+            // A load that's removed before it finishes never will
+            Self::settle_groups(&mut self.group_members, &key, false);
+        }
+
+        self.storage.remove_with_index(idx)
     }
 
     pub fn update_loaded(&mut self)
@@ -232,17 +749,45 @@ where
         let pending = &mut self.pending_load;
         let storage = &mut self.storage;
         let errors = &mut self.load_errors;
+        let sizes = &mut self.sizes;
+        let resident_bytes = &mut self.resident_bytes;
+        let (loader, retries, policy) = (&self.loader, &mut self.retries, self.retry_policy);
+        let streams = &self.streams;
+        let members = &mut self.group_members;
 
         pending.retain(|idx| {
+            if storage.get_key(idx).is_some_and(|key| streams.contains_key(key)) {
+                return true;
+            }
+
             let value = match storage.get_by_index_mut(idx) {
                 Some(value) => value,
                 None => return false,
             };
 
-            match value.update() {
-                Ok(status) => status == UpdateStatus::Waiting,
+            match value.update_sized() {
+                Ok((status, size)) => {
+                    if let Some(key) = storage.get_key(idx) {
+                        Self::record_size(sizes, resident_bytes, key, size);
+
+                        if status == UpdateStatus::Updated {
+                            retries.remove(key);
+                            Self::settle_groups(members, key, true);
+                        }
+                    }
+
+                    status == UpdateStatus::Waiting
+                }
                 Err(e) => {
-                    errors.push((storage.get_key(idx).unwrap().clone(), *idx, e));
+                    let key = storage.get_key(idx).unwrap().clone();
+                    let value = storage.get_by_index_mut(idx).unwrap();
+
+                    if Self::retry(loader, retries, policy, &key, value) {
+                        return true;
+                    }
+
+                    Self::settle_groups(members, &key, false);
+                    errors.push((key, *idx, e));
                     false
                 }
             }
@@ -256,17 +801,45 @@ where
         let pending = &mut self.pending_load;
         let storage = &mut self.storage;
         let errors = &mut self.load_errors;
+        let sizes = &mut self.sizes;
+        let resident_bytes = &mut self.resident_bytes;
+        let (loader, retries, policy) = (&self.loader, &mut self.retries, self.retry_policy);
+        let streams = &self.streams;
+        let members = &mut self.group_members;
 
         pending.retain(|idx| {
+            if storage.get_key(idx).is_some_and(|key| streams.contains_key(key)) {
+                return true;
+            }
+
             let value = match storage.get_by_index_mut(idx) {
                 Some(value) => value,
                 None => return false,
             };
 
-            match value.update_blocking() {
-                Ok(status) => status == UpdateStatus::Waiting,
+            match value.update_blocking_sized() {
+                Ok((status, size)) => {
+                    if let Some(key) = storage.get_key(idx) {
+                        Self::record_size(sizes, resident_bytes, key, size);
+
+                        if status == UpdateStatus::Updated {
+                            retries.remove(key);
+                            Self::settle_groups(members, key, true);
+                        }
+                    }
+
+                    status == UpdateStatus::Waiting
+                }
                 Err(e) => {
-                    errors.push((storage.get_key(idx).unwrap().clone(), *idx, e));
+                    let key = storage.get_key(idx).unwrap().clone();
+                    let value = storage.get_by_index_mut(idx).unwrap();
+
+                    if Self::retry(loader, retries, policy, &key, value) {
+                        return true;
+                    }
+
+                    Self::settle_groups(members, &key, false);
+                    errors.push((key, *idx, e));
                     false
                 }
             }
@@ -278,10 +851,28 @@ where
     where
         L::Item: Convert<T>,
     {
+        let (loader, retries, policy) = (&self.loader, &mut self.retries, self.retry_policy);
+
         for (key, idx, value) in self.storage.iter_mut() {
-            match value.update() {
-                Ok(UpdateStatus::Updated) => f(key, idx, value.get().unwrap()),
-                Err(e) => self.load_errors.push((key.clone(), *idx, e)),
+            if self.streams.contains_key(key) {
+                continue;
+            }
+
+            match value.update_sized() {
+                Ok((UpdateStatus::Updated, size)) => {
+                    retries.remove(key);
+                    Self::settle_groups(&mut self.group_members, key, true);
+                    Self::record_size(&mut self.sizes, &mut self.resident_bytes, key, size);
+                    f(key, idx, value.get().unwrap())
+                }
+                Err(e) => {
+                    let retried = Self::retry(loader, retries, policy, key, value);
+
+                    if !retried {
+                        Self::settle_groups(&mut self.group_members, key, false);
+                        self.load_errors.push((key.clone(), *idx, e))
+                    }
+                }
                 _ => (),
             }
         }
@@ -291,15 +882,120 @@ where
     where
         L::Item: Convert<T>,
     {
+        let (loader, retries, policy) = (&self.loader, &mut self.retries, self.retry_policy);
+
         for (key, idx, value) in self.storage.iter_mut() {
-            match value.update_blocking() {
-                Ok(UpdateStatus::Updated) => f(key, idx, value.get().unwrap()),
-                Err(e) => self.load_errors.push((key.clone(), *idx, e)),
+            if self.streams.contains_key(key) {
+                continue;
+            }
+
+            match value.update_blocking_sized() {
+                Ok((UpdateStatus::Updated, size)) => {
+                    retries.remove(key);
+                    Self::settle_groups(&mut self.group_members, key, true);
+                    Self::record_size(&mut self.sizes, &mut self.resident_bytes, key, size);
+                    f(key, idx, value.get().unwrap())
+                }
+                Err(e) => {
+                    let retried = Self::retry(loader, retries, policy, key, value);
+
+                    if !retried {
+                        Self::settle_groups(&mut self.group_members, key, false);
+                        self.load_errors.push((key.clone(), *idx, e))
+                    }
+                }
                 _ => (),
             }
         }
     }
 
+    // Appends whatever chunks have arrived for the streaming loads. A stream that's finished
+    // becomes loaded like any other entry, as does one the loader sent whole instead
+    pub fn update_streaming(&mut self)
+    where
+        T: Assemble,
+        S::Index: PartialEq,
+        L::Item: Convert<T::Chunk, Error = <L::Item as Convert<T>>::Error>,
+    {
+        let keys: Vec<_> = self.streams.keys().cloned().collect();
+
+        for key in keys {
+            let stream = self.streams.get_mut(&key).unwrap();
+            let idx = *self.storage.get_index(&key).unwrap();
+            let promise = self.storage.get_by_index_mut(&idx).unwrap();
+
+            let result = match Self::drain_stream(stream) {
+                Ok(false) => match promise.update_sized() {
+                    Ok((UpdateStatus::Waiting, _)) => continue,
+                    Ok((_, size)) => {
+                        Self::record_size(&mut self.sizes, &mut self.resident_bytes, &key, size);
+                        Ok(false)
+                    }
+                    // The stream may have been finished just before the sender was dropped
+                    Err(e) => match Self::drain_stream(stream) {
+                        Ok(true) => Ok(true),
+                        Ok(false) => Err(e),
+                        Err(e) => Err(e),
+                    },
+                },
+                result => result,
+            };
+
+            let stream = self.streams.remove(&key).unwrap();
+            self.pending_load.retain(|pending| *pending != idx);
+            Self::settle_groups(&mut self.group_members, &key, result.is_ok());
+
+            match result {
+                Ok(true) => {
+                    *promise = Promise::Owned(stream.partial.unwrap_or_else(T::empty));
+
+                    let size = Some(stream.status.bytes).filter(|bytes| *bytes > 0);
+                    Self::record_size(&mut self.sizes, &mut self.resident_bytes, &key, size);
+                }
+                Ok(false) => (),
+                Err(e) => self.load_errors.push((key, idx, e)),
+            }
+        }
+    }
+
+    // Appends the chunks that have arrived, returning whether the stream has ended
+    fn drain_stream(
+        stream: &mut Stream<T, L::Item>,
+    ) -> Result<bool, PromiseError<<L::Item as Convert<T>>::Error>>
+    where
+        T: Assemble,
+        L::Item: Convert<T::Chunk, Error = <L::Item as Convert<T>>::Error>,
+    {
+        loop {
+            match stream.chunks.try_recv() {
+                Ok(StreamMessage::Chunk(chunk)) => {
+                    let size = <L::Item as Convert<T::Chunk>>::size(&chunk);
+                    let chunk = <L::Item as Convert<T::Chunk>>::convert(chunk)
+                        .map_err(PromiseError::LoadError)?;
+
+                    stream.partial.get_or_insert_with(T::empty).append(chunk);
+                    stream.status.chunks += 1;
+                    stream.status.bytes += size.unwrap_or(0);
+                }
+                Ok(StreamMessage::End) => return Ok(true),
+                Err(_) => return Ok(false),
+            }
+        }
+    }
+
+    // What has arrived so far of a streaming load, or the whole value once it's loaded
+    pub fn get_partial(&self, ki: &KeyIdx<K::Item, S::Index>) -> Option<&T> {
+        match self.streams.get(&ki.key) {
+            Some(stream) => stream.partial.as_ref(),
+            None => self.get(ki),
+        }
+    }
+
+    // None once the stream has finished or if the load wasn't streaming
+    pub fn stream_status(&self, ki: &KeyIdx<K::Item, S::Index>) -> Option<StreamStatus> {
+        self.streams.get(&ki.key).map(|stream| stream.status)
+    }
+
     pub fn were_errors(&self) -> bool {
         !self.load_errors.is_empty()
     }
@@ -320,10 +1016,54 @@ where
         self.load_errors.drain(..)
     }
 
+    // Lists every key that is loaded or loading so it can be saved and passed to preload_manifest
+    // on the next run
+    pub fn export_manifest(&self) -> Vec<ManifestEntry<K::Item>> {
+        self.storage
+            .indices()
+            .map(|(key, _)| ManifestEntry::new::<T>(key.clone()))
+            .collect()
+    }
+
+    // Starts loading every entry meant for this system's type that isn't already present,
+    // returning how many loads were started
+    pub fn preload_manifest(
+        &mut self,
+        manifest: impl IntoIterator<Item = ManifestEntry<K::Item>>,
+    ) -> usize {
+        let mut started = 0;
+
+        for entry in manifest.into_iter().filter(|entry| entry.is::<T>()) {
+            let mut ki = KeyIdx::new(entry.key);
+
+            if !self.storage.set_idx(&mut ki) {
+                self.load(&mut ki);
+                started += 1;
+            }
+        }
+
+        started
+    }
+
     pub fn values(&self) -> impl Iterator<Item = &'_ T> + '_ {
         self.storage
             .iter()
             .filter(|(_, _, promise)| promise.is_owned())
             .map(|(_, _, promise)| promise.unwrap_ref())
     }
+
+    // Iterates over the entries that have finished loading
+    pub fn iter(&self) -> impl Iterator<Item = (&'_ K::Item, &'_ S::Index, &'_ T)> + '_ {
+        self.storage
+            .iter()
+            .filter(|(_, _, promise)| promise.is_owned())
+            .map(|(key, idx, promise)| (key, idx, promise.unwrap_ref()))
+    }
+
+    pub fn statuses(&self) -> impl Iterator<Item = (&'_ K::Item, &'_ S::Index, LoadStatus)> + '_ {
+        self.storage.iter().map(|(key, idx, promise)| match promise {
+            Promise::Owned(_) => (key, idx, LoadStatus::Loaded),
+            Promise::Waiting(_) => (key, idx, LoadStatus::Loading),
+        })
+    }
 }
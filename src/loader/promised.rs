@@ -1,5 +1,5 @@
-use cbc::{bounded, Receiver, Sender};
-use std::{error::Error, fmt::{self, Display}};
+use cbc::{bounded, unbounded, Receiver, Sender};
+use std::{any::Any, error::Error, fmt::{self, Debug, Display}, sync::Arc};
 
 use super::Convert;
 
@@ -27,16 +27,82 @@ pub enum UpdateStatus {
     Waiting,
 }
 
+// Extra information that travels with a single load request (level of detail, language, ...),
+// loaders read it back with 'get' using the type it was created with
+#[derive(Clone, Default)]
+pub struct LoadOptions(Option<Arc<dyn Any + Send + Sync>>);
+
+impl LoadOptions {
+    pub fn new<O: Any + Send + Sync>(options: O) -> Self {
+        Self(Some(Arc::new(options)))
+    }
+
+    pub fn none() -> Self {
+        Self(None)
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.0.is_none()
+    }
+
+    pub fn get<O: Any>(&self) -> Option<&O> {
+        self.0.as_ref()?.downcast_ref()
+    }
+}
+
+impl Debug for LoadOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(_) => write!(f, "LoadOptions(..)"),
+            None => write!(f, "LoadOptions(None)"),
+        }
+    }
+}
+
+// What a streaming load sends down its chunk channel
+#[derive(Debug)]
+pub enum StreamMessage<T> {
+    Chunk(T),
+    End,
+}
+
 #[derive(Debug)]
 pub struct PromiseSender<T, M> {
     sender: Sender<T>,
+    stream: Option<Sender<StreamMessage<T>>>,
     pub meta_data: M,
+    pub options: LoadOptions,
 }
 
 impl<T, M> PromiseSender<T, M> {
+    // Fulfills the promise with the whole value, or fails a streaming load with an error
     pub fn send(&self, value: T) -> Result<(), cbc::TrySendError<T>> {
         self.sender.try_send(value)
     }
+
+    // Whether the value can be sent in pieces with send_chunk and finish
+    pub fn is_streaming(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    // Hands back the chunk if the load isn't streaming or nothing is waiting on it anymore
+    pub fn send_chunk(&self, chunk: T) -> Result<(), T> {
+        match &self.stream {
+            Some(stream) => stream.send(StreamMessage::Chunk(chunk)).map_err(|e| match e.0 {
+                StreamMessage::Chunk(chunk) => chunk,
+                StreamMessage::End => unreachable!(),
+            }),
+            None => Err(chunk),
+        }
+    }
+
+    // Marks the streamed value as complete, dropping the sender without finishing fails the load
+    pub fn finish(self) -> bool {
+        match &self.stream {
+            Some(stream) => stream.send(StreamMessage::End).is_ok(),
+            None => false,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -47,14 +113,33 @@ pub enum Promise<T, U> {
 
 impl<T, U> Promise<T, U> {
     pub fn new_waiting<M>(meta: M) -> (Self, PromiseSender<U, M>) {
+        Self::new_waiting_with(meta, LoadOptions::none())
+    }
+
+    pub fn new_waiting_with<M>(meta: M, options: LoadOptions) -> (Self, PromiseSender<U, M>) {
         let (sender, receiver) = bounded(1);
         let promise_sender = PromiseSender {
             sender,
+            stream: None,
             meta_data: meta,
+            options,
         };
         (Self::Waiting(receiver), promise_sender)
     }
 
+    // A promise whose value may also arrive as chunks on the returned receiver. The promise itself
+    // only receives a value if the loader sends it whole, or an error
+    pub fn new_streaming_with<M>(
+        meta: M,
+        options: LoadOptions,
+    ) -> (Self, PromiseSender<U, M>, Receiver<StreamMessage<U>>) {
+        let (promise, mut promise_sender) = Self::new_waiting_with(meta, options);
+        let (stream, chunks) = unbounded();
+        promise_sender.stream = Some(stream);
+
+        (promise, promise_sender, chunks)
+    }
+
     pub fn get(&self) -> Option<&T> {
         match self {
             Self::Owned(value) => Some(value),
@@ -96,23 +181,28 @@ where
     U: Convert<T>,
 {
     pub fn update(&mut self) -> Result<UpdateStatus, PromiseError<U::Error>> {
+        self.update_sized().map(|(status, _)| status)
+    }
+
+    // Also returns the size the loader reported for a value that just arrived
+    pub fn update_sized(&mut self) -> Result<(UpdateStatus, Option<usize>), PromiseError<U::Error>> {
         match self {
-            Self::Owned(_) => return Ok(UpdateStatus::AlreadyOwned),
+            Self::Owned(_) => return Ok((UpdateStatus::AlreadyOwned, None)),
             _ => (),
         }
 
-        let mut result = Ok(UpdateStatus::Waiting);
+        let mut result = Ok((UpdateStatus::Waiting, None));
 
         take_mut::take(self, |value| {
             let receiver = value.unwrap_waiting();
 
             match receiver.try_recv() {
-                Ok(value) => match value.convert() {
-                    Ok(owned) => {
-                        result = Ok(UpdateStatus::Updated);
+                Ok(value) => match (value.size(), value.convert()) {
+                    (size, Ok(owned)) => {
+                        result = Ok((UpdateStatus::Updated, size));
                         return Promise::Owned(owned);
                     }
-                    Err(e) => {
+                    (_, Err(e)) => {
                         result = Err(PromiseError::LoadError(e));
                         return Promise::Waiting(receiver);
                     }
@@ -130,19 +220,27 @@ where
 
     pub fn update_blocking(&mut self) -> Result<UpdateStatus, PromiseError<U::Error>>
     {
+        self.update_blocking_sized().map(|(status, _)| status)
+    }
+
+    pub fn update_blocking_sized(
+        &mut self,
+    ) -> Result<(UpdateStatus, Option<usize>), PromiseError<U::Error>> {
         let value = match self {
-            Self::Owned(_) => return Ok(UpdateStatus::AlreadyOwned),
+            Self::Owned(_) => return Ok((UpdateStatus::AlreadyOwned, None)),
             Self::Waiting(receiver) => receiver
                 .recv()
                 .or_else(|_| Err(PromiseError::Disconnected))?,
         };
 
+        let size = value.size();
+
         let owned = match value.convert() {
             Ok(success) => success,
             Err(e) => return Err(PromiseError::LoadError(e)),
         };
 
         *self = Self::Owned(owned);
-        Ok(UpdateStatus::Updated)
+        Ok((UpdateStatus::Updated, size))
     }
 }
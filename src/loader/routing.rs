@@ -0,0 +1,94 @@
+use super::{Loader, PromiseSender};
+use std::collections::HashMap;
+
+pub type BoxedLoader<I, M> = Box<dyn Loader<Key = String, Item = I, Meta = M>>;
+
+// Forwards each request to the loader registered for the key's scheme, "tex://grass" is sent to
+// the "tex" loader as "grass". Keys without a known scheme go to the fallback untouched
+pub struct RoutingLoader<I, M> {
+    routes: HashMap<String, BoxedLoader<I, M>>,
+    fallback: Option<BoxedLoader<I, M>>,
+}
+
+impl<I, M> RoutingLoader<I, M> {
+    pub fn new() -> Self {
+        Self {
+            routes: HashMap::new(),
+            fallback: None,
+        }
+    }
+
+    pub fn with_route<L>(mut self, scheme: impl Into<String>, loader: L) -> Self
+    where
+        L: Loader<Key = String, Item = I, Meta = M> + 'static,
+    {
+        self.add_route(scheme, loader);
+        self
+    }
+
+    pub fn with_fallback<L>(mut self, loader: L) -> Self
+    where
+        L: Loader<Key = String, Item = I, Meta = M> + 'static,
+    {
+        self.set_fallback(loader);
+        self
+    }
+
+    // Returns the loader previously registered for the scheme
+    pub fn add_route<L>(
+        &mut self,
+        scheme: impl Into<String>,
+        loader: L,
+    ) -> Option<BoxedLoader<I, M>>
+    where
+        L: Loader<Key = String, Item = I, Meta = M> + 'static,
+    {
+        self.routes.insert(scheme.into(), Box::new(loader))
+    }
+
+    pub fn remove_route(&mut self, scheme: &str) -> Option<BoxedLoader<I, M>> {
+        self.routes.remove(scheme)
+    }
+
+    pub fn set_fallback<L>(&mut self, loader: L)
+    where
+        L: Loader<Key = String, Item = I, Meta = M> + 'static,
+    {
+        self.fallback = Some(Box::new(loader));
+    }
+
+    pub fn has_route(&self, scheme: &str) -> bool {
+        self.routes.contains_key(scheme)
+    }
+
+    pub fn schemes(&self) -> impl Iterator<Item = &str> + '_ {
+        self.routes.keys().map(|scheme| &scheme[..])
+    }
+}
+
+impl<I, M> Default for RoutingLoader<I, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I, M> Loader for RoutingLoader<I, M> {
+    type Key = String;
+    type Item = I;
+    type Meta = M;
+
+    // Returns false if no loader accepts the key, the promise is then dropped and reports
+    // being disconnected
+    fn load(&self, key: String, into: PromiseSender<I, M>) -> bool {
+        if let Some((scheme, path)) = key.split_once("://") {
+            if let Some(loader) = self.routes.get(scheme) {
+                return loader.load(path.to_string(), into);
+            }
+        }
+
+        match &self.fallback {
+            Some(loader) => loader.load(key, into),
+            None => false,
+        }
+    }
+}
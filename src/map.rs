@@ -1,12 +1,65 @@
-use crate::{generation::GenerationStorage, idvec::IdVec, novec::NoVec, *};
+use crate::{
+    densevec::DenseVec,
+    generation::{GenerationStorage, PackedGenerationStorage, StorageId},
+    hasher::BuildIdentityHasher,
+    idvec::IdVec,
+    novec::NoVec,
+    novec32::NoVec32,
+    versioned::VersionedNoVec,
+    *,
+};
 use std::{
     borrow::Borrow,
-    collections::hash_map::{Entry as HashEntry, HashMap},
-    hash::Hash,
+    collections::{hash_map::RandomState, HashMap},
+    error::Error,
+    fmt::{self, Debug, Display, Formatter},
+    hash::{BuildHasher, Hash},
 };
 
 pub type MappedGeneration<K, T> = MappedStorage<IdVec<K>, GenerationStorage<T>>;
+pub type MappedPackedGeneration<K, T> = MappedStorage<IdVec<K>, PackedGenerationStorage<T>>;
 pub type MappedNoVec<K, T> = MappedStorage<IdVec<K>, NoVec<T>>;
+pub type MappedVersioned<K, T> = MappedStorage<IdVec<K>, VersionedNoVec<T>>;
+pub type MappedNoVec32<K, T> = MappedStorage<IdVec<K>, NoVec32<T>>;
+pub type MappedDenseVec<K, T> = MappedStorage<IdVec<K>, DenseVec<T>>;
+
+// For small integer keys, which are used as their own hash
+pub type IntMappedGeneration<K, T> =
+    MappedStorage<IdVec<K>, GenerationStorage<T>, BuildIdentityHasher>;
+pub type IntMappedNoVec<K, T> = MappedStorage<IdVec<K>, NoVec<T>, BuildIdentityHasher>;
+
+// What to do when a key that's already stored is inserted again
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    // Swap the value in place, any KeyIdx holding the index stays valid
+    #[default]
+    ReplaceKeepIndex,
+    // Remove the old value and store the new one under a fresh index
+    ReplaceNewIndex,
+    // Leave the old value alone, inserts hand the new one back and loading the key again is skipped
+    KeepExisting,
+    // Leave the old value alone, inserts hand the new one back and loading the key again fails
+    Error,
+}
+
+// The value that was turned away and the index of the entry already holding its key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateKey<I, T> {
+    pub index: I,
+    pub value: T,
+}
+
+impl<I: Debug, T> Display for DuplicateKey<I, T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Key is already stored at {:?}", self.index)
+    }
+}
+
+impl<I: Debug, T: Debug> Error for DuplicateKey<I, T> {}
+
+pub type InsertResult<I, T> = Result<(I, Option<T>), DuplicateKey<I, T>>;
+pub type ReplaceResult<I, T> = Result<Option<T>, DuplicateKey<I, T>>;
+pub type InsertGetResult<'a, I, T> = Result<(&'a I, &'a mut T, Option<T>), DuplicateKey<I, T>>;
 
 pub struct Occupied<'a, K: 'a, T: 'a, I: 'a> {
     key: &'a K,
@@ -15,6 +68,14 @@ pub struct Occupied<'a, K: 'a, T: 'a, I: 'a> {
 }
 
 impl<'a, K, T, I> Occupied<'a, K, T, I> {
+    pub fn key(&self) -> &K {
+        self.key
+    }
+
+    pub fn index(&self) -> &I {
+        self.index
+    }
+
     pub fn get(&self) -> &T {
         self.value
     }
@@ -24,28 +85,29 @@ impl<'a, K, T, I> Occupied<'a, K, T, I> {
     }
 }
 
-pub struct VacantEntry<'a, K: 'a, S: 'a>
+pub struct VacantEntry<'a, K: 'a, S: 'a, H: 'a = RandomState>
 where
     S: ExpandableStorage,
     K: UnorderedStorage,
     K::Item: Hash + Eq,
 {
     key: K::Item,
-    storage: &'a mut MappedStorage<K, S>,
+    storage: &'a mut MappedStorage<K, S, H>,
 }
 
-pub enum Entry<'a, K: 'a, S: 'a>
+pub enum Entry<'a, K: 'a, S: 'a, H: 'a = RandomState>
 where
     S: ExpandableStorage,
     K: UnorderedStorage,
     K::Item: Hash + Eq,
 {
     Occupied(Occupied<'a, K::Item, S::Item, S::Index>),
-    Vacant(VacantEntry<'a, K, S>),
+    Vacant(VacantEntry<'a, K, S, H>),
 }
 
-impl<'a, K: 'a, S: 'a> Entry<'a, K, S>
+impl<'a, K: 'a, S: 'a, H: 'a> Entry<'a, K, S, H>
 where
+    H: BuildHasher,
     S: ExpandableStorage,
     K: UnorderedStorage,
     K::Item: Hash + Eq + Clone,
@@ -63,7 +125,10 @@ where
         match self {
             Self::Occupied(occupied) => (occupied.index, occupied.value),
             Self::Vacant(VacantEntry { key, storage }) => {
-                let (index, value, _) = storage.insert_get(key, default);
+                let (index, value, _) = match storage.insert_get(key, default) {
+                    Ok(inserted) => inserted,
+                    Err(_) => unreachable!("A vacant entry has no value to clash with"),
+                };
 
                 (index, value)
             }
@@ -74,7 +139,10 @@ where
         self,
         default: F,
     ) -> (&'a S::Index, &'a mut S::Item) {
-        self.or_insert(default())
+        match self {
+            Self::Occupied(occupied) => (occupied.index, occupied.value),
+            vacant => vacant.or_insert(default()),
+        }
     }
 
     pub fn and_modify<F: FnOnce(&mut S::Item)>(mut self, f: F) -> Self {
@@ -94,16 +162,96 @@ where
     }
 }
 
+// Like VacantEntry but only holds a borrowed key, which is turned into an owned one on insert
+pub struct VacantEntryRef<'a, 'q, K: 'a, S: 'a, Q: ?Sized, H: 'a = RandomState>
+where
+    S: ExpandableStorage,
+    K: UnorderedStorage,
+    K::Item: Hash + Eq,
+{
+    key: &'q Q,
+    storage: &'a mut MappedStorage<K, S, H>,
+}
+
+pub enum EntryRef<'a, 'q, K: 'a, S: 'a, Q: ?Sized, H: 'a = RandomState>
+where
+    S: ExpandableStorage,
+    K: UnorderedStorage,
+    K::Item: Hash + Eq,
+{
+    Occupied(Occupied<'a, K::Item, S::Item, S::Index>),
+    Vacant(VacantEntryRef<'a, 'q, K, S, Q, H>),
+}
+
+impl<'a, 'q, K: 'a, S: 'a, Q: ?Sized, H: 'a> EntryRef<'a, 'q, K, S, Q, H>
+where
+    H: BuildHasher,
+    S: ExpandableStorage,
+    K: UnorderedStorage,
+    K::Item: Hash + Eq + Clone + Borrow<Q> + From<&'q Q>,
+    Q: Hash + Eq,
+    S::Index: Into<K::Index> + Copy,
+    K::Index: Copy,
+{
+    pub fn key(&self) -> &Q {
+        match self {
+            EntryRef::Occupied(occupied) => occupied.key.borrow(),
+            EntryRef::Vacant(vacant) => vacant.key,
+        }
+    }
+
+    pub fn or_insert(self, default: S::Item) -> (&'a S::Index, &'a mut S::Item) {
+        match self {
+            Self::Occupied(occupied) => (occupied.index, occupied.value),
+            Self::Vacant(VacantEntryRef { key, storage }) => {
+                let (index, value, _) = match storage.insert_get(key.into(), default) {
+                    Ok(inserted) => inserted,
+                    Err(_) => unreachable!("A vacant entry has no value to clash with"),
+                };
+
+                (index, value)
+            }
+        }
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> S::Item>(
+        self,
+        default: F,
+    ) -> (&'a S::Index, &'a mut S::Item) {
+        match self {
+            Self::Occupied(occupied) => (occupied.index, occupied.value),
+            vacant => vacant.or_insert(default()),
+        }
+    }
+
+    pub fn and_modify<F: FnOnce(&mut S::Item)>(mut self, f: F) -> Self {
+        if let EntryRef::Occupied(Occupied { value, .. }) = &mut self {
+            f(value);
+        }
+
+        self
+    }
+
+    pub fn or_default(self) -> (&'a S::Index, &'a mut S::Item)
+    where
+        S::Item: Default,
+    {
+        self.or_insert_with(Default::default)
+    }
+}
+
+// H is the hasher used for the key map
 #[derive(Clone, Debug)]
-pub struct MappedStorage<K, S>
+pub struct MappedStorage<K, S, H = RandomState>
 where
     S: ExpandableStorage,
     K: UnorderedStorage,
     K::Item: Hash + Eq,
 {
-    indices: HashMap<K::Item, S::Index>,
+    indices: HashMap<K::Item, S::Index, H>,
     keys: K,
     storage: S,
+    policy: DuplicatePolicy,
 }
 
 impl<K, S> MappedStorage<K, S>
@@ -113,22 +261,85 @@ where
     K::Item: Hash + Eq,
 {
     pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, S, H> MappedStorage<K, S, H>
+where
+    S: ExpandableStorage + Default,
+    K: UnorderedStorage + Default,
+    K::Item: Hash + Eq,
+    H: BuildHasher,
+{
+    pub fn with_hasher(hasher: H) -> Self {
         MappedStorage {
-            indices: HashMap::new(),
+            indices: HashMap::with_hasher(hasher),
             keys: K::default(),
             storage: S::default(),
+            policy: DuplicatePolicy::default(),
         }
     }
 }
 
 impl<K, S> MappedStorage<K, S>
 where
+    S: ExpandableStorage + WithCapacity,
+    K: UnorderedStorage + WithCapacity,
+    K::Item: Hash + Eq,
+{
+    pub fn with_capacity(capacity: usize) -> Self {
+        MappedStorage {
+            indices: HashMap::with_capacity(capacity),
+            keys: K::with_capacity(capacity),
+            storage: S::with_capacity(capacity),
+            policy: DuplicatePolicy::default(),
+        }
+    }
+}
+
+impl<K, S, H> Default for MappedStorage<K, S, H>
+where
+    S: ExpandableStorage + Default,
+    K: UnorderedStorage + Default,
+    K::Item: Hash + Eq,
+    H: BuildHasher + Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(H::default())
+    }
+}
+
+impl<K, S, H> MappedStorage<K, S, H>
+where
+    H: BuildHasher,
     S: ExpandableStorage,
     K: UnorderedStorage,
     K::Item: Hash + Eq,
     K::Index: Copy,
     S::Index: Into<K::Index> + Copy,
 {
+    pub fn with_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn set_policy(&mut self, policy: DuplicatePolicy) {
+        self.policy = policy;
+    }
+
+    pub fn policy(&self) -> DuplicatePolicy {
+        self.policy
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
     pub fn contains(&self, ki: &KeyIdx<K::Item, S::Index>) -> bool {
         if let Some(value) = ki.index_ref() {
             return self.storage.get(value).is_some();
@@ -190,6 +401,37 @@ where
         }
     }
 
+    // Returns the stored key along with the value
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K::Item, &S::Item)>
+    where
+        K::Item: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_full(key).map(|(key, _, value)| (key, value))
+    }
+
+    pub fn get_full<Q>(&self, key: &Q) -> Option<(&K::Item, &S::Index, &S::Item)>
+    where
+        K::Item: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (key, index) = self.indices.get_key_value(key)?;
+        let value = self.storage.get(index)?;
+
+        Some((key, index, value))
+    }
+
+    pub fn get_full_mut<Q>(&mut self, key: &Q) -> Option<(&K::Item, &S::Index, &mut S::Item)>
+    where
+        K::Item: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (key, index) = self.indices.get_key_value(key)?;
+        let value = self.storage.get_mut(index)?;
+
+        Some((key, index, value))
+    }
+
     pub fn get_index<Q>(&self, key: &Q) -> Option<&S::Index>
     where
         K::Item: Borrow<Q>,
@@ -202,6 +444,54 @@ where
         self.keys.get(&index.clone().into())
     }
 
+    pub fn entry(&mut self, key: K::Item) -> Entry<'_, K, S, H> {
+        let occupied = match self.indices.get(&key) {
+            Some(index) => self.storage.get(index).is_some(),
+            None => false,
+        };
+
+        if !occupied {
+            return Entry::Vacant(VacantEntry { key, storage: self });
+        }
+
+        let (key, index) = self.indices.get_key_value(&key).unwrap();
+        let value = self.storage.get_mut(index).unwrap();
+
+        Entry::Occupied(Occupied { key, index, value })
+    }
+
+    // The key is only converted into an owned K::Item if the entry is vacant and gets filled
+    pub fn entry_ref<'a, 'q, Q>(&'a mut self, key: &'q Q) -> EntryRef<'a, 'q, K, S, Q, H>
+    where
+        K::Item: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let occupied = match self.indices.get(key) {
+            Some(index) => self.storage.get(index).is_some(),
+            None => false,
+        };
+
+        if !occupied {
+            return EntryRef::Vacant(VacantEntryRef { key, storage: self });
+        }
+
+        let (key, index) = self.indices.get_key_value(key).unwrap();
+        let value = self.storage.get_mut(index).unwrap();
+
+        EntryRef::Occupied(Occupied { key, index, value })
+    }
+
+    // Returns the index ki refers to, looking it up by key if ki has no valid index
+    pub fn index_of(&self, ki: &KeyIdx<K::Item, S::Index>) -> Option<S::Index> {
+        if let Some(idx) = &ki.index {
+            if self.get_by_index(idx).is_some() {
+                return Some(*idx);
+            }
+        }
+
+        self.get_index(&ki.key).copied()
+    }
+
     // Returns true if either ki received an index or it already had an index
     pub fn set_idx(&self, ki: &mut KeyIdx<K::Item, S::Index>) -> bool {
         if let Some(idx) = &ki.index {
@@ -238,61 +528,145 @@ where
         self.get_by_index_mut(ki.index_ref().unwrap())
     }
 
+    // ki is pointed at the key's entry whether or not the value was stored
     pub fn insert_replace_idx(
         &mut self,
         ki: &mut KeyIdx<K::Item, S::Index>,
         value: S::Item,
-    ) -> Option<S::Item>
+    ) -> ReplaceResult<S::Index, S::Item>
     where
         K::Item: Clone,
     {
-        let (index, removed) = self.insert(ki.key.clone(), value);
-        ki.index = Some(index);
+        match self.insert(ki.key.clone(), value) {
+            Ok((index, removed)) => {
+                ki.index = Some(index);
+                Ok(removed)
+            }
+            Err(duplicate) => {
+                ki.index = Some(duplicate.index);
+                Err(duplicate)
+            }
+        }
+    }
 
-        removed
+    // An existing key is handled by the storage's duplicate policy. Ok holds the value that was
+    // replaced, if any. Err hands the value back when the policy keeps the existing entry
+    pub fn insert(&mut self, key: K::Item, value: S::Item) -> InsertResult<S::Index, S::Item>
+    where
+        K::Item: Clone,
+    {
+        self.insert_with_policy(key, value, self.policy)
+    }
+
+    pub fn insert_with_policy(
+        &mut self,
+        key: K::Item,
+        value: S::Item,
+        policy: DuplicatePolicy,
+    ) -> InsertResult<S::Index, S::Item>
+    where
+        K::Item: Clone,
+    {
+        let existing = self
+            .indices
+            .get(&key)
+            .copied()
+            .filter(|index| self.storage.get(index).is_some());
+
+        let index = match existing {
+            Some(index) => index,
+            None => return Ok((self.push_new(key, value), None)),
+        };
+
+        match policy {
+            DuplicatePolicy::ReplaceKeepIndex => {
+                let current = self.storage.get_mut(&index).unwrap();
+                Ok((index, Some(std::mem::replace(current, value))))
+            }
+            DuplicatePolicy::ReplaceNewIndex => {
+                let removed = self.remove_with_index(&index);
+                Ok((self.push_new(key, value), removed))
+            }
+            DuplicatePolicy::KeepExisting | DuplicatePolicy::Error => {
+                Err(DuplicateKey { index, value })
+            }
+        }
     }
 
-    pub fn insert(&mut self, key: K::Item, value: S::Item) -> (S::Index, Option<S::Item>)
+    fn push_new(&mut self, key: K::Item, value: S::Item) -> S::Index
     where
         K::Item: Clone,
     {
         let index = self.storage.push(value);
         self.keys.insert(index.into(), key.clone());
+        self.indices.insert(key, index);
 
-        match self.indices.entry(key) {
-            HashEntry::Occupied(mut occupied) => {
-                let previous = occupied.insert(index);
-                let removed = self.storage.remove(&previous);
-                (*occupied.into_mut(), removed)
-            }
-            HashEntry::Vacant(vacant) => (*vacant.insert(index), None),
-        }
+        index
     }
 
+    // Same as insert but also borrows the stored value, Err hands the value back just like insert
     pub fn insert_get(
         &mut self,
         key: K::Item,
         value: S::Item,
-    ) -> (&S::Index, &mut S::Item, Option<S::Item>)
+    ) -> InsertGetResult<'_, S::Index, S::Item>
     where
         K::Item: Clone,
     {
-        let index = self.storage.push(value);
-        self.keys.insert(index.into(), key.clone());
+        let (_, removed) = self.insert(key.clone(), value)?;
 
-        match self.indices.entry(key) {
-            HashEntry::Occupied(mut occupied) => {
-                let previous = occupied.insert(index);
-                let removed = self.storage.remove(&previous);
-                let value = self.storage.get_mut(occupied.get()).unwrap();
-                (occupied.into_mut(), value, removed)
-            }
-            HashEntry::Vacant(vacant) => {
-                let index = vacant.insert(index);
-                let value = self.storage.get_mut(index).unwrap();
-                (index, value, None)
+        let index = self.indices.get(&key).unwrap();
+        let value = self.storage.get_mut(index).unwrap();
+
+        Ok((index, value, removed))
+    }
+
+    // Exchanges the values stored at a and b and updates their keys to match, so a KeyIdx holding
+    // either index will now find the other value. Returns false if either index is empty
+    pub fn swap_indices(&mut self, a: &S::Index, b: &S::Index) -> bool {
+        if !self.storage.swap_values(a, b) {
+            return false;
+        }
+
+        let a_key = self.keys.remove(&(*a).into()).unwrap();
+        let b_key = match self.keys.remove(&(*b).into()) {
+            Some(b_key) => b_key,
+            // a and b are the same index
+            None => {
+                self.keys.insert((*a).into(), a_key);
+                return true;
             }
+        };
+
+        *self.indices.get_mut(&a_key).unwrap() = *b;
+        *self.indices.get_mut(&b_key).unwrap() = *a;
+        self.keys.insert((*b).into(), a_key);
+        self.keys.insert((*a).into(), b_key);
+
+        true
+    }
+
+    // Moves the value at from into the empty slot at to, its key follows it. Returns false and
+    // leaves everything in place if from is empty, to is taken or the storage rejects the index
+    pub fn relocate(&mut self, from: &S::Index, to: &S::Index) -> bool {
+        // Checked up front so nothing has to be put back, a removed value can't always go back
+        // where it was. The keys are checked rather than the storage as to may be a newer
+        // generation of a slot another key still holds
+        if self.storage.get(from).is_none()
+            || self.keys.get(&(*to).into()).is_some()
+            || !self.storage.accepts(to)
+        {
+            return false;
         }
+
+        let value = self.storage.remove(from).unwrap();
+        self.storage.insert(*to, value);
+
+        let key = self.keys.remove(&(*from).into()).unwrap();
+        *self.indices.get_mut(&key).unwrap() = *to;
+        self.keys.insert((*to).into(), key);
+
+        true
     }
 
     pub fn remove_with_index(&mut self, index: &S::Index) -> Option<S::Item> {
@@ -393,3 +767,126 @@ where
         })
     }
 }
+
+// Every key has to lead to a value and back to itself, the backing storage is checked too
+#[cfg(feature = "testing")]
+impl<K, S, H> crate::testing::CheckInvariants for MappedStorage<K, S, H>
+where
+    H: BuildHasher,
+    S: ExpandableStorage + crate::testing::CheckInvariants,
+    K: UnorderedStorage,
+    K::Item: Hash + Eq + Debug,
+    K::Index: Copy,
+    S::Index: Into<K::Index> + Copy + Debug,
+{
+    fn check_invariants(&self) -> Result<(), String> {
+        for (key, index) in &self.indices {
+            if self.storage.get(index).is_none() {
+                return Err(format!("{:?} maps to {:?} which holds no value", key, index));
+            }
+
+            if self.keys.get(&(*index).into()) != Some(key) {
+                return Err(format!("{:?} maps to {:?} which is stored under another key", key, index));
+            }
+        }
+
+        self.storage.check_invariants()
+    }
+}
+
+impl<K, S, H> MappedStorage<IdVec<K>, S, H>
+where
+    S: ExpandableStorage,
+    K: Hash + Eq,
+{
+    // Releases the key slots left empty at the end by removals, see IdVec::shrink_to_fit
+    pub fn shrink_keys(&mut self) {
+        self.keys.shrink_to_fit();
+    }
+}
+
+impl<K, T, H> MappedStorage<K, GenerationStorage<T>, H>
+where
+    K: UnorderedStorage,
+    K::Item: Hash + Eq,
+    H: BuildHasher,
+    StorageId: Into<K::Index>,
+{
+    // Compacts the storage with every key following its value, see GenerationStorage::compact
+    pub fn compact<F: FnMut(StorageId, StorageId)>(&mut self, mut on_move: F) {
+        let keys = &mut self.keys;
+        let indices = &mut self.indices;
+
+        self.storage.compact(|from, to| {
+            if let Some(key) = keys.remove(&from.into()) {
+                *indices.get_mut(&key).unwrap() = to;
+                keys.insert(to.into(), key);
+            }
+
+            on_move(from, to);
+        });
+    }
+}
+
+// Walks the backing storage's data directly rather than the key map, so items come in storage
+// order. Entries without a key are skipped
+#[cfg(feature = "rayon")]
+impl<K, S, H> MappedStorage<K, S, H>
+where
+    H: BuildHasher,
+    S: ExpandableStorage + crate::par::ParStorage,
+    K: UnorderedStorage + Sync,
+    K::Item: Hash + Eq + Sync,
+    K::Index: Copy,
+    S::Index: Into<K::Index> + Copy + Send,
+{
+    pub fn par_iter(
+        &self,
+    ) -> impl rayon::iter::ParallelIterator<Item = (&K::Item, S::Index, &S::Item)> + '_
+    where
+        S::Item: Sync,
+    {
+        use rayon::prelude::*;
+
+        let keys = &self.keys;
+
+        self.storage
+            .par_iter()
+            .filter_map(move |(idx, value)| keys.get(&idx.into()).map(|key| (key, idx, value)))
+    }
+
+    pub fn par_iter_mut(
+        &mut self,
+    ) -> impl rayon::iter::ParallelIterator<Item = (&K::Item, S::Index, &mut S::Item)> + '_
+    where
+        S::Item: Send,
+    {
+        use rayon::prelude::*;
+
+        let keys = &self.keys;
+
+        self.storage
+            .par_iter_mut()
+            .filter_map(move |(idx, value)| keys.get(&idx.into()).map(|key| (key, idx, value)))
+    }
+
+    pub fn par_values(&self) -> impl rayon::iter::ParallelIterator<Item = &S::Item> + '_
+    where
+        S::Item: Sync,
+    {
+        use rayon::prelude::*;
+
+        self.par_iter().map(|(_, _, value)| value)
+    }
+
+    pub fn par_values_mut(
+        &mut self,
+    ) -> impl rayon::iter::ParallelIterator<Item = &mut S::Item> + '_
+    where
+        S::Item: Send,
+    {
+        use rayon::prelude::*;
+
+        self.par_iter_mut().map(|(_, _, value)| value)
+    }
+}
@@ -1,17 +1,21 @@
 use take_mut::take;
 use super::*;
+use std::{iter::{Enumerate, FromIterator}, slice, vec};
 
-// None points to the next closest empty entry;
+// Empty entries form a doubly linked free list so any of them can be unlinked in constant time,
+// 'NONE' marks either end of the list
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Entry<T> {
     Data(T),
-    Next(usize)
+    Next { prev: usize, next: usize }
 }
 
+pub const NONE: usize = usize::MAX;
+
 impl<T> Entry<T> {
     pub fn is_next(&self) -> bool {
         match self {
-            Entry::Next(_) => true,
+            Entry::Next { .. } => true,
             _ => false
         }
     }
@@ -25,7 +29,14 @@ impl<T> Entry<T> {
 
     pub fn next_ref_mut(&mut self) -> &mut usize {
         match self {
-            Entry::Next(ref mut next) => next,
+            Entry::Next { ref mut next, .. } => next,
+            _ => panic!("Tried to unwrap data")
+        }
+    }
+
+    pub fn prev_ref_mut(&mut self) -> &mut usize {
+        match self {
+            Entry::Next { ref mut prev, .. } => prev,
             _ => panic!("Tried to unwrap data")
         }
     }
@@ -46,7 +57,14 @@ impl<T> Entry<T> {
 
     pub fn unwrap_next(&self) -> usize {
         match self {
-            Entry::Next(next) => *next,
+            Entry::Next { next, .. } => *next,
+            _ => panic!("Tried to unwrap data")
+        }
+    }
+
+    pub fn unwrap_prev(&self) -> usize {
+        match self {
+            Entry::Next { prev, .. } => *prev,
             _ => panic!("Tried to unwrap data")
         }
     }
@@ -58,15 +76,15 @@ impl<T> Entry<T> {
         }
     }
 
-    pub fn swap_next(&mut self, next: usize) -> Option<T> {
+    pub fn swap_next(&mut self, prev: usize, next: usize) -> Option<T> {
         let mut value = None;
         take(self, |x| {
             match x {
                 Entry::Data(data) => {
                     value = Some(data);
-                    Entry::Next(next)
+                    Entry::Next { prev, next }
                 }
-                _ => Entry::Next(next)
+                _ => Entry::Next { prev, next }
             }
         });
 
@@ -92,42 +110,56 @@ impl<T> Entry<T> {
         *self = Entry::Data(data)
     }
 
-    pub fn set_next(&mut self, next: usize) {
-        *self = Entry::Next(next)
+    pub fn set_next(&mut self, prev: usize, next: usize) {
+        *self = Entry::Next { prev, next }
     }
 
     pub fn option(self) -> Option<T> {
         match self {
             Entry::Data(data) => Some(data),
-            Entry::Next(_) => None,
+            Entry::Next { .. } => None,
         }
     }
 
     pub fn option_ref(&self) -> Option<&T> {
         match self {
             Entry::Data(ref data) => Some(data),
-            Entry::Next(_) => None,
+            Entry::Next { .. } => None,
         }
     }
 
     pub fn option_ref_mut(&mut self) -> Option<&mut T> {
         match self {
             Entry::Data(ref mut data) => Some(data),
-            Entry::Next(_) => None,
+            Entry::Next { .. } => None,
         }
     }
 }
 
+// What 'insert_at_outcome' did with the entry at the index
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InsertOutcome<T> {
+    // The entry held a value which was swapped out
+    Replaced(T),
+    // The entry was empty and has been taken out of the free list
+    Filled,
+    // The index was past the end so the storage grew, the skipped entries are left empty
+    Extended,
+}
+
 #[derive(Clone, Debug)]
 pub struct NoVec<T> {
     next: usize,
+    // The number of entries holding data
+    len: usize,
     entries: Vec<Entry<T>>,
 }
 
 impl<T> Default for NoVec<T> {
     fn default() -> Self {
         Self {
-            next: 0,
+            next: NONE,
+            len: 0,
             entries: vec![]
         }
     }
@@ -138,20 +170,56 @@ impl<T> NoVec<T> {
         let entries = Vec::with_capacity(cap);
 
         NoVec {
-            next: 0,
+            next: NONE,
+            len: 0,
             entries
         }
     }
 
     pub fn new() -> NoVec<T> {
         NoVec {
-            next: 0,
+            next: NONE,
+            len: 0,
             entries: vec![]
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // The number of entries that fit before the storage reallocates, empty entries included
+    pub fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
+    // Room for at least this many more entries, empty ones are already in the free list so every
+    // reserved entry can go to a push without reallocating
+    pub fn reserve(&mut self, additional: usize) {
+        self.entries.reserve(additional);
+    }
+
+    // Drops the empty entries at the end before shrinking the allocation, indices of values that
+    // are still held don't change
+    pub fn shrink_to_fit(&mut self) {
+        while let Some(Entry::Next { .. }) = self.entries.last() {
+            self.unlink(self.entries.len() - 1);
+            self.entries.pop();
+        }
+
+        self.entries.shrink_to_fit();
+    }
+
+    // The index the next push will use, the most recently emptied entry is reused first
     pub fn next_id(&self) -> usize {
-        self.next
+        match self.next {
+            NONE => self.entries.len(),
+            next => next
+        }
     }
 
     pub fn get(&self, index: usize) -> Option<&T> {
@@ -170,65 +238,179 @@ impl<T> NoVec<T> {
         self.entries[index].option_ref_mut()
     }
 
-    pub fn insert_at(&mut self, index: usize, value: T) -> Option<T> {
-        if index == self.next {
-            self.push(value);
+    // Mutable references to several values at once, None if any index is repeated or empty
+    pub fn get_many_mut<const N: usize>(&mut self, indices: [usize; N]) -> Option<[&mut T; N]> {
+        let entries = self.entries.get_disjoint_mut(indices).ok()?;
+
+        if !entries.iter().all(|x| x.is_data()) {
             return None;
         }
 
-        if index >= self.entries.len() {
+        Some(entries.map(|x| x.data_ref_mut()))
+    }
+
+    /// # Safety
+    /// The index must hold data, bounds and the entry kind are only checked in debug builds
+    pub unsafe fn get_unchecked(&self, index: usize) -> &T {
+        debug_assert!(self.get(index).is_some(), "No data at index {}", index);
+
+        match self.entries.get_unchecked(index) {
+            Entry::Data(data) => data,
+            Entry::Next { .. } => std::hint::unreachable_unchecked()
+        }
+    }
+
+    /// # Safety
+    /// Same as get_unchecked
+    pub unsafe fn get_unchecked_mut(&mut self, index: usize) -> &mut T {
+        debug_assert!(self.get(index).is_some(), "No data at index {}", index);
+
+        match self.entries.get_unchecked_mut(index) {
+            Entry::Data(data) => data,
+            Entry::Next { .. } => std::hint::unreachable_unchecked()
+        }
+    }
+
+    // Looks at the entry at the index before deciding what to put there, a vacant entry may be
+    // in the free list or past the end
+    pub fn entry(&mut self, index: usize) -> IndexEntry<'_, T> {
+        match self.get(index) {
+            Some(_) => IndexEntry::Occupied(OccupiedEntry { novec: self, index }),
+            None => IndexEntry::Vacant(VacantEntry { novec: self, index }),
+        }
+    }
+
+    pub fn insert_at(&mut self, index: usize, value: T) -> Option<T> {
+        match self.insert_at_outcome(index, value) {
+            InsertOutcome::Replaced(replaced) => Some(replaced),
+            _ => None
+        }
+    }
+
+    // Places the value at the index whether or not it's in use, any empty entry is spliced out of
+    // the free list so later pushes never hand it out again
+    pub fn insert_at_outcome(&mut self, index: usize, value: T) -> InsertOutcome<T> {
+        let extended = index >= self.entries.len();
+
+        if extended {
             self.fill_to(index + 1);
         }
 
         if self.entries[index].is_data() {
             let replaced = self.entries[index].swap_data(value).unwrap();
             
-            return Some(replaced);
+            return InsertOutcome::Replaced(replaced);
         }
 
-        let next = self.entries[index].unwrap_next(); 
+        self.unlink(index);
+        self.entries[index].insert_data(value);
+        self.len += 1;
 
-        for i in (0..index).rev() {
-            if self.entries[i].is_next() {
-                self.entries[i].swap_next(next);
-                break;
-            }
+        if extended {
+            InsertOutcome::Extended
+        }
+        else {
+            InsertOutcome::Filled
         }
-        
-
-        None
     }
 
     pub fn push(&mut self, value: T) -> usize {
-        let output = self.next;
-        if self.next >= self.entries.len() {
+        self.len += 1;
+
+        if self.next == NONE {
             self.entries.push(Entry::Data(value));
-            self.next += 1;
-        }
-        else {
-            let entry = &mut self.entries[self.next];
-            let next = entry.unwrap_next();
-            entry.insert_data(value);
-            self.next = next;
+
+            return self.entries.len() - 1;
         }
 
+        let output = self.next;
+        self.unlink(output);
+        self.entries[output].insert_data(value);
+
         output
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
-        self.entries.iter().enumerate().filter(|(_, x)| x.is_data()).map(|(index, x)| (index, x.data_ref()))
+    // Takes an empty entry out of the free list
+    fn unlink(&mut self, index: usize) {
+        let prev = self.entries[index].unwrap_prev();
+        let next = self.entries[index].unwrap_next();
+
+        match prev {
+            NONE => self.next = next,
+            prev => *self.entries[prev].next_ref_mut() = next
+        }
+
+        if next != NONE {
+            *self.entries[next].prev_ref_mut() = prev;
+        }
+    }
+
+    // Empties an entry and puts it at the front of the free list
+    fn link_front(&mut self, index: usize) -> Option<T> {
+        let value = self.entries[index].swap_next(NONE, self.next);
+
+        if self.next != NONE {
+            *self.entries[self.next].prev_ref_mut() = index;
+        }
+
+        self.next = index;
+
+        value
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { entries: self.entries.iter().enumerate(), remaining: self.len }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut { entries: self.entries.iter_mut().enumerate(), remaining: self.len }
+    }
+
+    pub fn values(&self) -> impl ExactSizeIterator<Item = &T> {
+        self.iter().map(|(_, x)| x)
     }
 
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> {
-        self.entries.iter_mut().enumerate().filter(|(_, x)| x.is_data()).map(|(index, x)| (index, x.data_ref_mut()))
+    pub fn values_mut(&mut self) -> impl ExactSizeIterator<Item = &mut T> {
+        self.iter_mut().map(|(_, x)| x)
     }
 
-    pub fn values(&self) -> impl Iterator<Item = &T> {
-        self.entries.iter().filter(|x| x.is_data()).map(|x| x.data_ref())
+    // Moves every value out along with its index, the storage is left empty but keeps its
+    // allocation. Values that aren't iterated over are dropped
+    pub fn drain(&mut self) -> impl Iterator<Item = (usize, T)> + '_ {
+        self.next = NONE;
+        self.len = 0;
+        self.entries.drain(..).enumerate().filter_map(|(index, x)| x.option().map(|x| (index, x)))
     }
 
-    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
-        self.entries.iter_mut().filter(|x| x.is_data()).map(|x| x.data_ref_mut())
+    // Drops every value and forgets the free list, the allocation is kept for reuse
+    pub fn clear(&mut self) {
+        self.next = NONE;
+        self.len = 0;
+        self.entries.clear();
+    }
+
+    // Drops every entry at or past len, empty ones are taken out of the free list
+    pub fn truncate(&mut self, len: usize) {
+        while self.entries.len() > len {
+            let index = self.entries.len() - 1;
+
+            match self.entries[index] {
+                Entry::Next { .. } => self.unlink(index),
+                Entry::Data(_) => self.len -= 1,
+            }
+
+            self.entries.pop();
+        }
+    }
+
+    // Removes the value with the highest index, the entries after it are dropped too so the
+    // storage ends up as it was before that value was pushed onto the end
+    pub fn pop_last(&mut self) -> Option<(usize, T)> {
+        let index = self.entries.iter().rposition(|x| x.is_data())?;
+        let value = self.remove(index)?;
+        self.truncate(index);
+
+        Some((index, value))
     }
 
     pub fn fill_to(&mut self, size: usize) {
@@ -237,8 +419,13 @@ impl<T> NoVec<T> {
             return;
         }
 
-        for i in len..size {
-            self.entries.push(Entry::Next(i + 1));
+        for _ in len..size {
+            self.entries.push(Entry::Next { prev: NONE, next: NONE });
+        }
+
+        // Linked backwards so the lowest new entry is used first
+        for i in (len..size).rev() {
+            self.link_front(i);
         }
     }
     
@@ -251,25 +438,308 @@ impl<T> NoVec<T> {
             return None;
         }
 
-        if index < self.next {
-            let value = self.entries[index].swap_next(self.next);
-            self.next = index;
+        self.len -= 1;
+        self.link_front(index)
+    }
+}
+
+pub struct OccupiedEntry<'a, T: 'a> {
+    novec: &'a mut NoVec<T>,
+    index: usize,
+}
+
+impl<'a, T: 'a> OccupiedEntry<'a, T> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get(&self) -> &T {
+        self.novec.entries[self.index].data_ref()
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.novec.entries[self.index].data_ref_mut()
+    }
+
+    pub fn into_mut(self) -> &'a mut T {
+        self.novec.entries[self.index].data_ref_mut()
+    }
+
+    pub fn insert(&mut self, value: T) -> T {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    // Empties the entry, its index goes back in the free list
+    pub fn remove(self) -> T {
+        self.novec.remove(self.index).unwrap()
+    }
+}
+
+pub struct VacantEntry<'a, T: 'a> {
+    novec: &'a mut NoVec<T>,
+    index: usize,
+}
+
+impl<'a, T: 'a> VacantEntry<'a, T> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn insert(self, value: T) -> &'a mut T {
+        self.novec.insert_at(self.index, value);
+        self.novec.entries[self.index].data_ref_mut()
+    }
+}
+
+pub enum IndexEntry<'a, T: 'a> {
+    Occupied(OccupiedEntry<'a, T>),
+    Vacant(VacantEntry<'a, T>),
+}
+
+impl<'a, T: 'a> IndexEntry<'a, T> {
+    pub fn index(&self) -> usize {
+        match self {
+            IndexEntry::Occupied(occupied) => occupied.index,
+            IndexEntry::Vacant(vacant) => vacant.index,
+        }
+    }
+
+    pub fn or_insert(self, default: T) -> &'a mut T {
+        self.or_insert_with(|| default)
+    }
 
-            return value;
+    pub fn or_insert_with<F: FnOnce() -> T>(self, default: F) -> &'a mut T {
+        match self {
+            IndexEntry::Occupied(occupied) => occupied.into_mut(),
+            IndexEntry::Vacant(vacant) => vacant.insert(default()),
         }
+    }
 
-        let mut next = self.next;
-        let mut prev_val = next;
+    pub fn or_default(self) -> &'a mut T
+    where
+        T: Default,
+    {
+        self.or_insert_with(Default::default)
+    }
 
-        while next <= index {
-            prev_val = next;
-            next = self.entries[next].unwrap_next();
+    pub fn and_modify<F: FnOnce(&mut T)>(mut self, f: F) -> Self {
+        if let IndexEntry::Occupied(occupied) = &mut self {
+            f(occupied.get_mut());
         }
 
-        let value = self.entries[index].swap_next(next);
-        self.entries[prev_val].set_next(index);
+        self
+    }
+}
 
-        value
+// Yields the values along with their indices, skipping empty entries. The number of values left
+// is tracked so the length is known up front
+pub struct Iter<'a, T> {
+    entries: Enumerate<slice::Iter<'a, Entry<T>>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<(usize, &'a T)> {
+        let next = self.entries.find_map(|(index, x)| x.option_ref().map(|x| (index, x)))?;
+        self.remaining -= 1;
+
+        Some(next)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+pub struct IterMut<'a, T> {
+    entries: Enumerate<slice::IterMut<'a, Entry<T>>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = (usize, &'a mut T);
+
+    fn next(&mut self) -> Option<(usize, &'a mut T)> {
+        let next = self.entries.find_map(|(index, x)| x.option_ref_mut().map(|x| (index, x)))?;
+        self.remaining -= 1;
+
+        Some(next)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for IterMut<'_, T> {}
+
+pub struct IntoIter<T> {
+    entries: Enumerate<vec::IntoIter<Entry<T>>>,
+    remaining: usize,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = (usize, T);
+
+    fn next(&mut self) -> Option<(usize, T)> {
+        let next = self.entries.find_map(|(index, x)| x.option().map(|x| (index, x)))?;
+        self.remaining -= 1;
+
+        Some(next)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for NoVec<T> {
+    type Item = (usize, T);
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { entries: self.entries.into_iter().enumerate(), remaining: self.len }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a NoVec<T> {
+    type Item = (usize, &'a T);
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut NoVec<T> {
+    type Item = (usize, &'a mut T);
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+// Pushes every value, so empty entries are filled before the storage grows
+impl<T> Extend<T> for NoVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for NoVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut novec = NoVec::new();
+        novec.extend(iter);
+        novec
+    }
+}
+
+// Walks the free list checking it links every empty entry exactly once
+#[cfg(feature = "testing")]
+impl<T> crate::testing::CheckInvariants for NoVec<T> {
+    fn check_invariants(&self) -> Result<(), String> {
+        let free = self.entries.iter().filter(|entry| entry.is_next()).count();
+
+        if self.len != self.entries.len() - free {
+            return Err(format!("Counted {} entries but {} hold data", self.len, self.entries.len() - free));
+        }
+
+        let mut prev = NONE;
+        let mut current = self.next;
+
+        for _ in 0..free {
+            match self.entries.get(current) {
+                Some(Entry::Next { prev: linked, next }) => {
+                    if *linked != prev {
+                        return Err(format!("Entry {} links back to {} not {}", current, linked, prev));
+                    }
+
+                    prev = current;
+                    current = *next;
+                }
+                Some(Entry::Data(_)) => return Err(format!("Entry {} is free but holds data", current)),
+                None if current == NONE => {
+                    return Err(format!("The free list is shorter than the {} empty entries", free))
+                }
+                None => return Err(format!("The free list points past the end at {}", current)),
+            }
+        }
+
+        if current != NONE {
+            return Err(format!("The free list is longer than the {} empty entries", free));
+        }
+
+        Ok(())
+    }
+}
+
+// The same as the ParStorage methods without needing the trait in scope
+#[cfg(feature = "rayon")]
+impl<T> NoVec<T> {
+    pub fn par_iter(&self) -> impl rayon::iter::ParallelIterator<Item = (usize, &T)>
+    where
+        T: Sync,
+    {
+        use rayon::prelude::*;
+
+        self.entries.par_iter().enumerate().filter_map(|(index, x)| x.option_ref().map(|x| (index, x)))
+    }
+
+    pub fn par_iter_mut(&mut self) -> impl rayon::iter::ParallelIterator<Item = (usize, &mut T)>
+    where
+        T: Send,
+    {
+        use rayon::prelude::*;
+
+        self.entries.par_iter_mut().enumerate().filter_map(|(index, x)| x.option_ref_mut().map(|x| (index, x)))
+    }
+
+    pub fn par_values(&self) -> impl rayon::iter::ParallelIterator<Item = &T>
+    where
+        T: Sync,
+    {
+        use rayon::prelude::*;
+
+        self.entries.par_iter().filter_map(|x| x.option_ref())
+    }
+
+    pub fn par_values_mut(&mut self) -> impl rayon::iter::ParallelIterator<Item = &mut T>
+    where
+        T: Send,
+    {
+        use rayon::prelude::*;
+
+        self.entries.par_iter_mut().filter_map(|x| x.option_ref_mut())
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T> crate::par::ParStorage for NoVec<T> {
+    fn par_iter<'a>(
+        &'a self,
+    ) -> impl rayon::iter::ParallelIterator<Item = (usize, &'a T)> + 'a
+    where
+        T: Sync + 'a,
+    {
+        NoVec::par_iter(self)
+    }
+
+    fn par_iter_mut<'a>(
+        &'a mut self,
+    ) -> impl rayon::iter::ParallelIterator<Item = (usize, &'a mut T)> + 'a
+    where
+        T: Send + 'a,
+    {
+        NoVec::par_iter_mut(self)
     }
 }
 
@@ -298,4 +768,10 @@ impl<T> ExpandableStorage for NoVec<T> {
     fn push(&mut self, value: T) -> usize {
         self.push(value)
     }
+}
+
+impl<T> WithCapacity for NoVec<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        NoVec::with_capacity(capacity)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,255 @@
+use crate::{idvec::IdVecIndex, *};
+use std::convert::TryFrom;
+
+// The index NoVec32 is addressed by through the storage traits. It's its own type so u32 doesn't
+// have to convert into IdVecIndex, which would leave integer literals without a single type to
+// infer for IdVec's methods
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Index32(pub u32);
+
+impl From<Index32> for IdVecIndex {
+    fn from(index: Index32) -> Self {
+        IdVecIndex(index.0 as usize)
+    }
+}
+
+// A NoVec addressed by u32, which halves the size of the free list links and of every index held
+// elsewhere, such as the key map of a MappedStorage. Holds at most u32::MAX entries, 'NONE'
+// marks either end of the free list
+#[derive(Clone, Debug)]
+pub struct NoVec32<T> {
+    next: u32,
+    len: usize,
+    entries: Vec<Entry32<T>>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Entry32<T> {
+    Data(T),
+    Next { prev: u32, next: u32 },
+}
+
+pub const NONE: u32 = u32::MAX;
+
+impl<T> Entry32<T> {
+    pub fn option_ref(&self) -> Option<&T> {
+        match self {
+            Entry32::Data(data) => Some(data),
+            Entry32::Next { .. } => None,
+        }
+    }
+
+    pub fn option_ref_mut(&mut self) -> Option<&mut T> {
+        match self {
+            Entry32::Data(data) => Some(data),
+            Entry32::Next { .. } => None,
+        }
+    }
+
+    pub fn is_data(&self) -> bool {
+        matches!(self, Entry32::Data(_))
+    }
+
+    fn links(&self) -> (u32, u32) {
+        match self {
+            Entry32::Next { prev, next } => (*prev, *next),
+            Entry32::Data(_) => panic!("Tried to unwrap data"),
+        }
+    }
+}
+
+impl<T> Default for NoVec32<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> NoVec32<T> {
+    pub fn new() -> Self {
+        Self { next: NONE, len: 0, entries: vec![] }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { next: NONE, len: 0, entries: Vec::with_capacity(capacity) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
+    // The index the next push will use, the most recently emptied entry is reused first
+    pub fn next_id(&self) -> u32 {
+        match self.next {
+            NONE => self.entries.len() as u32,
+            next => next,
+        }
+    }
+
+    pub fn get(&self, index: u32) -> Option<&T> {
+        self.entries.get(index as usize)?.option_ref()
+    }
+
+    pub fn get_mut(&mut self, index: u32) -> Option<&mut T> {
+        self.entries.get_mut(index as usize)?.option_ref_mut()
+    }
+
+    // Panics if the storage would need more than u32::MAX entries
+    pub fn push(&mut self, value: T) -> u32 {
+        self.len += 1;
+
+        if self.next == NONE {
+            let index = Self::to_index(self.entries.len());
+            self.entries.push(Entry32::Data(value));
+
+            return index;
+        }
+
+        let index = self.next;
+        self.unlink(index);
+        self.entries[index as usize] = Entry32::Data(value);
+
+        index
+    }
+
+    // Places the value at the index whether or not it's in use, skipped entries are left empty
+    pub fn insert_at(&mut self, index: u32, value: T) -> Option<T> {
+        let len = self.entries.len() as u32;
+
+        if index >= len {
+            Self::to_index(index as usize);
+            self.entries.resize_with(index as usize + 1, || Entry32::Next { prev: NONE, next: NONE });
+
+            // Linked backwards so the lowest new entry is used first
+            for skipped in (len..=index).rev() {
+                self.link_front(skipped);
+            }
+        }
+
+        if let Entry32::Data(data) = &mut self.entries[index as usize] {
+            return Some(std::mem::replace(data, value));
+        }
+
+        self.unlink(index);
+        self.entries[index as usize] = Entry32::Data(value);
+        self.len += 1;
+
+        None
+    }
+
+    pub fn remove(&mut self, index: u32) -> Option<T> {
+        let entry = self.entries.get_mut(index as usize)?;
+
+        if !entry.is_data() {
+            return None;
+        }
+
+        let removed = std::mem::replace(entry, Entry32::Next { prev: NONE, next: NONE });
+        self.link_front(index);
+        self.len -= 1;
+
+        match removed {
+            Entry32::Data(data) => Some(data),
+            Entry32::Next { .. } => None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.next = NONE;
+        self.len = 0;
+        self.entries.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &T)> {
+        self.entries.iter().enumerate().filter_map(|(index, x)| x.option_ref().map(|x| (index as u32, x)))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (u32, &mut T)> {
+        self.entries.iter_mut().enumerate().filter_map(|(index, x)| x.option_ref_mut().map(|x| (index as u32, x)))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().filter_map(|x| x.option_ref())
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.entries.iter_mut().filter_map(|x| x.option_ref_mut())
+    }
+
+    fn to_index(len: usize) -> u32 {
+        match u32::try_from(len) {
+            Ok(index) if index != NONE => index,
+            _ => panic!("NoVec32 can't hold more than {} entries", NONE),
+        }
+    }
+
+    // Takes an empty entry out of the free list
+    fn unlink(&mut self, index: u32) {
+        let (prev, next) = self.entries[index as usize].links();
+
+        match prev {
+            NONE => self.next = next,
+            prev => self.set_links(prev, |links| links.1 = next),
+        }
+
+        if next != NONE {
+            self.set_links(next, |links| links.0 = prev);
+        }
+    }
+
+    // Puts an empty entry at the front of the free list
+    fn link_front(&mut self, index: u32) {
+        if self.next != NONE {
+            self.set_links(self.next, |links| links.0 = index);
+        }
+
+        self.entries[index as usize] = Entry32::Next { prev: NONE, next: self.next };
+        self.next = index;
+    }
+
+    fn set_links(&mut self, index: u32, f: impl FnOnce(&mut (u32, u32))) {
+        let mut links = self.entries[index as usize].links();
+        f(&mut links);
+        self.entries[index as usize] = Entry32::Next { prev: links.0, next: links.1 };
+    }
+}
+
+impl<T> UnorderedStorage for NoVec32<T> {
+    type Index = Index32;
+    type Item = T;
+
+    fn insert(&mut self, index: Index32, value: T) -> Option<T> {
+        self.insert_at(index.0, value)
+    }
+
+    fn remove(&mut self, index: &Index32) -> Option<T> {
+        <NoVec32<T>>::remove(self, index.0)
+    }
+
+    fn get(&self, index: &Index32) -> Option<&T> {
+        <NoVec32<T>>::get(self, index.0)
+    }
+
+    fn get_mut(&mut self, index: &Index32) -> Option<&mut T> {
+        <NoVec32<T>>::get_mut(self, index.0)
+    }
+}
+
+impl<T> ExpandableStorage for NoVec32<T> {
+    fn push(&mut self, value: T) -> Index32 {
+        Index32(self.push(value))
+    }
+}
+
+impl<T> WithCapacity for NoVec32<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        NoVec32::with_capacity(capacity)
+    }
+}
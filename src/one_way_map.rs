@@ -1,39 +1,478 @@
-use std::{collections::HashMap, hash::Hash};
+use crate::{ExpandableStorage, UnorderedStorage, WithCapacity};
+use std::{
+    borrow::Borrow,
+    collections::hash_map::{self, Entry as HashEntry, HashMap, RandomState},
+    hash::{BuildHasher, Hash},
+};
 
-pub struct OneWayMap<K, T> {
-    mapping: HashMap<K, usize>,
-    storage: Vec<T>,
+// Slots of removed keys are left empty until a new key claims them so every other index stays
+// the same. Each slot keeps a copy of its key so indices can be looked up the other way.
+// Through the storage traits it's addressed by index alone, values stored that way have no key
+pub struct OneWayMap<K, T, H = RandomState> {
+    mapping: HashMap<K, usize, H>,
+    storage: Vec<Option<T>>,
+    // The key of each slot, lined up with 'storage'
+    keys: Vec<Option<K>>,
+    // Every empty slot, the last one is claimed first
+    free: Vec<usize>,
 }
 
-impl<K: Hash + Eq, T> OneWayMap<K, T> {
+impl<K: Hash + Eq + Clone, T> OneWayMap<K, T> {
     pub fn new() -> Self {
-        Self { mapping: HashMap::new(), storage: vec![] }
+        Self::with_hasher(RandomState::new())
     }
 
-    pub fn get_idx(&self, binding: &K) -> Option<usize> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            mapping: HashMap::with_capacity(capacity),
+            storage: Vec::with_capacity(capacity),
+            keys: Vec::with_capacity(capacity),
+            free: vec![],
+        }
+    }
+}
+
+impl<K, T, H> Default for OneWayMap<K, T, H>
+where
+    K: Hash + Eq + Clone,
+    H: BuildHasher + Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(H::default())
+    }
+}
+
+impl<K, T, H> OneWayMap<K, T, H>
+where
+    K: Hash + Eq + Clone,
+    H: BuildHasher,
+{
+    pub fn with_hasher(hasher: H) -> Self {
+        Self { mapping: HashMap::with_hasher(hasher), storage: vec![], keys: vec![], free: vec![] }
+    }
+
+    pub fn get_key(&self, idx: usize) -> Option<&K> {
+        self.keys.get(idx)?.as_ref()
+    }
+
+    pub fn get_idx<Q>(&self, binding: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
         self.mapping.get(binding).map(|value| *value)
     }
 
+    // Counts values stored without a key too
+    pub fn len(&self) -> usize {
+        self.storage.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The number of slots left empty by removals that haven't been claimed by a new key yet
+    pub fn vacant_count(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.mapping.contains_key(key)
+    }
+
     pub fn get(&self, idx: usize) -> Option<&T> {
-        self.storage.get(idx)
+        self.storage.get(idx).and_then(|value| value.as_ref())
+    }
+
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+        self.storage.get_mut(idx).and_then(|value| value.as_mut())
+    }
+
+    pub fn get_by_key<Q>(&self, key: &Q) -> Option<&T>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(*self.mapping.get(key)?)
+    }
+
+    pub fn get_mut_by_key<Q>(&mut self, key: &Q) -> Option<&mut T>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = *self.mapping.get(key)?;
+        self.get_mut(idx)
     }
 
     pub fn clear(&mut self) {
         self.mapping.clear();
         self.storage.clear();
+        self.keys.clear();
+        self.free.clear();
     }
 
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, T> {
+        let storage = &mut self.storage;
+
+        match self.mapping.entry(key) {
+            HashEntry::Occupied(entry) => Entry::Occupied(OccupiedEntry { entry, storage }),
+            HashEntry::Vacant(entry) => Entry::Vacant(VacantEntry {
+                entry,
+                storage,
+                keys: &mut self.keys,
+                free: &mut self.free,
+            }),
+        }
+    }
+
+    // Returns the index of the key, only calling 'f' when the key isn't in the map yet
+    pub fn get_or_insert_with<F: FnOnce() -> T>(&mut self, key: K, f: F) -> usize {
+        self.entry(key).or_insert_with(f)
+    }
+
+    // Replaces the value of an existing key in place, use 'entry' to keep the existing value
     pub fn push(&mut self, key: K, value: T) -> usize {
-        let next = self.storage.len();
-        let mapping = &mut self.mapping;
+        if let Some(idx) = self.mapping.get(&key) {
+            self.storage[*idx] = Some(value);
+            return *idx;
+        }
+
+        let idx = claim_slot(&mut self.storage, &mut self.keys, &mut self.free, key.clone(), value);
+        self.mapping.insert(key, idx);
+
+        idx
+    }
+
+    // Stores a value that can only be reached by its index
+    pub fn push_value(&mut self, value: T) -> usize {
+        match self.free.pop() {
+            Some(idx) => {
+                self.storage[idx] = Some(value);
+                idx
+            }
+            None => {
+                self.storage.push(Some(value));
+                self.keys.push(None);
+                self.storage.len() - 1
+            }
+        }
+    }
+
+    // Places the value at the index whether or not it's in use, a slot that has a key keeps it.
+    // Skipped slots are left empty
+    pub fn insert_at(&mut self, idx: usize, value: T) -> Option<T> {
+        let len = self.storage.len();
+
+        if idx >= len {
+            self.storage.resize_with(idx + 1, || None);
+            self.keys.resize_with(idx + 1, || None);
+            self.free.extend((len..idx).rev());
+        } else if self.storage[idx].is_none() {
+            let claimed = self.free.iter().rposition(|free| *free == idx).unwrap();
+            self.free.remove(claimed);
+        }
+
+        self.storage[idx].replace(value)
+    }
+
+    // The slot is left empty rather than moved into so every other index stays the same
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<T>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.mapping.remove(key)?;
+        self.keys[idx] = None;
+        self.free.push(idx);
+
+        self.storage[idx].take()
+    }
+
+    pub fn remove_idx(&mut self, idx: usize) -> Option<T> {
+        let value = self.storage.get_mut(idx)?.take()?;
+        if let Some(key) = self.keys[idx].take() {
+            self.mapping.remove(&key);
+        }
+        self.free.push(idx);
+
+        Some(value)
+    }
+
+    // Only visits values with a key, in the same order as the underlying hash map
+    pub fn iter(&self) -> impl Iterator<Item = (&K, usize, &T)> + '_ {
+        let storage = &self.storage;
+
+        self.mapping
+            .iter()
+            .filter_map(move |(key, idx)| storage[*idx].as_ref().map(|value| (key, *idx, value)))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> + '_ {
+        self.mapping.keys()
+    }
+
+    // Iterates in index order
+    pub fn values(&self) -> impl Iterator<Item = &T> + '_ {
+        self.storage.iter().filter_map(|value| value.as_ref())
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        self.storage.iter_mut().filter_map(|value| value.as_mut())
+    }
+}
+
+// Entries are written alongside their index and the slot count so a loaded map hands out
+// exactly the same indices as the one that was saved
+#[cfg(feature = "serde")]
+impl<K, T, H> serde::Serialize for OneWayMap<K, T, H>
+where
+    K: Hash + Eq + Clone + serde::Serialize,
+    T: serde::Serialize,
+    H: BuildHasher,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(serde::Serialize)]
+        struct Persisted<'a, K, T> {
+            slots: usize,
+            entries: Vec<(&'a K, usize, &'a T)>,
+            unkeyed: Vec<(usize, &'a T)>,
+        }
+
+        let unkeyed = self
+            .storage
+            .iter()
+            .zip(&self.keys)
+            .enumerate()
+            .filter_map(|(idx, (value, key))| match (value, key) {
+                (Some(value), None) => Some((idx, value)),
+                _ => None,
+            })
+            .collect();
+
+        Persisted { slots: self.storage.len(), entries: self.iter().collect(), unkeyed }
+            .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K, T, H> serde::Deserialize<'de> for OneWayMap<K, T, H>
+where
+    K: Hash + Eq + Clone + serde::Deserialize<'de>,
+    T: serde::Deserialize<'de>,
+    H: BuildHasher + Default,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        struct Persisted<K, T> {
+            slots: usize,
+            entries: Vec<(K, usize, T)>,
+            // Missing from maps saved before values could be stored without a key
+            #[serde(default = "Vec::new")]
+            unkeyed: Vec<(usize, T)>,
+        }
+
+        let persisted = Persisted::<K, T>::deserialize(deserializer)?;
+        let mut map = Self::default();
+        map.storage.resize_with(persisted.slots, || None);
+        map.keys.resize_with(persisted.slots, || None);
+
+        let keyed = persisted.entries.into_iter().map(|(key, idx, value)| (Some(key), idx, value));
+        let unkeyed = persisted.unkeyed.into_iter().map(|(idx, value)| (None, idx, value));
+
+        for (key, idx, value) in keyed.chain(unkeyed) {
+            match map.storage.get_mut(idx) {
+                Some(slot @ None) => *slot = Some(value),
+                Some(Some(_)) => return Err(D::Error::custom(format!("duplicate index {}", idx))),
+                None => return Err(D::Error::custom(format!("index {} out of bounds", idx))),
+            }
+
+            if let Some(key) = key {
+                map.keys[idx] = Some(key.clone());
+                if map.mapping.insert(key, idx).is_some() {
+                    return Err(D::Error::custom("duplicate key"));
+                }
+            }
+        }
+
+        // Reversed so the lowest free slot is claimed first
+        map.free = (0..map.storage.len()).rev().filter(|idx| map.storage[*idx].is_none()).collect();
+
+        Ok(map)
+    }
+}
+
+impl<K, T, H> UnorderedStorage for OneWayMap<K, T, H>
+where
+    K: Hash + Eq + Clone,
+    H: BuildHasher,
+{
+    type Index = usize;
+    type Item = T;
+
+    fn insert(&mut self, index: usize, value: T) -> Option<T> {
+        self.insert_at(index, value)
+    }
+
+    fn remove(&mut self, index: &usize) -> Option<T> {
+        self.remove_idx(*index)
+    }
+
+    fn get(&self, index: &usize) -> Option<&T> {
+        <OneWayMap<K, T, H>>::get(self, *index)
+    }
+
+    fn get_mut(&mut self, index: &usize) -> Option<&mut T> {
+        <OneWayMap<K, T, H>>::get_mut(self, *index)
+    }
+}
+
+impl<K, T, H> ExpandableStorage for OneWayMap<K, T, H>
+where
+    K: Hash + Eq + Clone,
+    H: BuildHasher,
+{
+    fn push(&mut self, value: T) -> usize {
+        self.push_value(value)
+    }
+}
+
+impl<K: Hash + Eq + Clone, T> WithCapacity for OneWayMap<K, T> {
+    fn with_capacity(capacity: usize) -> Self {
+        OneWayMap::with_capacity(capacity)
+    }
+}
+
+pub struct OccupiedEntry<'a, K: 'a, T: 'a> {
+    entry: hash_map::OccupiedEntry<'a, K, usize>,
+    storage: &'a mut Vec<Option<T>>,
+}
+
+impl<'a, K: 'a, T: 'a> OccupiedEntry<'a, K, T> {
+    pub fn key(&self) -> &K {
+        self.entry.key()
+    }
+
+    pub fn index(&self) -> usize {
+        *self.entry.get()
+    }
+
+    pub fn get(&self) -> &T {
+        self.storage[self.index()].as_ref().unwrap()
+    }
 
-        let idx = mapping.entry(key).or_insert(next);
+    pub fn get_mut(&mut self) -> &mut T {
+        let idx = self.index();
+        self.storage[idx].as_mut().unwrap()
+    }
+
+    pub fn into_mut(self) -> &'a mut T {
+        let idx = self.index();
+        self.storage[idx].as_mut().unwrap()
+    }
+
+    // Swaps in a new value while keeping the index
+    pub fn insert(&mut self, value: T) -> T {
+        std::mem::replace(self.get_mut(), value)
+    }
+}
+
+pub struct VacantEntry<'a, K: 'a, T: 'a> {
+    entry: hash_map::VacantEntry<'a, K, usize>,
+    storage: &'a mut Vec<Option<T>>,
+    keys: &'a mut Vec<Option<K>>,
+    free: &'a mut Vec<usize>,
+}
+
+impl<'a, K: 'a, T: 'a> VacantEntry<'a, K, T> {
+    pub fn key(&self) -> &K {
+        self.entry.key()
+    }
+
+    pub fn insert(self, value: T) -> usize
+    where
+        K: Clone,
+    {
+        let key = self.entry.key().clone();
+        let idx = claim_slot(self.storage, self.keys, self.free, key, value);
+        self.entry.insert(idx);
+
+        idx
+    }
+}
+
+// Puts the key and value in the most recently freed slot, or a new one at the end
+fn claim_slot<K, T>(
+    storage: &mut Vec<Option<T>>,
+    keys: &mut Vec<Option<K>>,
+    free: &mut Vec<usize>,
+    key: K,
+    value: T,
+) -> usize {
+    match free.pop() {
+        Some(idx) => {
+            storage[idx] = Some(value);
+            keys[idx] = Some(key);
+            idx
+        }
+        None => {
+            storage.push(Some(value));
+            keys.push(Some(key));
+            storage.len() - 1
+        }
+    }
+}
+
+pub enum Entry<'a, K: 'a, T: 'a> {
+    Occupied(OccupiedEntry<'a, K, T>),
+    Vacant(VacantEntry<'a, K, T>),
+}
+
+impl<'a, K: Clone + 'a, T: 'a> Entry<'a, K, T> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(occupied) => occupied.key(),
+            Entry::Vacant(vacant) => vacant.key(),
+        }
+    }
+
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            Entry::Occupied(occupied) => Some(occupied.index()),
+            Entry::Vacant(_) => None,
+        }
+    }
+
+    pub fn or_insert(self, default: T) -> usize {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> T>(self, default: F) -> usize {
+        match self {
+            Entry::Occupied(occupied) => occupied.index(),
+            Entry::Vacant(vacant) => vacant.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> usize
+    where
+        T: Default,
+    {
+        self.or_insert_with(Default::default)
+    }
 
-        match self.storage.get_mut(*idx) {
-            Some(data) => *data = value,
-            None => self.storage.push(value),
+    pub fn and_modify<F: FnOnce(&mut T)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(occupied) = &mut self {
+            f(occupied.get_mut());
         }
 
-        *idx
+        self
     }
 }
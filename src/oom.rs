@@ -1,10 +1,194 @@
-use std::{slice::IterMut, iter::{once, Once}};
+use std::{
+    fmt::{self, Debug, Formatter},
+    iter::{once, FromIterator, Once},
+    mem::MaybeUninit,
+    ptr,
+    slice::IterMut,
+    vec::IntoIter,
+};
 
+pub const INLINE_CAPACITY: usize = 4;
 
-#[derive(Clone, Debug)]
+/// Fixed capacity storage that lives inside the 'OneOrMany' so that small collections don't need
+/// to allocate
+pub struct Inline<T> {
+    len: usize,
+    items: [MaybeUninit<T>; INLINE_CAPACITY],
+}
+
+impl<T> Inline<T> {
+    pub fn new() -> Self {
+        // An array of uninitialized values doesn't need to be initialized itself
+        let items = unsafe { MaybeUninit::<[MaybeUninit<T>; INLINE_CAPACITY]>::uninit().assume_init() };
+
+        Self { len: 0, items }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == INLINE_CAPACITY
+    }
+
+    /// Hands the item back if there is no room left
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(item);
+        }
+
+        self.items[self.len] = MaybeUninit::new(item);
+        self.len += 1;
+
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.len -= 1;
+
+        Some(unsafe { self.items[self.len].as_ptr().read() })
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        if index >= self.len {
+            return None;
+        }
+
+        unsafe {
+            let item = self.items[index].as_ptr().read();
+            let base = self.items.as_mut_ptr();
+            ptr::copy(base.add(index + 1), base.add(index), self.len - index - 1);
+            self.len -= 1;
+
+            Some(item)
+        }
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        let len = self.len;
+        let mut kept = 0;
+
+        // If f panics the remaining items are leaked rather than dropped twice
+        self.len = 0;
+
+        for i in 0..len {
+            let item = unsafe { self.items[i].as_ptr().read() };
+
+            if f(&item) {
+                self.items[kept] = MaybeUninit::new(item);
+                kept += 1;
+                self.len = kept;
+            }
+        }
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.items.as_ptr() as *const T, self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.items.as_mut_ptr() as *mut T, self.len) }
+    }
+
+    /// Moves the items into a vector with room for one more
+    fn spill(mut self) -> Vec<T> {
+        let mut vec = Vec::with_capacity(self.len + 1);
+
+        while let Some(item) = self.remove(0) {
+            vec.push(item);
+        }
+
+        vec
+    }
+}
+
+impl<T> Default for Inline<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Inline<T> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.as_mut_slice()) }
+    }
+}
+
+impl<T: Clone> Clone for Inline<T> {
+    fn clone(&self) -> Self {
+        let mut inline = Self::new();
+
+        for item in self.as_slice() {
+            let _ = inline.push(item.clone());
+        }
+
+        inline
+    }
+}
+
+impl<T: Debug> Debug for Inline<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+pub struct InlineIntoIter<T> {
+    index: usize,
+    inline: Inline<T>,
+}
+
+impl<T> Iterator for InlineIntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.index >= self.inline.len {
+            return None;
+        }
+
+        let item = unsafe { self.inline.items[self.index].as_ptr().read() };
+        self.index += 1;
+
+        Some(item)
+    }
+}
+
+impl<T> Drop for InlineIntoIter<T> {
+    fn drop(&mut self) {
+        let len = self.inline.len;
+        self.inline.len = 0;
+
+        for i in self.index..len {
+            unsafe { ptr::drop_in_place(self.inline.items[i].as_mut_ptr()) }
+        }
+    }
+}
+
+impl<T> IntoIterator for Inline<T> {
+    type Item = T;
+    type IntoIter = InlineIntoIter<T>;
+
+    fn into_iter(self) -> InlineIntoIter<T> {
+        InlineIntoIter { index: 0, inline: self }
+    }
+}
+
+/// 'Few' holds up to 'INLINE_CAPACITY' items without allocating, anything larger spills into
+/// 'Many'
+#[derive(Clone, Debug, Default)]
 pub enum OneOrMany<T> {
+    #[default]
     None,
     One(T),
+    Few(Inline<T>),
     Many(Vec<T>),
 }
 
@@ -15,16 +199,107 @@ impl<T> OneOrMany<T> {
             None => return OneOrMany::None,
         };
 
-        let mut vec = match items.next() {
-            Some(item) => vec![first, item],
+        let mut values = match items.next() {
+            Some(second) => {
+                let mut inline = Inline::new();
+                let _ = inline.push(first);
+                let _ = inline.push(second);
+                OneOrMany::Few(inline)
+            }
             None => return OneOrMany::One(first)
         };
 
         for item in items {
-            vec.push(item);
+            values.push(item);
+        }
+
+        values
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            OneOrMany::None => 0,
+            OneOrMany::One(_) => 1,
+            OneOrMany::Few(inline) => inline.len(),
+            OneOrMany::Many(vec) => vec.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.as_slice().get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.as_mut_slice().get_mut(index)
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        match self {
+            OneOrMany::None => &[],
+            OneOrMany::One(item) => std::slice::from_ref(item),
+            OneOrMany::Few(inline) => inline.as_slice(),
+            OneOrMany::Many(vec) => vec.as_slice(),
         }
+    }
 
-        OneOrMany::Many(vec)
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        match self {
+            OneOrMany::None => &mut [],
+            OneOrMany::One(item) => std::slice::from_mut(item),
+            OneOrMany::Few(inline) => inline.as_mut_slice(),
+            OneOrMany::Many(vec) => vec.as_mut_slice(),
+        }
+    }
+
+    /// Moves the items out, leaving 'None' behind
+    pub fn drain(&mut self) -> OneOrManyIntoIter<T> {
+        self.take().into_iter()
+    }
+
+    pub fn take(&mut self) -> OneOrMany<T> {
+        std::mem::take(self)
+    }
+
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> OneOrMany<U> {
+        match self {
+            OneOrMany::None => OneOrMany::None,
+            OneOrMany::One(item) => OneOrMany::One(f(item)),
+            OneOrMany::Few(inline) => {
+                let mut mapped = Inline::new();
+
+                for item in inline {
+                    let _ = mapped.push(f(item));
+                }
+
+                OneOrMany::Few(mapped)
+            }
+            OneOrMany::Many(vec) => OneOrMany::Many(vec.into_iter().map(f).collect()),
+        }
+    }
+
+    /// Like 'map' but drops the items f returns None for, demoting the result if it shrinks
+    pub fn filter_map<U>(self, mut f: impl FnMut(T) -> Option<U>) -> OneOrMany<U> {
+        let mut mapped = match self {
+            OneOrMany::None => OneOrMany::None,
+            OneOrMany::One(item) => OneOrMany::from(f(item)),
+            OneOrMany::Few(inline) => {
+                let mut mapped = Inline::new();
+
+                for item in inline.into_iter().filter_map(f) {
+                    let _ = mapped.push(item);
+                }
+
+                OneOrMany::Few(mapped)
+            }
+            OneOrMany::Many(vec) => OneOrMany::Many(vec.into_iter().filter_map(f).collect()),
+        };
+
+        mapped.demote();
+        mapped
     }
 
     pub fn take_one(self) -> T {
@@ -39,12 +314,68 @@ impl<T> OneOrMany<T> {
             OneOrMany::None => *self = OneOrMany::One(item),
             OneOrMany::One(_) => {
                 let temp = std::mem::replace(self, OneOrMany::None);
-                *self = OneOrMany::Many(vec![temp.take_one(), item]);
+                let mut inline = Inline::new();
+                let _ = inline.push(temp.take_one());
+                let _ = inline.push(item);
+                *self = OneOrMany::Few(inline);
             },
+            OneOrMany::Few(inline) => {
+                if let Err(item) = inline.push(item) {
+                    let mut vec = std::mem::take(inline).spill();
+                    vec.push(item);
+                    *self = OneOrMany::Many(vec);
+                }
+            }
             OneOrMany::Many(vec) => vec.push(item),
         }
     }
 
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        let removed = match self {
+            OneOrMany::One(_) if index == 0 => match std::mem::replace(self, OneOrMany::None) {
+                OneOrMany::One(item) => Some(item),
+                _ => unreachable!(),
+            },
+            OneOrMany::Few(inline) => inline.remove(index),
+            OneOrMany::Many(vec) if index < vec.len() => Some(vec.remove(index)),
+            _ => None,
+        };
+
+        self.demote();
+        removed
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        match self.len() {
+            0 => None,
+            len => self.remove(len - 1),
+        }
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        match self {
+            OneOrMany::None => {}
+            OneOrMany::One(item) => {
+                if !f(item) {
+                    *self = OneOrMany::None;
+                }
+            }
+            OneOrMany::Few(inline) => inline.retain(f),
+            OneOrMany::Many(vec) => vec.retain(f),
+        }
+
+        self.demote();
+    }
+
+    // Shrinks a Few or Many holding less than two items down to One or None
+    fn demote(&mut self) {
+        match self {
+            OneOrMany::Few(inline) if inline.len() < 2 => *self = OneOrMany::from(inline.pop()),
+            OneOrMany::Many(vec) if vec.len() < 2 => *self = OneOrMany::from(vec.pop()),
+            _ => {}
+        }
+    }
+
     pub fn iter(&self) -> OneOrManyIter<T> {
         OneOrManyIter {
             index: 0,
@@ -56,11 +387,47 @@ impl<T> OneOrMany<T> {
         match self {
             OneOrMany::None => OneOrManyIterMut::None,
             OneOrMany::One(item) => OneOrManyIterMut::One(once(item)),
+            OneOrMany::Few(inline) => OneOrManyIterMut::Many(inline.as_mut_slice().iter_mut()),
             OneOrMany::Many(vec) => OneOrManyIterMut::Many(vec.iter_mut()),
         }
     }
 }
 
+impl<T> From<T> for OneOrMany<T> {
+    fn from(item: T) -> Self {
+        OneOrMany::One(item)
+    }
+}
+
+impl<T> From<Option<T>> for OneOrMany<T> {
+    fn from(item: Option<T>) -> Self {
+        match item {
+            Some(item) => OneOrMany::One(item),
+            None => OneOrMany::None,
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for OneOrMany<T> {
+    fn from(vec: Vec<T>) -> Self {
+        match vec.len() {
+            0 | 1 => OneOrMany::new(vec.into_iter()),
+            _ => OneOrMany::Many(vec),
+        }
+    }
+}
+
+impl<T> From<OneOrMany<T>> for Vec<T> {
+    fn from(items: OneOrMany<T>) -> Self {
+        match items {
+            OneOrMany::None => vec![],
+            OneOrMany::One(item) => vec![item],
+            OneOrMany::Few(inline) => inline.into_iter().collect(),
+            OneOrMany::Many(vec) => vec,
+        }
+    }
+}
+
 pub struct OneOrManyIter<'a, T> {
     index: usize,
     values: &'a OneOrMany<T>
@@ -79,6 +446,11 @@ impl<'a, T> Iterator for OneOrManyIter<'a, T> {
                 },
                 _ => None
             }
+            OneOrMany::Few(inline) => {
+                let to_return = inline.as_slice().get(self.index);
+                self.index += 1;
+                to_return
+            }
             OneOrMany::Many(vec) => {
                 let to_return = vec.get(self.index);
                 self.index += 1;
@@ -104,4 +476,98 @@ impl<'a, T> Iterator for OneOrManyIterMut<'a, T> {
             OneOrManyIterMut::Many(iter) => iter.next(), 
         }
     }
-}
\ No newline at end of file
+}
+
+pub enum OneOrManyIntoIter<T> {
+    None,
+    One(Once<T>),
+    Few(InlineIntoIter<T>),
+    Many(IntoIter<T>),
+}
+
+impl<T> Iterator for OneOrManyIntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match self {
+            OneOrManyIntoIter::None => None,
+            OneOrManyIntoIter::One(iter) => iter.next(),
+            OneOrManyIntoIter::Few(iter) => iter.next(),
+            OneOrManyIntoIter::Many(iter) => iter.next(),
+        }
+    }
+}
+
+impl<T> IntoIterator for OneOrMany<T> {
+    type Item = T;
+    type IntoIter = OneOrManyIntoIter<T>;
+
+    fn into_iter(self) -> OneOrManyIntoIter<T> {
+        match self {
+            OneOrMany::None => OneOrManyIntoIter::None,
+            OneOrMany::One(item) => OneOrManyIntoIter::One(once(item)),
+            OneOrMany::Few(inline) => OneOrManyIntoIter::Few(inline.into_iter()),
+            OneOrMany::Many(vec) => OneOrManyIntoIter::Many(vec.into_iter()),
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a OneOrMany<T> {
+    type Item = &'a T;
+    type IntoIter = OneOrManyIter<'a, T>;
+
+    fn into_iter(self) -> OneOrManyIter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut OneOrMany<T> {
+    type Item = &'a mut T;
+    type IntoIter = OneOrManyIterMut<'a, T>;
+
+    fn into_iter(self) -> OneOrManyIterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+impl<T> FromIterator<T> for OneOrMany<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        OneOrMany::new(iter.into_iter())
+    }
+}
+
+impl<T> Extend<T> for OneOrMany<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+// A single item is written as a bare value and anything else as a sequence
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for OneOrMany<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            OneOrMany::One(item) => item.serialize(serializer),
+            _ => serializer.collect_seq(self.iter()),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for OneOrMany<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Untagged<T> {
+            Many(Vec<T>),
+            One(T),
+        }
+
+        Ok(match Untagged::deserialize(deserializer)? {
+            Untagged::Many(vec) => OneOrMany::new(vec.into_iter()),
+            Untagged::One(item) => OneOrMany::One(item),
+        })
+    }
+}
@@ -0,0 +1,261 @@
+use crate::{idvec::IdVecIndex, UnorderedStorage, WithCapacity};
+use std::iter::FromIterator;
+
+pub const PAGE_SIZE: usize = 256;
+
+// An IdVec that allocates its slots a page at a time, only for the pages its indices fall in. A
+// single index of 10 million costs one page and a pointer for every page before it, rather than
+// 10 million slots. Pages are freed again as soon as they're emptied
+#[derive(Clone, Debug)]
+pub struct PagedIdVec<T> {
+    pages: Vec<Option<Page<T>>>,
+    // The number of slots holding a value
+    len: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Page<T> {
+    slots: Box<[Option<T>]>,
+    len: usize,
+}
+
+impl<T> Page<T> {
+    fn new() -> Self {
+        Self { slots: (0..PAGE_SIZE).map(|_| None).collect(), len: 0 }
+    }
+}
+
+fn split(index: usize) -> (usize, usize) {
+    (index / PAGE_SIZE, index % PAGE_SIZE)
+}
+
+impl<T> Default for PagedIdVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> PagedIdVec<T> {
+    pub fn new() -> Self {
+        Self { pages: vec![], len: 0 }
+    }
+
+    // Only reserves the page pointers, pages are still allocated as they're needed
+    pub fn with_capacity(cap: usize) -> Self {
+        Self { pages: Vec::with_capacity(cap.div_ceil(PAGE_SIZE)), len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // The number of pages currently allocated
+    pub fn page_count(&self) -> usize {
+        self.pages.iter().filter(|page| page.is_some()).count()
+    }
+
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.len = 0;
+    }
+
+    // Drops the page pointers left at the end by emptied pages
+    pub fn shrink_to_fit(&mut self) {
+        let len = self.pages.iter().rposition(Option::is_some).map_or(0, |last| last + 1);
+        self.pages.truncate(len);
+        self.pages.shrink_to_fit();
+    }
+
+    pub fn insert(&mut self, index: impl Into<IdVecIndex>, value: T) -> Option<T> {
+        let (page, slot) = split(*index.into());
+
+        if page >= self.pages.len() {
+            self.pages.resize_with(page + 1, || None);
+        }
+
+        let page = self.pages[page].get_or_insert_with(Page::new);
+        let replaced = page.slots[slot].replace(value);
+
+        if replaced.is_none() {
+            page.len += 1;
+            self.len += 1;
+        }
+
+        replaced
+    }
+
+    pub fn remove(&mut self, index: impl Into<IdVecIndex>) -> Option<T> {
+        let (page_index, slot) = split(*index.into());
+        let page = self.pages.get_mut(page_index)?.as_mut()?;
+        let removed = page.slots[slot].take()?;
+
+        page.len -= 1;
+        self.len -= 1;
+
+        if page.len == 0 {
+            self.pages[page_index] = None;
+        }
+
+        Some(removed)
+    }
+
+    pub fn get(&self, index: impl Into<IdVecIndex>) -> Option<&T> {
+        let (page, slot) = split(*index.into());
+
+        self.pages.get(page)?.as_ref()?.slots[slot].as_ref()
+    }
+
+    pub fn get_mut(&mut self, index: impl Into<IdVecIndex>) -> Option<&mut T> {
+        let (page, slot) = split(*index.into());
+
+        self.pages.get_mut(page)?.as_mut()?.slots[slot].as_mut()
+    }
+
+    pub fn iter(&'_ self) -> impl Iterator<Item = (usize, &'_ T)> + '_ {
+        self.pages
+            .iter()
+            .enumerate()
+            .filter_map(|(page_index, page)| page.as_ref().map(|page| (page_index, page)))
+            .flat_map(|(page_index, page)| {
+                page.slots.iter().enumerate().filter_map(move |(slot, value)| {
+                    value.as_ref().map(|value| (page_index * PAGE_SIZE + slot, value))
+                })
+            })
+    }
+
+    pub fn iter_mut(&'_ mut self) -> impl Iterator<Item = (usize, &'_ mut T)> + '_ {
+        self.pages
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(page_index, page)| page.as_mut().map(|page| (page_index, page)))
+            .flat_map(|(page_index, page)| {
+                page.slots.iter_mut().enumerate().filter_map(move |(slot, value)| {
+                    value.as_mut().map(|value| (page_index * PAGE_SIZE + slot, value))
+                })
+            })
+    }
+}
+
+#[cfg(feature = "testing")]
+impl<T> crate::testing::CheckInvariants for PagedIdVec<T> {
+    fn check_invariants(&self) -> Result<(), String> {
+        let mut len = 0;
+
+        for (page_index, page) in self.pages.iter().enumerate() {
+            let page = match page {
+                Some(page) => page,
+                None => continue,
+            };
+
+            let occupied = page.slots.iter().filter(|slot| slot.is_some()).count();
+
+            if occupied != page.len {
+                return Err(format!(
+                    "Page {} claims {} values but holds {}",
+                    page_index, page.len, occupied
+                ));
+            }
+
+            if occupied == 0 {
+                return Err(format!("Page {} is empty but still allocated", page_index));
+            }
+
+            len += occupied;
+        }
+
+        if len != self.len {
+            return Err(format!("Holds {} values but its length is {}", len, self.len));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T> crate::par::ParStorage for PagedIdVec<T> {
+    fn par_iter<'a>(
+        &'a self,
+    ) -> impl rayon::iter::ParallelIterator<Item = (IdVecIndex, &'a T)> + 'a
+    where
+        T: Sync + 'a,
+    {
+        use rayon::prelude::*;
+
+        self.pages
+            .par_iter()
+            .enumerate()
+            .filter_map(|(page_index, page)| page.as_ref().map(|page| (page_index, page)))
+            .flat_map_iter(|(page_index, page)| {
+                page.slots.iter().enumerate().filter_map(move |(slot, value)| {
+                    value.as_ref().map(|value| (IdVecIndex(page_index * PAGE_SIZE + slot), value))
+                })
+            })
+    }
+
+    fn par_iter_mut<'a>(
+        &'a mut self,
+    ) -> impl rayon::iter::ParallelIterator<Item = (IdVecIndex, &'a mut T)> + 'a
+    where
+        T: Send + 'a,
+    {
+        use rayon::prelude::*;
+
+        self.pages
+            .par_iter_mut()
+            .enumerate()
+            .filter_map(|(page_index, page)| page.as_mut().map(|page| (page_index, page)))
+            .flat_map_iter(|(page_index, page)| {
+                page.slots.iter_mut().enumerate().filter_map(move |(slot, value)| {
+                    value.as_mut().map(|value| (IdVecIndex(page_index * PAGE_SIZE + slot), value))
+                })
+            })
+    }
+}
+
+impl<T> UnorderedStorage for PagedIdVec<T> {
+    type Index = IdVecIndex;
+    type Item = T;
+
+    fn insert(&mut self, index: Self::Index, value: Self::Item) -> Option<Self::Item> {
+        PagedIdVec::insert(self, index, value)
+    }
+
+    fn remove(&mut self, index: &Self::Index) -> Option<Self::Item> {
+        PagedIdVec::remove(self, **index)
+    }
+
+    fn get(&self, index: &Self::Index) -> Option<&Self::Item> {
+        PagedIdVec::get(self, **index)
+    }
+
+    fn get_mut(&mut self, index: &Self::Index) -> Option<&mut Self::Item> {
+        PagedIdVec::get_mut(self, **index)
+    }
+}
+
+// Later pairs replace earlier ones with the same index
+impl<T> Extend<(usize, T)> for PagedIdVec<T> {
+    fn extend<I: IntoIterator<Item = (usize, T)>>(&mut self, iter: I) {
+        for (index, value) in iter {
+            self.insert(index, value);
+        }
+    }
+}
+
+impl<T> FromIterator<(usize, T)> for PagedIdVec<T> {
+    fn from_iter<I: IntoIterator<Item = (usize, T)>>(iter: I) -> Self {
+        let mut ids = PagedIdVec::new();
+        ids.extend(iter);
+        ids
+    }
+}
+
+impl<T> WithCapacity for PagedIdVec<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        PagedIdVec::with_capacity(capacity)
+    }
+}
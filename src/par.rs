@@ -0,0 +1,15 @@
+use crate::UnorderedStorage;
+use rayon::prelude::*;
+
+// Storages that can hand out their items to rayon, in storage order along with their indices
+pub trait ParStorage: UnorderedStorage {
+    fn par_iter<'a>(&'a self) -> impl ParallelIterator<Item = (Self::Index, &'a Self::Item)> + 'a
+    where
+        Self::Item: Sync + 'a;
+
+    fn par_iter_mut<'a>(
+        &'a mut self,
+    ) -> impl ParallelIterator<Item = (Self::Index, &'a mut Self::Item)> + 'a
+    where
+        Self::Item: Send + 'a;
+}
@@ -1,6 +1,20 @@
-use crate::{generation::*, loader::*, map::*, *};
-use manager::ManagedGen;
-use std::{any::TypeId, thread};
+use crate::{
+    any_storage::*,
+    densevec::DenseVec,
+    generation::*,
+    loader::*,
+    map::*,
+    novec::{IndexEntry, InsertOutcome, NoVec},
+    novec32::NoVec32,
+    one_way_map::*,
+    oom::*,
+    versioned::*,
+    *,
+};
+use manager::{
+    EvictionPolicy, EvictionStats, Frequency, ManagedGen, ManagedNoVec, ManagedStorage, WallClock,
+};
+use std::{any::TypeId, num::NonZeroU64, rc::Rc, thread, time::Duration};
 
 #[derive(Clone, Debug)]
 struct TestLoader(pub GenericReceiver<String>);
@@ -9,10 +23,10 @@ impl TestLoader {
     fn receive(&self) {
         for (_, into) in self.0.iter() {
             let item = match into.meta_data {
-                x if x == TypeId::of::<f32>() => GenericItem::new(101.1_f32),
-                x if x == TypeId::of::<u32>() => GenericItem::new(55_u32),
-                x if x == TypeId::of::<i32>() => GenericItem::new(-21_i32),
-                _ => GenericItem::new(false),
+                x if x == TypeId::of::<f32>() => GenericResult::new(101.1_f32),
+                x if x == TypeId::of::<u32>() => GenericResult::new(55_u32),
+                x if x == TypeId::of::<i32>() => GenericResult::new(-21_i32),
+                _ => GenericResult::new(false),
             };
 
             into.send(item).expect("Failed to send test data");
@@ -23,18 +37,18 @@ impl TestLoader {
 #[test]
 fn mapped_generation_test() {
     let mut storage = MappedGeneration::new();
-    storage.insert("Beta".to_string(), "not a cool dude".to_string());
-    storage.insert("Gamma".to_string(), "a loser".to_string());
-    storage.insert("Alpha".to_string(), "what a cool dude".to_string());
+    storage.insert("Beta".to_string(), "not a cool dude".to_string()).unwrap();
+    storage.insert("Gamma".to_string(), "a loser".to_string()).unwrap();
+    storage.insert("Alpha".to_string(), "what a cool dude".to_string()).unwrap();
 
     let mut key_idx: KeyIdx<String, StorageId> = KeyIdx::new("Alpha".to_string());
-    storage.fill_key_idx_get(&mut key_idx);
+    storage.set_idx(&mut key_idx);
     let removed = storage.remove(&key_idx).unwrap();
 
-    storage.insert("Alpha".to_string(), removed);
+    storage.insert("Alpha".to_string(), removed).unwrap();
     let key = key_idx.into_key();
     let mut key_idx: KeyIdx<String, StorageId> = KeyIdx::new(key);
-    storage.fill_key_idx_get(&mut key_idx);
+    storage.set_idx(&mut key_idx);
     let item = storage.get(&key_idx);
 
     assert!(
@@ -42,7 +56,7 @@ fn mapped_generation_test() {
             key: "Alpha".to_string(),
             index: Some(StorageId {
                 index: 2,
-                generation: 1
+                generation: NonZeroU64::new(2).unwrap()
             })
         } == key_idx
     );
@@ -82,12 +96,12 @@ fn loader_test() {
 #[test]
 fn retain_test() {
     let mut storage = MappedGeneration::new();
-    let (a, _) = storage.insert("A", 100);
-    let (b, _) = storage.insert("B", 100);
-    let (c, _) = storage.insert("C", 200);
-    let (d, _) = storage.insert("D", 300);
+    let (a, _) = storage.insert("A", 100).unwrap();
+    let (b, _) = storage.insert("B", 100).unwrap();
+    let (c, _) = storage.insert("C", 200).unwrap();
+    let (d, _) = storage.insert("D", 300).unwrap();
 
-    storage.retain(|_, value| *value != 100);
+    storage.retain(|_, _, value| *value != 100);
 
     println!("{:?}", storage);
     assert!(storage.get_by_index(&a) == None);
@@ -109,33 +123,3187 @@ fn manager_test() {
 
     let mut a = KeyIdx::new("a".to_string());
     manager.load(&mut a);
-    manager.update_loaded_blocking().unwrap();
+    manager.update_loaded_blocking();
     manager.increment(&1);
 
 
     let mut b = KeyIdx::new("b".to_string());
     manager.load(&mut b);
-    manager.update_loaded_blocking().unwrap();
+    manager.update_loaded_blocking();
     manager.increment(&1);
 
     let mut c = KeyIdx::new("c".to_string());
     manager.load(&mut c);
-    manager.update_loaded_blocking().unwrap();
+    manager.update_loaded_blocking();
     manager.increment(&1);
+    manager.remove_out_of_date();
 
     assert!(manager.get(&a) == None);
     assert!(manager.get(&b) == Some(&101.1_f32));
     assert!(manager.get(&c) == Some(&101.1_f32));
 
     manager.increment(&1);
+    manager.remove_out_of_date();
 
     assert!(manager.get(&a) == None);
     assert!(manager.get(&b) == None);
     assert!(manager.get(&c) == Some(&101.1_f32));
 
     manager.increment(&1);
+    manager.remove_out_of_date();
 
     assert!(manager.get(&a) == None);
     assert!(manager.get(&b) == None);
     assert!(manager.get(&c) == None);
 }
+
+#[test]
+fn drain_out_of_date_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut manager = ManagedGen::<String, f32, u32>::new(GenLoader::new_with_loader(sender), 2);
+
+    let mut a = KeyIdx::new("a".to_string());
+    manager.load(&mut a);
+    manager.update_loaded_blocking();
+    manager.increment(&1);
+
+    let mut b = KeyIdx::new("b".to_string());
+    manager.load(&mut b);
+    manager.update_loaded_blocking();
+    manager.increment(&1);
+
+    let drained = manager.drain_out_of_date();
+
+    assert!(drained.len() == 1);
+    assert!(drained[0].0 == "a");
+    assert!(Some(drained[0].1) == a.index);
+    assert!(drained[0].2 == 101.1_f32);
+    assert!(manager.get(&a).is_none());
+    assert!(manager.get(&b) == Some(&101.1_f32));
+    assert!(manager.drain_out_of_date().is_empty());
+}
+
+#[test]
+fn max_entries_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut manager = ManagedGen::<String, f32, u32>::new(GenLoader::new_with_loader(sender), 10)
+        .with_max_entries(2)
+        .with_evicted_limit(4);
+
+    let mut a = KeyIdx::new("a".to_string());
+    manager.load(&mut a);
+    manager.update_loaded_blocking();
+    manager.increment(&1);
+
+    let mut b = KeyIdx::new("b".to_string());
+    manager.load(&mut b);
+    manager.update_loaded_blocking();
+    manager.increment(&1);
+
+    let mut c = KeyIdx::new("c".to_string());
+    manager.load(&mut c);
+    manager.update_loaded_blocking();
+
+    assert!(manager.get(&a).is_none());
+    assert!(manager.get(&b) == Some(&101.1_f32));
+    assert!(manager.get(&c) == Some(&101.1_f32));
+
+    let evicted: Vec<_> = manager.drain_evicted().collect();
+    assert!(evicted.len() == 1);
+    assert!(evicted[0].0 == "a");
+}
+
+#[test]
+fn evicted_limit_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut manager =
+        ManagedGen::<String, f32, u32>::new(GenLoader::new_with_loader(sender), 10).with_max_entries(1);
+
+    let load = |manager: &mut ManagedGen<String, f32, u32>, key: &str| {
+        manager.load(&mut KeyIdx::new(key.to_string()));
+        manager.update_loaded_blocking();
+        manager.increment(&1);
+    };
+
+    // Nothing is kept by default, evicting frees the value straight away
+    load(&mut manager, "a");
+    load(&mut manager, "b");
+    assert!(manager.stats().total_evictions == 1);
+    assert!(manager.drain_evicted().next().is_none());
+
+    // Past the limit the oldest evictions are dropped
+    manager.set_evicted_limit(2);
+    for key in ["c", "d", "e"] {
+        load(&mut manager, key);
+    }
+    let evicted: Vec<_> = manager.drain_evicted().map(|(key, _, _)| key).collect();
+    assert!(evicted == vec!["c", "d"]);
+
+    // Explicit evictions hand the values straight back
+    let evicted = manager.evict_oldest(1);
+    assert!(evicted.len() == 1 && evicted[0].0 == "e" && evicted[0].2 == 101.1_f32);
+    assert!(manager.drain_evicted().next().is_none());
+}
+
+#[test]
+fn byte_budget_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut manager = ManagedGen::<String, f32, u32>::new(GenLoader::new_with_loader(sender), 10)
+        .with_byte_budget(8, |_| 4)
+        .with_evicted_limit(4);
+
+    let mut a = KeyIdx::new("a".to_string());
+    manager.load(&mut a);
+    manager.update_loaded_blocking();
+    manager.increment(&1);
+
+    let mut b = KeyIdx::new("b".to_string());
+    manager.load(&mut b);
+    manager.update_loaded_blocking();
+    manager.increment(&1);
+
+    assert!(manager.resident_bytes() == 8);
+
+    let mut c = KeyIdx::new("c".to_string());
+    manager.load(&mut c);
+    manager.update_loaded_blocking();
+
+    assert!(manager.resident_bytes() == 8);
+    assert!(manager.get(&a).is_none());
+    assert!(manager.get(&b) == Some(&101.1_f32));
+    assert!(manager.get(&c) == Some(&101.1_f32));
+    assert!(manager.drain_evicted().count() == 1);
+}
+
+#[test]
+fn wall_clock_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let threshold = WallClock::threshold(Duration::from_millis(20));
+    let mut manager = ManagedGen::<String, f32, WallClock>::new(GenLoader::new_with_loader(sender), threshold);
+
+    let mut a = KeyIdx::new("a".to_string());
+    manager.load(&mut a);
+    manager.update_loaded_blocking();
+    manager.remove_out_of_date();
+
+    assert!(manager.get(&a) == Some(&101.1_f32));
+
+    thread::sleep(Duration::from_millis(30));
+    manager.increment(&threshold);
+    manager.remove_out_of_date();
+
+    assert!(manager.get(&a).is_none());
+}
+
+#[test]
+fn eviction_stats_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut manager = ManagedGen::<String, f32, u32>::new(GenLoader::new_with_loader(sender), 2);
+
+    let mut a = KeyIdx::new("a".to_string());
+    manager.load(&mut a);
+    manager.update_loaded_blocking();
+    manager.increment(&1);
+
+    let mut b = KeyIdx::new("b".to_string());
+    manager.load(&mut b);
+    manager.update_loaded_blocking();
+    manager.increment(&1);
+
+    let mut counters: Vec<_> = manager.counters().map(|(key, _, value)| (key.clone(), *value)).collect();
+    counters.sort_by(|a, b| a.0.cmp(&b.0));
+    assert!(counters == vec![("a".to_string(), 2), ("b".to_string(), 1)]);
+
+    manager.remove_out_of_date();
+    assert!(manager.stats() == EvictionStats { total_evictions: 1, recent_evictions: 1, resident: 1 });
+
+    manager.increment(&1);
+    manager.remove_out_of_date();
+    assert!(manager.stats() == EvictionStats { total_evictions: 2, recent_evictions: 1, resident: 0 });
+
+    manager.increment(&1);
+    assert!(manager.stats() == EvictionStats { total_evictions: 2, recent_evictions: 0, resident: 0 });
+}
+
+#[test]
+fn eviction_stats_counts_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut manager = ManagedGen::<String, f32, u32>::new(GenLoader::new_with_loader(sender), 2);
+
+    let mut a = KeyIdx::new("a".to_string());
+    let mut b = KeyIdx::new("b".to_string());
+    manager.load(&mut a);
+    manager.load(&mut b);
+
+    // Nothing is resident until it finishes loading
+    assert!(manager.stats().resident == 0);
+    manager.update_loaded_blocking();
+    assert!(manager.stats().resident == 2);
+
+    // Explicitly evicting an entry isn't counted, only evictions made by the policy are
+    assert!(manager.evict(&a) == Some(101.1_f32));
+    assert!(manager.stats() == EvictionStats { total_evictions: 0, recent_evictions: 0, resident: 1 });
+
+    assert!(manager.evict_lru(1).len() == 1);
+    assert!(manager.stats() == EvictionStats { total_evictions: 1, recent_evictions: 1, resident: 0 });
+
+    // Neither is cancelling a load that hasn't finished
+    manager.load(&mut a);
+    assert!(manager.evict(&a).is_none());
+    assert!(manager.stats() == EvictionStats { total_evictions: 1, recent_evictions: 1, resident: 0 });
+}
+
+#[test]
+fn lru_policy_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut manager = ManagedGen::<String, f32, u32>::new(GenLoader::new_with_loader(sender), 1)
+        .with_policy(EvictionPolicy::Lru);
+
+    let mut a = KeyIdx::new("a".to_string());
+    let mut b = KeyIdx::new("b".to_string());
+    let mut c = KeyIdx::new("c".to_string());
+    manager.load(&mut a);
+    manager.load(&mut b);
+    manager.load(&mut c);
+    manager.update_loaded_blocking();
+
+    // Increments are ignored in LRU mode so nothing expires
+    manager.increment(&10);
+    manager.remove_out_of_date();
+    assert!(manager.stats().resident == 3);
+
+    let mut order: Vec<_> = manager.counters().map(|(key, _, _)| key.clone()).collect();
+    order.sort();
+    assert!(order == vec!["a", "b", "c"]);
+
+    manager.access(&mut a);
+    manager.access(&mut c);
+    manager.access(&mut b);
+
+    let evicted = manager.evict_lru(2);
+    assert!(evicted.iter().map(|(key, _, _)| &key[..]).collect::<Vec<_>>() == vec!["a", "c"]);
+    assert!(manager.get(&b) == Some(&101.1_f32));
+}
+
+#[test]
+fn eviction_skips_reloads_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut manager = ManagedGen::<String, f32, u32>::new(GenLoader::new_with_loader(sender), 1);
+    manager.storage_mut().set_duplicate_policy(DuplicatePolicy::ReplaceKeepIndex);
+
+    let mut a = KeyIdx::new("a".to_string());
+    manager.load(&mut a);
+    manager.update_loaded_blocking();
+    manager.increment(&5);
+
+    // The reload keeps a's counter around while the new value is on its way
+    manager.load(&mut a);
+    assert!(manager.drain_out_of_date().is_empty());
+    assert!(manager.evict_lru(1).is_empty());
+    assert!(manager.evict_oldest(1).is_empty());
+
+    manager.update_loaded_blocking();
+    assert!(manager.get(&a) == Some(&101.1_f32));
+}
+
+#[test]
+fn preload_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut manager = ManagedGen::<String, f32, u32>::new(GenLoader::new_with_loader(sender.clone()), 10);
+
+    let mut a = KeyIdx::new("a".to_string());
+    let mut b = KeyIdx::new("b".to_string());
+    manager.load(&mut a);
+    manager.load(&mut b);
+    manager.update_loaded_blocking();
+    manager.increment(&1);
+    manager.reset_counter(b.index.unwrap());
+
+    let keys = manager.export_resident_keys();
+    assert!(keys == vec!["b".to_string(), "a".to_string()]);
+
+    let mut warm = ManagedGen::<String, f32, u32>::new(GenLoader::new_with_loader(sender), 10);
+    assert!(warm.preload_from(keys.clone()) == 2);
+    assert!(warm.preload_from(keys) == 0);
+    warm.update_loaded_blocking();
+
+    assert!(warm.get(&KeyIdx::new("a".to_string())) == Some(&101.1_f32));
+    assert!(warm.get(&KeyIdx::new("b".to_string())) == Some(&101.1_f32));
+}
+
+#[test]
+fn evict_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    let mut manager = ManagedGen::<String, f32, u32>::new(GenLoader::new_with_loader(sender), 10);
+
+    let mut a = KeyIdx::new("a".to_string());
+    let mut b = KeyIdx::new("b".to_string());
+    manager.load(&mut a);
+    manager.load(&mut b);
+
+    // b is evicted while its load is still pending
+    assert!(manager.evict(&KeyIdx::new("b".to_string())).is_none());
+    assert!(manager.get_status(&b).is_none());
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    manager.update_loaded_blocking();
+
+    assert!(manager.evict(&a) == Some(101.1_f32));
+    assert!(manager.get(&a).is_none());
+    assert!(manager.evict(&a).is_none());
+    assert!(manager.counters().count() == 0);
+}
+
+#[test]
+fn manager_iter_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    let mut manager = ManagedGen::<String, f32, u32>::new(GenLoader::new_with_loader(sender), 10);
+
+    let mut a = KeyIdx::new("a".to_string());
+    manager.load(&mut a);
+
+    assert!(manager.values().count() == 0);
+    assert!(manager.statuses().map(|(_, _, status)| status).collect::<Vec<_>>() == vec![LoadStatus::Loading]);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    manager.update_loaded_blocking();
+
+    assert!(manager.values().collect::<Vec<_>>() == vec![&101.1_f32]);
+    assert!(manager.statuses().map(|(_, _, status)| status).collect::<Vec<_>>() == vec![LoadStatus::Loaded]);
+
+    let (key, idx, value) = manager.iter().next().unwrap();
+    assert!(key == "a" && Some(*idx) == a.index && *value == 101.1_f32);
+    assert!(manager.storage().values().count() == 1);
+}
+
+#[test]
+fn managed_novec_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut manager = ManagedNoVec::<String, i32, u32>::new_with_loader(sender, 2).with_max_entries(1);
+
+    let mut a = KeyIdx::new("a".to_string());
+    manager.load(&mut a);
+    manager.update_loaded_blocking();
+    manager.increment(&1);
+
+    assert!(a.index == Some(0));
+    assert!(manager.get(&a) == Some(&-21_i32));
+
+    let mut b = KeyIdx::new("b".to_string());
+    manager.load(&mut b);
+    manager.update_loaded_blocking();
+
+    // NoVec hands the freed slot straight to the next load so indices aren't generational
+    assert!(b.index == Some(0));
+    assert!(manager.get(&KeyIdx::new("a".to_string())).is_none());
+    assert!(manager.get(&b) == Some(&-21_i32));
+
+    manager.increment(&1);
+    manager.increment(&1);
+    manager.remove_out_of_date();
+
+    assert!(manager.get(&b).is_none());
+}
+
+#[test]
+fn frequency_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut manager =
+        ManagedGen::<String, f32, Frequency>::new(GenLoader::new_with_loader(sender), Frequency::threshold(0.3));
+
+    let mut a = KeyIdx::new("a".to_string());
+    let mut b = KeyIdx::new("b".to_string());
+    manager.load(&mut a);
+    manager.load(&mut b);
+    manager.update_loaded_blocking();
+
+    // a is used in a burst and then left alone while b is never used again
+    manager.access(&mut a);
+    manager.access(&mut a);
+
+    let decay = Frequency::decay(0.5);
+    manager.increment(&decay);
+    manager.increment(&decay);
+    manager.remove_out_of_date();
+
+    assert!(manager.get(&a) == Some(&101.1_f32));
+    assert!(manager.get(&b).is_none());
+
+    manager.increment(&decay);
+    manager.increment(&decay);
+    manager.remove_out_of_date();
+
+    assert!(manager.get(&a).is_none());
+}
+
+#[test]
+fn background_eviction_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut manager = ManagedGen::<String, f32, u32>::new(GenLoader::new_with_loader(sender), 3);
+    manager.spawn_background_eviction(Duration::from_millis(1), 1);
+
+    let mut a = KeyIdx::new("a".to_string());
+    manager.load(&mut a);
+    manager.update_loaded_blocking();
+
+    assert!(manager.get(&a) == Some(&101.1_f32));
+
+    thread::sleep(Duration::from_millis(50));
+    let drained = manager.drain_out_of_date();
+
+    assert!(drained.len() == 1);
+    assert!(manager.get(&a).is_none());
+
+    manager.stop_background_eviction();
+}
+
+#[test]
+fn lease_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut manager = ManagedGen::<String, f32, u32>::new(GenLoader::new_with_loader(sender), 2);
+
+    let mut a = KeyIdx::new("a".to_string());
+    let mut b = KeyIdx::new("b".to_string());
+    manager.load(&mut a);
+    manager.load(&mut b);
+    manager.update_loaded_blocking();
+
+    let lease = manager.lease(&mut a).unwrap();
+    assert!(manager.lease(&mut KeyIdx::new("c".to_string())).is_none());
+
+    manager.increment(&1);
+    manager.increment(&1);
+    manager.remove_out_of_date();
+
+    assert!(manager.get(&a) == Some(&101.1_f32));
+    assert!(manager.get(&b).is_none());
+    assert!(manager.evict_lru(1).is_empty());
+
+    drop(lease);
+    assert!(!manager.is_leased(&a.index.unwrap()));
+
+    manager.increment(&1);
+    manager.increment(&1);
+    manager.remove_out_of_date();
+
+    assert!(manager.get(&a).is_none());
+}
+
+#[test]
+fn one_or_many_iter_test() {
+    let mut values: OneOrMany<u32> = std::iter::once(1).collect();
+    assert!(values.iter().copied().collect::<Vec<_>>() == vec![1]);
+
+    values.extend(vec![2, 3]);
+
+    for value in &mut values {
+        *value *= 10;
+    }
+
+    assert!((&values).into_iter().copied().collect::<Vec<_>>() == vec![10, 20, 30]);
+    assert!(values.into_iter().collect::<Vec<_>>() == vec![10, 20, 30]);
+
+    let empty: OneOrMany<u32> = std::iter::empty().collect();
+    assert!(empty.into_iter().next().is_none());
+}
+
+#[test]
+fn one_or_many_slice_test() {
+    let mut values = OneOrMany::None;
+    assert!(values.is_empty());
+    assert!(values.as_slice().is_empty());
+
+    values.push(1);
+    assert!(values.len() == 1);
+    assert!(values.as_slice() == [1]);
+    assert!(values.get(1).is_none());
+
+    values.push(2);
+    *values.get_mut(0).unwrap() = 5;
+    assert!(values.len() == 2);
+    assert!(values.get(0) == Some(&5));
+    assert!(values.as_slice() == [5, 2]);
+}
+
+#[test]
+fn one_or_many_remove_test() {
+    let mut values: OneOrMany<u32> = vec![1, 2, 3, 4].into_iter().collect();
+
+    assert!(values.remove(1) == Some(2));
+    assert!(values.remove(5).is_none());
+    assert!(values.pop() == Some(4));
+
+    values.retain(|value| *value != 1);
+    assert!(matches!(values, OneOrMany::One(3)));
+
+    assert!(values.remove(1).is_none());
+    assert!(values.pop() == Some(3));
+    assert!(matches!(values, OneOrMany::None));
+    assert!(values.pop().is_none());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn one_or_many_serde_test() {
+    let one: OneOrMany<String> = serde_json::from_str("\"a\"").unwrap();
+    let many: OneOrMany<String> = serde_json::from_str("[\"a\", \"b\"]").unwrap();
+    let single_array: OneOrMany<String> = serde_json::from_str("[\"a\"]").unwrap();
+
+    assert!(matches!(&one, OneOrMany::One(value) if value == "a"));
+    assert!(many.as_slice() == ["a", "b"]);
+    assert!(matches!(&single_array, OneOrMany::One(value) if value == "a"));
+
+    assert!(serde_json::to_string(&one).unwrap() == "\"a\"");
+    assert!(serde_json::to_string(&many).unwrap() == "[\"a\",\"b\"]");
+    assert!(serde_json::to_string(&OneOrMany::<String>::None).unwrap() == "[]");
+}
+
+#[test]
+fn one_or_many_from_test() {
+    assert!(matches!(OneOrMany::<u32>::default(), OneOrMany::None));
+    assert!(matches!(OneOrMany::from(1), OneOrMany::One(1)));
+    assert!(matches!(OneOrMany::<u32>::from(None), OneOrMany::None));
+    assert!(matches!(OneOrMany::from(Some(2)), OneOrMany::One(2)));
+    assert!(matches!(OneOrMany::from(vec![3]), OneOrMany::One(3)));
+    assert!(matches!(OneOrMany::<u32>::from(vec![]), OneOrMany::None));
+
+    let many = OneOrMany::from(vec![1, 2]);
+    assert!(many.as_slice() == [1, 2]);
+
+    let vec: Vec<u32> = many.into();
+    assert!(vec == vec![1, 2]);
+    assert!(Vec::from(OneOrMany::One(4)) == vec![4]);
+}
+
+#[test]
+fn one_or_many_inline_test() {
+    let counter = Rc::new(());
+
+    let mut values: OneOrMany<Rc<()>> = (0..INLINE_CAPACITY).map(|_| counter.clone()).collect();
+    assert!(matches!(values, OneOrMany::Few(_)));
+    assert!(Rc::strong_count(&counter) == INLINE_CAPACITY + 1);
+
+    values.push(counter.clone());
+    assert!(matches!(values, OneOrMany::Many(_)));
+    assert!(values.len() == INLINE_CAPACITY + 1);
+
+    let mut few: OneOrMany<u32> = vec![1, 2, 3].into_iter().collect();
+    few.retain(|value| *value != 2);
+    assert!(few.as_slice() == [1, 3]);
+    assert!(few.clone().into_iter().collect::<Vec<_>>() == vec![1, 3]);
+
+    let mut partial = vec![counter.clone(), counter.clone(), counter.clone()]
+        .into_iter()
+        .collect::<OneOrMany<_>>()
+        .into_iter();
+    partial.next();
+    drop(partial);
+    drop(values);
+
+    assert!(Rc::strong_count(&counter) == 1);
+}
+
+#[test]
+fn one_or_many_drain_test() {
+    let mut values: OneOrMany<u32> = vec![1, 2, 3].into_iter().collect();
+
+    assert!(values.drain().collect::<Vec<_>>() == vec![1, 2, 3]);
+    assert!(values.is_empty());
+
+    values.push(4);
+    let taken = values.take();
+
+    assert!(matches!(taken, OneOrMany::One(4)));
+    assert!(matches!(values, OneOrMany::None));
+}
+
+#[test]
+fn one_or_many_map_test() {
+    assert!(matches!(OneOrMany::One(2).map(|value| value * 2), OneOrMany::One(4)));
+
+    let few: OneOrMany<u32> = vec![1, 2, 3].into_iter().collect();
+    let mapped = few.map(|value| value.to_string());
+    assert!(matches!(&mapped, OneOrMany::Few(_)));
+    assert!(mapped.as_slice() == ["1", "2", "3"]);
+
+    let many: OneOrMany<u32> = (0..10).collect();
+    let evens = many.filter_map(|value| if value % 2 == 0 { Some(value) } else { None });
+    assert!(matches!(&evens, OneOrMany::Many(_)));
+    assert!(evens.as_slice() == [0, 2, 4, 6, 8]);
+
+    let one = evens.filter_map(|value| if value == 4 { Some(value) } else { None });
+    assert!(matches!(one, OneOrMany::One(4)));
+    assert!(matches!(OneOrMany::One(1).filter_map(|_| None::<u32>), OneOrMany::None));
+}
+
+#[test]
+fn one_way_map_remove_test() {
+    let mut map = OneWayMap::new();
+    let a = map.push("a", 1);
+    let b = map.push("b", 2);
+    let c = map.push("c", 3);
+
+    assert!(map.remove(&"b") == Some(2));
+    assert!(map.remove(&"b").is_none());
+    assert!(map.get(b).is_none());
+    assert!(map.get(a) == Some(&1));
+    assert!(map.get(c) == Some(&3));
+
+    assert!(map.remove_idx(a) == Some(1));
+    assert!(map.remove_idx(a).is_none());
+    assert!(map.get_idx(&"a").is_none());
+    assert!(map.vacant_count() == 2 && map.len() == 1);
+
+    // Removed slots are reused without moving anything else
+    let d = map.push("d", 4);
+    assert!(d == a || d == b);
+    assert!(map.get(c) == Some(&3));
+    assert!(map.vacant_count() == 1);
+}
+
+#[test]
+fn one_way_map_remove_borrowed_test() {
+    let mut map = OneWayMap::new();
+    map.push("binding".to_string(), 1);
+
+    assert!(map.remove("binding") == Some(1));
+    assert!(map.vacant_count() == 1);
+}
+
+#[test]
+fn one_way_map_iter_test() {
+    let mut map = OneWayMap::new();
+    let a = map.push("a", 1);
+    map.push("b", 2);
+    let c = map.push("c", 3);
+    map.remove(&"b");
+
+    for value in map.values_mut() {
+        *value *= 10;
+    }
+
+    let mut entries: Vec<_> = map.iter().map(|(key, idx, value)| (*key, idx, *value)).collect();
+    entries.sort();
+    assert!(entries == vec![("a", a, 10), ("c", c, 30)]);
+
+    let mut keys: Vec<_> = map.keys().copied().collect();
+    keys.sort();
+    assert!(keys == vec!["a", "c"]);
+    assert!(map.values().copied().collect::<Vec<_>>() == vec![10, 30]);
+}
+
+#[test]
+fn one_way_map_access_test() {
+    let mut map = OneWayMap::new();
+    let a = map.push("a".to_string(), 1);
+
+    *map.get_mut(a).unwrap() += 1;
+    *map.get_mut_by_key("a").unwrap() += 1;
+
+    assert!(map.get_by_key("a") == Some(&3));
+    assert!(map.get_idx("a") == Some(a));
+    assert!(map.get_by_key("b").is_none());
+    assert!(map.get_mut(a + 1).is_none());
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn one_way_map_serde_test() {
+    let mut map = OneWayMap::new();
+    map.push("a".to_string(), 1);
+    let b = map.push("b".to_string(), 2);
+    let c = map.push("c".to_string(), 3);
+    let unkeyed = map.push_value(5);
+    map.remove(&"a".to_string());
+
+    let json = serde_json::to_string(&map).unwrap();
+    let mut loaded: OneWayMap<String, i32> = serde_json::from_str(&json).unwrap();
+
+    assert!(loaded.get_idx("b") == Some(b));
+    assert!(loaded.len() == 3);
+    assert!(loaded.get(unkeyed) == Some(&5) && loaded.get_key(unkeyed).is_none());
+    assert!(loaded.get_idx("c") == Some(c));
+    assert!(loaded.get(c) == Some(&3));
+    assert!(loaded.get_by_key("a").is_none());
+    assert!(loaded.push("d".to_string(), 4) == 0);
+
+    let duplicate = r#"{"slots":1,"entries":[["a",0,1],["b",0,2]]}"#;
+    assert!(serde_json::from_str::<OneWayMap<String, i32>>(duplicate).is_err());
+}
+
+#[test]
+fn one_way_map_entry_test() {
+    let mut map = OneWayMap::new();
+    assert!(map.is_empty());
+
+    let a = map.entry("a").or_insert(1);
+    assert!(map.entry("a").or_insert_with(|| panic!("a is already present")) == a);
+    assert!(map.get(a) == Some(&1));
+
+    let b = map.entry("b").and_modify(|value| *value += 1).or_default();
+    map.entry("b").and_modify(|value| *value += 1);
+    assert!(map.get(b) == Some(&1));
+    assert!(map.entry("c").index().is_none());
+
+    if let one_way_map::Entry::Occupied(mut occupied) = map.entry("a") {
+        assert!(occupied.insert(5) == 1);
+        assert!(occupied.index() == a);
+    }
+
+    assert!(map.get(a) == Some(&5));
+    assert!(map.len() == 2);
+    assert!(map.contains_key("b"));
+    assert!(!map.contains_key("c"));
+}
+
+pub struct Position;
+pub struct Velocity;
+
+impl any_storage::Component for Position {
+    type Storage = Vec<(f32, f32)>;
+}
+
+impl any_storage::Component for Velocity {
+    type Storage = Vec<(f32, f32)>;
+}
+
+pub struct Health;
+
+impl any_storage::Component for Health {
+    type Storage = Vec<u32>;
+}
+
+#[test]
+fn dyn_component_storage_test() {
+    let mut storage = DynComponentStorage::new();
+    assert!(storage.register::<Position>(vec![(0.0, 0.0)]).is_none());
+    assert!(storage.get::<Velocity>().is_none());
+
+    storage.register::<Velocity>(vec![(1.0, 2.0)]);
+    storage.get_mut::<Position>().unwrap().push((3.0, 4.0));
+
+    // Sharing a storage type doesn't mix the components up
+    assert!(storage.get::<Position>().unwrap() == &vec![(0.0, 0.0), (3.0, 4.0)]);
+    assert!(storage.get::<Velocity>().unwrap() == &vec![(1.0, 2.0)]);
+    assert!(storage.len() == 2);
+
+    assert!(storage.unregister::<Velocity>() == Some(vec![(1.0, 2.0)]));
+    assert!(!storage.is_registered::<Velocity>());
+}
+
+#[cfg(feature = "derive")]
+#[derive(any_storage::ComponentStorage)]
+struct DerivedStorage<T>
+where
+    T: Clone,
+{
+    #[component(Position)]
+    positions: Vec<(f32, f32)>,
+    velocities: <Velocity as any_storage::Component>::Storage,
+    #[component(skip)]
+    #[allow(dead_code)]
+    extra: T,
+    #[resource]
+    time: Time,
+}
+
+#[test]
+#[cfg(feature = "derive")]
+fn derive_component_storage_test() {
+    use any_storage::{ComponentStorage, ResourceStorage};
+
+    let mut storage =
+        DerivedStorage { positions: vec![], velocities: vec![], extra: 0u8, time: Time(0.0) };
+    storage.get_mut::<Position>().unwrap().push((1.0, 2.0));
+    storage.get_mut::<Velocity>().unwrap().push((3.0, 4.0));
+
+    assert!(storage.positions == vec![(1.0, 2.0)]);
+    assert!(storage.get::<Velocity>().unwrap() == &vec![(3.0, 4.0)]);
+    assert!(storage.get::<Health>().is_none());
+
+    let (positions, velocities) = fetch!(storage, (&mut Position, &mut Velocity)).unwrap();
+    positions.append(velocities);
+    assert!(storage.positions.len() == 2 && storage.velocities.is_empty());
+
+    storage.get_resource_mut::<Time>().unwrap().0 = 1.0;
+    assert!(storage.get_resource::<Time>() == Some(&Time(1.0)));
+    assert!(storage.get_resource::<u8>().is_none());
+}
+
+// Health's storage is a Vec<u32>, the attribute alone doesn't make the field one
+#[cfg(feature = "derive")]
+#[derive(any_storage::ComponentStorage)]
+struct MismatchedStorage {
+    #[component(Health)]
+    healths: Vec<u8>,
+    #[component(Position)]
+    positions: Vec<(f32, f32)>,
+}
+
+#[test]
+#[cfg(feature = "derive")]
+fn derive_mismatched_storage_test() {
+    use any_storage::ComponentStorage;
+
+    let mut storage = MismatchedStorage { healths: vec![1, 2], positions: vec![(1.0, 2.0)] };
+
+    // The downcast turns the mismatched field away instead of reading it as the wrong type
+    assert!(storage.get::<Health>().is_none());
+    assert!(storage.get_mut::<Health>().is_none());
+    assert!(storage.healths == vec![1, 2]);
+    assert!(storage.get::<Position>() == Some(&vec![(1.0, 2.0)]));
+}
+
+#[test]
+fn join_test() {
+    use crate::join::Join;
+
+    let mut positions = GenerationStorage::new();
+    let mut velocities = GenerationStorage::new();
+
+    let a = positions.push(1.0f32);
+    let b = positions.push(2.0);
+    velocities.insert(a, 10.0f32);
+    velocities.insert(positions.nth_available(0), 20.0);
+
+    for (_, (position, velocity)) in join!(&mut positions, &velocities) {
+        *position += *velocity;
+    }
+
+    assert!(positions.get(a) == Some(&11.0));
+    assert!(positions.get(b) == Some(&2.0));
+
+    let joined: Vec<_> = (&positions, &velocities).join().map(|(id, _)| id).collect();
+    assert!(joined == vec![a]);
+
+    // A single storage can be joined on its own
+    assert!(join!(&velocities).count() == 2);
+}
+
+create_storage!(FetchTestStorage { positions: Position, velocities: Velocity });
+
+#[derive(Debug, PartialEq)]
+pub struct Time(f32);
+
+create_storage!(ResourceTestStorage { positions: Position } resources { time: Time, frame: u64 });
+
+#[test]
+fn fetch_test() {
+    let mut storage =
+        FetchTestStorage { positions: vec![(0.0, 0.0)], velocities: vec![(1.0, 2.0)] };
+
+    {
+        let (positions, velocities) = fetch!(storage, (&mut Position, &Velocity)).unwrap();
+        positions[0].0 += velocities[0].0;
+        positions[0].1 += velocities[0].1;
+    }
+
+    assert!(storage.positions == vec![(1.0, 2.0)]);
+    assert!(fetch!(storage, (&Position, &Position)).is_some());
+
+    let conflict = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        fetch!(storage, (&mut Position, &Position)).map(|_| ())
+    }));
+    assert!(conflict.is_err());
+    assert!(fetch!(storage, (&Health,)).is_none());
+
+    // Both components use the same storage type but still get their own storage
+    assert!(storage.get::<Velocity>().unwrap() == &vec![(1.0, 2.0)]);
+    storage.get_mut::<Position>().unwrap().clear();
+    assert!(storage.velocities.len() == 1);
+    assert!(storage.get::<Health>().is_none());
+}
+
+pub struct Tagged<T>(T);
+
+impl<T: 'static> any_storage::Component for Tagged<T> {
+    type Storage = Vec<T>;
+}
+
+create_storage!(GenericTestStorage<T: Clone, U> where U: std::fmt::Debug + Clone {
+    items: Tagged<T>,
+    others: Tagged<U>,
+    positions: Position,
+});
+
+#[test]
+fn generic_create_storage_test() {
+    let mut storage: GenericTestStorage<u32, String> =
+        GenericTestStorage { items: vec![1], others: vec![], positions: vec![] };
+
+    storage.get_mut::<Tagged<String>>().unwrap().push("a".to_string());
+
+    assert!(storage.get::<Tagged<u32>>().unwrap() == &vec![1]);
+    assert!(storage.others == vec!["a".to_string()]);
+    assert!(storage.get::<Tagged<u8>>().is_none());
+
+    let (items, positions) = fetch!(storage, (&mut Tagged<u32>, &Position)).unwrap();
+    items.push(positions.len() as u32);
+    assert!(storage.items == vec![1, 0]);
+}
+
+#[test]
+fn resource_storage_test() {
+    let mut storage = ResourceTestStorage { positions: vec![], time: Time(0.5), frame: 0 };
+
+    storage.get_resource_mut::<Time>().unwrap().0 += 1.0;
+    *storage.get_resource_mut::<u64>().unwrap() += 1;
+
+    assert!(storage.get_resource::<Time>() == Some(&Time(1.5)));
+    assert!(storage.frame == 1);
+    assert!(storage.get_resource::<u32>().is_none());
+    assert!(storage.get::<Position>().unwrap().is_empty());
+
+    // Storages without resources still answer
+    let fetch_storage = FetchTestStorage { positions: vec![], velocities: vec![] };
+    assert!(fetch_storage.get_resource::<Time>().is_none());
+
+    let mut dynamic = DynComponentStorage::new();
+    assert!(dynamic.insert_resource(Time(2.0)).is_none());
+    dynamic.get_resource_mut::<Time>().unwrap().0 += 1.0;
+
+    assert!(dynamic.get_resource::<Time>() == Some(&Time(3.0)));
+    assert!(dynamic.remove_resource::<Time>() == Some(Time(3.0)));
+    assert!(dynamic.get_resource::<Time>().is_none());
+}
+
+#[test]
+fn visit_component_storage_test() {
+    let mut storage =
+        FetchTestStorage { positions: vec![(0.0, 0.0)], velocities: vec![(1.0, 2.0), (3.0, 4.0)] };
+
+    let components = storage.components();
+    assert!(components == vec![ComponentInfo::of::<Position>(), ComponentInfo::of::<Velocity>()]);
+    assert!(components[0].name.ends_with("Position"));
+
+    let mut total = 0;
+    storage.visit(&mut |_, storage| {
+        total += storage.downcast_ref::<Vec<(f32, f32)>>().map(|storage| storage.len()).unwrap_or(0)
+    });
+    assert!(total == 3);
+
+    // Clearing everything without naming each storage
+    storage.visit_mut(&mut |_, storage| {
+        if let Some(storage) = storage.downcast_mut::<Vec<(f32, f32)>>() {
+            storage.clear();
+        }
+    });
+    assert!(storage.positions.is_empty() && storage.velocities.is_empty());
+
+    let mut dynamic = DynComponentStorage::new();
+    dynamic.register::<Health>(vec![10]);
+    assert!(dynamic.components() == vec![ComponentInfo::of::<Health>()]);
+    assert!(dynamic.contains::<Health>() && !dynamic.contains::<Position>());
+}
+
+#[test]
+fn generation_snapshot_test() {
+    let mut storage = GenerationStorage::new();
+    let a = storage.push(1u32);
+    let b = storage.push(2);
+    storage.push(3);
+    storage.remove_id(b);
+    let b2 = storage.push(4);
+    storage.remove_id(a);
+
+    use std::io::{Read, Write};
+
+    let read_item = |reader: &mut &[u8]| {
+        let mut bytes = [0; 4];
+        reader.read_exact(&mut bytes).map(|_| u32::from_le_bytes(bytes))
+    };
+
+    let mut bytes = vec![];
+    storage.write_snapshot(&mut bytes, |writer, item| writer.write_all(&item.to_le_bytes())).unwrap();
+    let mut restored = GenerationStorage::read_snapshot(&bytes[..], read_item).unwrap();
+
+    assert!(restored.get(b2) == Some(&4));
+    assert!(restored.get(b).is_none());
+    assert!(restored.get(a).is_none());
+    assert!(restored.values().copied().collect::<Vec<_>>() == vec![4, 3]);
+
+    // The freed slot is reused with the same generation the original would have given it
+    assert!(restored.push(5) == storage.push(5));
+
+    assert!(GenerationStorage::read_snapshot(&bytes[..10], read_item).is_err());
+    assert!(GenerationStorage::read_snapshot(&b"nope"[..], read_item).is_err());
+}
+
+#[test]
+fn novec_free_list_test() {
+    let mut novec = NoVec::new();
+    for i in 0..6 {
+        novec.push(i);
+    }
+
+    assert!(novec.remove(1) == Some(1));
+    assert!(novec.remove(4) == Some(4));
+    assert!(novec.remove(3) == Some(3));
+    assert!(novec.remove(3).is_none());
+
+    // Most recently emptied entries are reused first
+    assert!(novec.next_id() == 3);
+    assert!(novec.push(10) == 3);
+    assert!(novec.push(11) == 4);
+
+    // Filling a hole in the middle of the free list keeps the rest of it intact
+    novec.remove(0);
+    novec.remove(5);
+    assert!(novec.insert_at(0, 12).is_none());
+    assert!(novec.push(13) == 5);
+    assert!(novec.push(14) == 1);
+    assert!(novec.push(15) == 6);
+
+    novec.fill_to(9);
+    assert!(novec.push(16) == 7);
+    assert!(novec.values().copied().collect::<Vec<_>>() == vec![12, 14, 2, 10, 11, 13, 15, 16]);
+}
+
+#[test]
+fn novec_insert_at_test() {
+    let mut novec = NoVec::new();
+    let mut model: Vec<Option<u32>> = vec![];
+
+    // Pseudo random mix of pushes, removals and inserts over a fragmented layout
+    let mut seed = 7u32;
+    for value in 0..500 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        let index = (seed >> 16) as usize % 40;
+
+        match (seed >> 8) % 3 {
+            0 => {
+                let index = novec.push(value);
+                model.resize(model.len().max(index + 1), None);
+                assert!(model[index].is_none());
+                model[index] = Some(value);
+            }
+            1 => {
+                let expected = model.get_mut(index).and_then(|slot| slot.take());
+                assert!(novec.remove(index) == expected);
+            }
+            _ => {
+                let outcome = novec.insert_at_outcome(index, value);
+                let expected = match model.get(index) {
+                    Some(Some(old)) => InsertOutcome::Replaced(*old),
+                    Some(None) => InsertOutcome::Filled,
+                    None => InsertOutcome::Extended,
+                };
+                assert!(outcome == expected);
+
+                model.resize(model.len().max(index + 1), None);
+                model[index] = Some(value);
+            }
+        }
+    }
+
+    // Every hole gets handed out exactly once before the storage grows again
+    let holes = model.iter().filter(|slot| slot.is_none()).count();
+    let mut filled: Vec<_> = (0..holes).map(|_| novec.push(0)).collect();
+    filled.sort();
+
+    let expected: Vec<_> = (0..model.len()).filter(|idx| model[*idx].is_none()).collect();
+    assert!(filled == expected);
+    assert!(novec.push(0) == model.len());
+
+    assert!(novec.insert_at(1, 5).is_some());
+    assert!(novec.get(1) == Some(&5));
+}
+
+#[test]
+fn generation_insert_test() {
+    let mut storage = GenerationStorage::new();
+    let a = storage.push(1);
+
+    assert!(storage.insert(a, 2) == Some(1));
+    assert!(storage.get(a) == Some(&2));
+
+    storage.remove_id(a);
+    let b = storage.push(3);
+    assert!(b.index == a.index && b.generation > a.generation);
+
+    // 'a' is stale so it can't clobber 'b'
+    assert!(storage.try_insert(a, 4) == Err(4));
+    assert!(storage.insert(a, 4) == Some(4));
+    assert!(storage.get(b) == Some(&3));
+
+    assert!(storage.insert_force(a, 5) == Some(3));
+    assert!(storage.get(a) == Some(&5));
+    assert!(storage.get(b).is_none());
+
+    // Inserting past the end claims the slot without handing it out again
+    let far = StorageId { index: 4, generation: NonZeroU64::new(3).unwrap() };
+    assert!(storage.insert(far, 6).is_none());
+    assert!(storage.push(7).index != far.index);
+    assert!(UnorderedStorage::insert(&mut storage, far, 8) == Some(6));
+}
+
+#[test]
+fn mapped_insert_stable_index_test() {
+    let mut storage = MappedGeneration::<String, i32>::new();
+    let mut ki = KeyIdx::new("a");
+
+    assert!(storage.insert_replace_idx(&mut ki, 1) == Ok(None));
+    let (index, removed) = storage.insert("a".to_string(), 2).unwrap();
+
+    assert!(Some(index) == ki.index && removed == Some(1));
+    assert!(storage.get(&ki) == Some(&2));
+    assert!(storage.get_key(&index) == Some(&"a".to_string()));
+
+    let (index, value, removed) = storage.insert_get("a".to_string(), 3).unwrap();
+    assert!(Some(*index) == ki.index && *value == 3 && removed == Some(2));
+    assert!(storage.len() == 1);
+}
+
+#[test]
+fn get_or_insert_with_test() {
+    // Nothing answers the loader so only locally computed values ever show up
+    let (sender, _receiver) = cbc::unbounded();
+    let mut floats: GenLoader<String, f32> = GenLoader::new_with_loader(sender);
+
+    let mut a = KeyIdx::new("a".to_string());
+    assert!(*floats.get_or_insert_with(&mut a, || 1.0) == 1.0);
+    assert!(*floats.get_or_insert_with(&mut a, || 2.0) == 1.0);
+    assert!(floats.get(&a) == Some(&1.0));
+
+    // A pending load is replaced and no longer waited on
+    let mut b = KeyIdx::new("b".to_string());
+    floats.load(&mut b);
+    let index = b.index;
+    assert!(*floats.get_or_insert_with(&mut b, || 3.0) == 3.0);
+    assert!(b.index == index);
+
+    floats.update_loaded_blocking();
+    assert!(floats.get(&b) == Some(&3.0));
+}
+
+#[test]
+fn load_blocking_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut floats: GenLoader<String, f32> = GenLoader::new_with_loader(sender.clone());
+    let mut a = KeyIdx::new("a".to_string());
+
+    assert!(*floats.load_blocking(&mut a).unwrap() == 101.1);
+    assert!(floats.get_status(&a) == Some(LoadStatus::Loaded));
+    assert!(*floats.load_blocking(&mut a).unwrap() == 101.1);
+
+    // The test loader answers unknown types with a bool which fails to convert
+    let mut strings: GenLoader<String, String> = GenLoader::new_with_loader(sender);
+    let mut b = KeyIdx::new("b".to_string());
+
+    assert!(matches!(strings.load_blocking(&mut b), Err(PromiseError::LoadError(_))));
+    assert!(strings.get_status(&b).is_none());
+}
+
+#[test]
+fn file_mapper_options_test() {
+    use file_mapper::{FileMapper, MappingOptions};
+
+    let dir = std::env::temp_dir().join(format!("novec_mapper_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.png"), "a").unwrap();
+    std::fs::write(dir.join("b.png"), "b").unwrap();
+
+    let manifest = format!("{}\na => a.png | srgb, mips=4\nb => b.png\n", dir.display());
+    std::fs::write(dir.join("mapping"), manifest).unwrap();
+
+    let (sender, receiver) = cbc::unbounded();
+    let mapper = FileMapper::<String>::from_file(receiver, None, dir.join("mapping")).unwrap();
+
+    let a_options = mapper.options("a").unwrap();
+    assert!(a_options.has("srgb") && a_options.get("srgb").is_none());
+    assert!(a_options.parse::<u32>("mips") == Some(4));
+    assert!(mapper.options("b") == Some(&MappingOptions::new()));
+
+    let mut mips: GenLoader<String, u32> = GenLoader::new_with_loader(sender);
+    let mut a = KeyIdx::new("a".to_string());
+    let mut b = KeyIdx::new("b".to_string());
+    mips.load(&mut a);
+    mips.load(&mut b);
+
+    mapper
+        .receive_non_blocking(
+            |object| GenericResult::new(object.options.parse::<u32>("mips").unwrap_or(1)),
+            |_, _| panic!("Every key is mapped"),
+        )
+        .unwrap();
+    mips.update_loaded_blocking();
+
+    assert!(mips.get(&a) == Some(&4));
+    assert!(mips.get(&b) == Some(&1));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn load_options_test() {
+    #[derive(Debug)]
+    struct Scale(f32);
+
+    let (sender, receiver): (GenericSender<String>, _) = cbc::unbounded();
+
+    thread::spawn(move || {
+        for (_, into) in receiver.iter() {
+            let scale = into.options.get::<Scale>().map(|scale| scale.0).unwrap_or(1.0);
+            into.send(GenericResult::new(10.0_f32 * scale)).unwrap();
+        }
+    });
+
+    let mut floats: GenLoader<String, f32> = GenLoader::new_with_loader(sender);
+    let mut a = KeyIdx::new("a".to_string());
+    let mut b = KeyIdx::new("b".to_string());
+
+    floats.load_with(&mut a, LoadOptions::new(Scale(0.5)));
+    floats.load(&mut b);
+
+    // Already loading so the new options are dropped
+    assert!(floats.load_with(&mut b, LoadOptions::new(Scale(2.0))) == LoadStatus::Loading);
+
+    floats.update_loaded_blocking();
+
+    assert!(floats.get(&a) == Some(&5.0));
+    assert!(floats.get(&b) == Some(&10.0));
+    assert!(LoadOptions::new(Scale(1.0)).get::<u32>().is_none());
+    assert!(LoadOptions::default().is_none());
+}
+
+#[test]
+fn duplicate_policy_test() {
+    let mut storage: MappedGeneration<&str, u32> = MappedGeneration::new();
+    let (a, _) = storage.insert("a", 1).unwrap();
+
+    let (index, removed) = storage.insert("a", 2).unwrap();
+    assert!(index == a && removed == Some(1));
+
+    // Turning a value away is told apart from replacing one
+    storage.set_policy(DuplicatePolicy::KeepExisting);
+    assert!(storage.insert("a", 3) == Err(DuplicateKey { index: a, value: 3 }));
+    assert!(storage.get_by_key("a") == Some(&2));
+    assert!(storage.insert_get("a", 3).err() == Some(DuplicateKey { index: a, value: 3 }));
+
+    let mut ki = KeyIdx::new("a");
+    assert!(storage.insert_replace_idx(&mut ki, 3).is_err() && ki.index == Some(a));
+
+    storage.set_policy(DuplicatePolicy::Error);
+    assert!(storage.insert("a", 4) == Err(DuplicateKey { index: a, value: 4 }));
+    assert!(storage.insert_get("a", 4).err() == Some(DuplicateKey { index: a, value: 4 }));
+    assert!(storage.get_by_key("a") == Some(&2));
+    assert!(storage.insert("b", 5).is_ok());
+
+    storage.set_policy(DuplicatePolicy::ReplaceNewIndex);
+    let (index, removed) = storage.insert("a", 6).unwrap();
+    assert!(index != a && removed == Some(2));
+    assert!(storage.get_by_index(&a).is_none());
+    assert!(storage.get_by_key("a") == Some(&6));
+    assert!(storage.get_key(&index) == Some(&"a"));
+    assert!(storage.len() == 2);
+}
+
+#[test]
+fn load_duplicate_policy_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut floats: GenLoader<String, f32> = GenLoader::new_with_loader(sender.clone());
+    let mut a = KeyIdx::new("a".to_string());
+
+    floats.get_or_insert_with(&mut a, || 1.0);
+    let first = a.index.unwrap();
+
+    // Kept by default
+    assert!(floats.load(&mut a) == LoadStatus::Loaded);
+    assert!(floats.get(&a) == Some(&1.0));
+
+    floats.set_duplicate_policy(DuplicatePolicy::Error);
+    let error = floats.try_load_with(&mut a, LoadOptions::none()).unwrap_err();
+    assert!(error.index == first);
+    assert!(floats.load(&mut a) == LoadStatus::Loaded);
+
+    floats.set_duplicate_policy(DuplicatePolicy::ReplaceKeepIndex);
+    assert!(floats.load(&mut a) == LoadStatus::Loading);
+    assert!(a.index == Some(first));
+    floats.update_loaded_blocking();
+    assert!(floats.get(&a) == Some(&101.1));
+
+    floats.set_duplicate_policy(DuplicatePolicy::ReplaceNewIndex);
+    assert!(floats.load(&mut a) == LoadStatus::Loading);
+    assert!(a.index != Some(first));
+    assert!(floats.get_by_index(&first).is_none());
+    floats.update_loaded_blocking();
+    assert!(floats.get(&a) == Some(&101.1));
+    assert!(floats.storage.len() == 1);
+}
+
+#[test]
+fn reported_size_test() {
+    let (sender, receiver): (GenericSender<String>, _) = cbc::unbounded();
+
+    // Keys are the size of the decoded value, "unsized" isn't reported
+    thread::spawn(move || {
+        for (key, into) in receiver.iter() {
+            let result = match key.parse::<usize>() {
+                Ok(bytes) => GenericResult::new_sized(bytes as f32, bytes),
+                Err(_) => GenericResult::new(0.0_f32),
+            };
+
+            into.send(result).unwrap();
+        }
+    });
+
+    let mut floats: GenLoader<String, f32> = GenLoader::new_with_loader(sender.clone());
+    let mut a = KeyIdx::new("6".to_string());
+    let mut b = KeyIdx::new("10".to_string());
+    let mut c = KeyIdx::new("unsized".to_string());
+
+    floats.load(&mut a);
+    floats.load(&mut b);
+    floats.load_blocking(&mut c).unwrap();
+    floats.update_loaded_blocking();
+
+    assert!(floats.resident_bytes() == 16);
+    assert!(floats.reported_size(&b) == Some(10));
+    assert!(floats.reported_size(&c).is_none());
+
+    floats.remove(&a);
+    assert!(floats.resident_bytes() == 10);
+
+    // Reported sizes take priority over size_of when budgeting
+    let mut manager = ManagedGen::<String, f32, u32>::new(GenLoader::new_with_loader(sender), 10)
+        .with_byte_budget(12, |_| 1)
+        .with_evicted_limit(4);
+
+    for key in ["4", "unsized", "8", "2"] {
+        let mut ki = KeyIdx::new(key.to_string());
+        manager.load(&mut ki);
+        manager.update_loaded_blocking();
+        manager.increment(&1);
+    }
+
+    assert!(manager.resident_bytes() == 11);
+    assert!(manager.storage().resident_bytes() == 10);
+    assert!(manager.get(&KeyIdx::new("4".to_string())).is_none());
+    assert!(manager.drain_evicted().count() == 1);
+}
+
+#[test]
+fn routing_loader_test() {
+    use routing::RoutingLoader;
+
+    // Each backend answers with the key it was given and its own name
+    fn spawn_backend(name: &'static str) -> GenericSender<String> {
+        let (sender, receiver): (GenericSender<String>, _) = cbc::unbounded();
+
+        thread::spawn(move || {
+            for (key, into) in receiver.iter() {
+                into.send(GenericResult::new(format!("{}:{}", name, key))).unwrap();
+            }
+        });
+
+        sender
+    }
+
+    let loader = RoutingLoader::new()
+        .with_route("tex", spawn_backend("tex"))
+        .with_route("snd", spawn_backend("snd"));
+
+    assert!(loader.has_route("tex") && !loader.has_route("http"));
+
+    let mut strings: GenSystem<String, RoutingLoader<GenericResult, TypeId>, String> =
+        GenSystem::new_with_loader(loader);
+
+    let mut grass = KeyIdx::new("tex://grass".to_string());
+    let mut boom = KeyIdx::new("snd://boom".to_string());
+    let mut web = KeyIdx::new("http://example.com/a".to_string());
+
+    assert!(strings.load_blocking(&mut grass).unwrap() == "tex:grass");
+    assert!(strings.load_blocking(&mut boom).unwrap() == "snd:boom");
+    assert!(matches!(strings.load_blocking(&mut web), Err(PromiseError::Disconnected)));
+
+    let mut strings: GenSystem<String, RoutingLoader<GenericResult, TypeId>, String> =
+        GenSystem::new_with_loader(RoutingLoader::new().with_fallback(spawn_backend("any")));
+
+    assert!(strings.load_blocking(&mut web).unwrap() == "any:http://example.com/a");
+}
+
+#[test]
+fn manifest_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut floats: GenLoader<String, f32> = GenLoader::new_with_loader(sender.clone());
+    let mut uints: GenLoader<String, u32> = GenLoader::new_with_loader(sender.clone());
+
+    floats.load(&mut KeyIdx::new("a".to_string()));
+    floats.load(&mut KeyIdx::new("b".to_string()));
+    uints.load(&mut KeyIdx::new("c".to_string()));
+    floats.update_loaded_blocking();
+    uints.update_loaded_blocking();
+
+    let mut manifest = floats.export_manifest();
+    manifest.extend(uints.export_manifest());
+    manifest.sort_by(|a, b| a.key.cmp(&b.key));
+
+    assert!(manifest.len() == 3);
+    assert!(manifest[0].is::<f32>() && manifest[2].is::<u32>());
+
+    // A fresh run only picks up the keys saved for its own type
+    let mut floats: GenLoader<String, f32> = GenLoader::new_with_loader(sender.clone());
+    floats.load(&mut KeyIdx::new("a".to_string()));
+
+    assert!(floats.preload_manifest(manifest.clone()) == 1);
+    floats.update_loaded_blocking();
+    assert!(floats.get(&KeyIdx::new("b".to_string())) == Some(&101.1));
+    assert!(floats.get(&KeyIdx::new("c".to_string())).is_none());
+
+    let mut manager = ManagedGen::<String, u32, u32>::new(GenLoader::new_with_loader(sender), 10);
+    assert!(manager.preload_manifest(manifest) == 1);
+    manager.update_loaded_blocking();
+    assert!(manager.export_manifest() == vec![ManifestEntry::new::<u32>("c".to_string())]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn manifest_serde_test() {
+    let manifest = vec![ManifestEntry::new::<f32>("a".to_string())];
+    let json = serde_json::to_string(&manifest).unwrap();
+    let read: Vec<ManifestEntry<String>> = serde_json::from_str(&json).unwrap();
+
+    assert!(read == manifest);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn mapped_par_iter_test() {
+    use rayon::prelude::*;
+
+    let mut generation: MappedGeneration<String, u32> = MappedGeneration::new();
+    let mut novec: MappedNoVec<String, u32> = MappedNoVec::new();
+
+    for i in 0..100 {
+        generation.insert(i.to_string(), i).unwrap();
+        novec.insert(i.to_string(), i).unwrap();
+    }
+
+    generation.remove(&KeyIdx::new("50".to_string()));
+
+    generation.par_values_mut().for_each(|value| *value *= 2);
+    novec.par_iter_mut().for_each(|(key, _, value)| *value += key.parse::<u32>().unwrap());
+
+    assert!(generation.par_values().sum::<u32>() == (0..100).sum::<u32>() * 2 - 100);
+    assert!(novec.par_values().sum::<u32>() == (0..100).sum::<u32>() * 2);
+    assert!(generation.par_iter().all(|(key, idx, value)| {
+        generation.get_key(&idx) == Some(key) && *value == key.parse::<u32>().unwrap() * 2
+    }));
+}
+
+#[test]
+fn generation_get_unchecked_test() {
+    let mut storage = GenerationStorage::new();
+    let a = storage.push(1);
+    let b = storage.push(2);
+
+    unsafe {
+        *storage.get_unchecked_mut(b) += 10;
+        assert!(*storage.get_unchecked(a) == 1);
+        assert!(*storage.get_unchecked(b) == 12);
+    }
+
+    storage.remove_id(a);
+    let c = storage.push(3);
+
+    // The slot is reused, only the checked accessors notice the stale id
+    assert!(c.index == a.index);
+    assert!(storage.get(a).is_none());
+    assert!(storage.get_any_generation(a.index) == Some(&3));
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic]
+fn generation_get_unchecked_stale_test() {
+    let mut storage = GenerationStorage::new();
+    let a = storage.push(1);
+    storage.remove_id(a);
+    storage.push(2);
+
+    unsafe {
+        storage.get_unchecked(a);
+    }
+}
+
+#[test]
+fn novec_get_unchecked_test() {
+    let mut novec = NoVec::new();
+    let a = novec.push(1.0_f32);
+    let b = novec.push(2.0);
+
+    unsafe {
+        *novec.get_unchecked_mut(a) *= 4.0;
+        assert!(*novec.get_unchecked(a) == 4.0);
+        assert!(*novec.get_unchecked(b) == 2.0);
+    }
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic]
+fn novec_get_unchecked_empty_test() {
+    let mut novec = NoVec::new();
+    let a = novec.push(1);
+    novec.remove(a);
+
+    unsafe {
+        novec.get_unchecked(a);
+    }
+}
+
+#[test]
+fn entry_ref_test() {
+    let mut storage: MappedNoVec<String, u32> = MappedNoVec::new();
+
+    let (a, value) = storage.entry_ref("a").or_insert(1);
+    *value += 1;
+    let a = *a;
+
+    assert!(storage.entry_ref("a").key() == "a");
+
+    let (index, value) = storage.entry_ref("a").and_modify(|value| *value *= 10).or_insert(0);
+    assert!(*index == a && *value == 20);
+
+    let (_, value) = storage.entry_ref("b").or_default();
+    assert!(*value == 0);
+
+    assert!(matches!(storage.entry_ref("b"), EntryRef::Occupied(_)));
+    assert!(matches!(storage.entry_ref("c"), EntryRef::Vacant(_)));
+    assert!(storage.len() == 2);
+    assert!(storage.get_by_key("a") == Some(&20));
+}
+
+#[test]
+fn entry_test() {
+    let mut storage: MappedNoVec<String, u32> = MappedNoVec::new();
+
+    let (a, value) = storage.entry("a".to_string()).or_insert(1);
+    *value += 1;
+    let a = *a;
+
+    match storage.entry("a".to_string()) {
+        map::Entry::Occupied(occupied) => {
+            assert!(occupied.key() == "a" && *occupied.index() == a && *occupied.get() == 2);
+        }
+        map::Entry::Vacant(_) => panic!("'a' should be occupied"),
+    }
+
+    // The default is only made when the entry is vacant
+    let (index, value) = storage
+        .entry("a".to_string())
+        .and_modify(|value| *value *= 10)
+        .or_insert_with(|| panic!("'a' is already stored"));
+    assert!(*index == a && *value == 20);
+
+    let (b, value) = storage.entry("b".to_string()).or_default();
+    assert!(*value == 0 && *b != a);
+
+    assert!(matches!(storage.entry("c".to_string()), map::Entry::Vacant(_)));
+    assert!(storage.len() == 2);
+    assert!(storage.get_key(&a).map(String::as_str) == Some("a"));
+}
+
+#[test]
+fn get_full_test() {
+    let mut storage: MappedGeneration<String, u32> = MappedGeneration::new();
+    let (a, _) = storage.insert("a".to_string(), 1).unwrap();
+
+    assert!(storage.get_key_value("a") == Some((&"a".to_string(), &1)));
+    assert!(storage.get_full("a") == Some((&"a".to_string(), &a, &1)));
+    assert!(storage.get_full("b").is_none());
+
+    let (_, index, value) = storage.get_full_mut("a").unwrap();
+    *value = 5;
+    assert!(*index == a);
+    assert!(storage.get_by_index(&a) == Some(&5));
+}
+
+#[test]
+fn swap_relocate_test() {
+    let mut storage: MappedNoVec<&str, u32> = MappedNoVec::new();
+    let (a, _) = storage.insert("a", 1).unwrap();
+    let (b, _) = storage.insert("b", 2).unwrap();
+    let (c, _) = storage.insert("c", 3).unwrap();
+
+    assert!(storage.swap_indices(&a, &c));
+    assert!(storage.get_index("a") == Some(&c) && storage.get_index("c") == Some(&a));
+    assert!(storage.get_key(&a) == Some(&"c") && storage.get_key(&c) == Some(&"a"));
+    assert!(storage.get_by_index(&a) == Some(&3));
+    assert!(storage.swap_indices(&b, &b));
+    assert!(storage.get_by_key("b") == Some(&2));
+
+    assert!(!storage.relocate(&b, &a));
+    assert!(storage.relocate(&b, &10));
+    assert!(storage.get_index("b") == Some(&10) && storage.get_key(&10) == Some(&"b"));
+    assert!(storage.get_by_index(&b).is_none());
+    assert!(!storage.swap_indices(&b, &a));
+
+    let (d, _) = storage.insert("d", 4).unwrap();
+    assert!(storage.get_key(&d) == Some(&"d") && storage.len() == 4);
+
+    let mut generation: MappedGeneration<&str, u32> = MappedGeneration::new();
+    let (a, _) = generation.insert("a", 1).unwrap();
+    let (b, _) = generation.insert("b", 2).unwrap();
+    generation.remove(&KeyIdx::new("b"));
+    generation.insert("c", 3).unwrap();
+    generation.remove(&KeyIdx::new("c"));
+
+    // b's slot has moved on a generation so its old id is refused
+    assert!(!generation.relocate(&a, &b));
+    assert!(generation.get_by_key("a") == Some(&1));
+
+    let to = StorageId::first(5);
+    assert!(generation.relocate(&a, &to));
+    assert!(generation.get_by_key("a") == Some(&1) && generation.get_index("a") == Some(&to));
+
+    // Swapping keeps both ids current even though a removed id would go stale
+    let (d, _) = generation.insert("d", 4).unwrap();
+    assert!(generation.swap_indices(&to, &d));
+    assert!(generation.get_by_index(&to) == Some(&4) && generation.get_by_index(&d) == Some(&1));
+    assert!(generation.get_index("a") == Some(&d) && generation.get_key(&to) == Some(&"d"));
+    assert!(generation.swap_indices(&d, &d) && generation.len() == 2);
+
+    // A newer generation of a slot another key holds is still taken
+    let newer =
+        StorageId { index: to.index, generation: NonZeroU64::new(to.generation.get() + 1).unwrap() };
+    assert!(!generation.relocate(&d, &newer));
+    assert!(generation.get_by_index(&to) == Some(&4) && generation.get_by_key("d") == Some(&4));
+    assert!(generation.get_by_index(&d) == Some(&1) && generation.len() == 2);
+
+    let mut versioned: MappedVersioned<&str, u32> = MappedVersioned::new();
+    let (x, _) = versioned.insert("x", 1).unwrap();
+    let (y, _) = versioned.insert("y", 2).unwrap();
+    assert!(versioned.swap_indices(&x, &y));
+    assert!(versioned.get_by_key("x") == Some(&1) && versioned.get_index("x") == Some(&y));
+}
+
+#[test]
+fn ordered_generation_test() {
+    let mut storage = OrderedGenerationStorage::new();
+    let a = storage.push("a");
+    let b = storage.push("b");
+    let c = storage.push("c");
+
+    storage.remove_id(a);
+    let d = storage.push("d");
+    assert!(d.index == a.index);
+
+    storage.insert(b, "B");
+    storage.insert(StorageId::first(5), "e");
+
+    let values: Vec<_> = storage.values_ordered().copied().collect();
+    assert!(values == vec!["B", "c", "d", "e"]);
+
+    storage.remove_id(c);
+    let ids: Vec<_> = storage.iter_ordered().map(|(id, _)| id.index).collect();
+    assert!(ids == vec![b.index, d.index, 5]);
+
+    // Reads go through to the inner storage
+    assert!(storage.get(d) == Some(&"d"));
+    assert!(storage.iter_with_ids().count() == 3);
+
+    storage.clear();
+    assert!(storage.iter_ordered().next().is_none());
+}
+
+#[test]
+fn int_mapped_test() {
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut hasher = hasher::BuildIdentityHasher::default().build_hasher();
+    hasher.write_u64(42);
+    assert!(hasher.finish() == 42);
+
+    let mut storage: IntMappedNoVec<u64, &str> = IntMappedNoVec::default();
+    let (a, _) = storage.insert(7, "seven").unwrap();
+    storage.insert(1 << 40, "big").unwrap();
+
+    assert!(storage.get_by_key(&7) == Some(&"seven"));
+    assert!(storage.get_by_key(&(1 << 40)) == Some(&"big"));
+    assert!(storage.get_key(&a) == Some(&7));
+
+    let mut generation: IntMappedGeneration<u32, f32> =
+        MappedStorage::with_hasher(Default::default());
+    generation.insert(3, 1.5).unwrap();
+    assert!(generation.get_by_key(&3) == Some(&1.5));
+}
+
+#[test]
+fn versioned_novec_test() {
+    let mut storage = VersionedNoVec::new();
+    let a = storage.push("a");
+    let b = storage.push("b");
+
+    assert!(storage.remove(a) == Some("a"));
+    assert!(storage.remove(a).is_none());
+
+    // The freed slot is reused but the old index no longer reaches it
+    let c = storage.push("c");
+    assert!(c.index == a.index);
+    assert!(c.version != a.version);
+    assert!(storage.get(a).is_none());
+    assert!(storage.get(c) == Some(&"c"));
+    assert!(storage.insert(a, "stale") == Some("stale"));
+    assert!(storage.get(c) == Some(&"c"));
+
+    assert!(storage.insert(b, "B") == Some("b"));
+    assert!(storage.get(b) == Some(&"B"));
+
+    let next = storage.next_id();
+    storage.remove(b);
+    assert!(storage.next_id() != next);
+    assert!(storage.try_insert(storage.next_id(), "d") == Ok(None));
+
+    let mut values: Vec<_> = storage.iter().map(|(id, value)| (id, *value)).collect();
+    values.sort_by_key(|(id, _)| id.index);
+    assert!(values == vec![(c, "c"), (VersionedIndex { index: 1, version: 1 }, "d")]);
+
+    // Slots skipped over by an insert further along have never been used, so they take any
+    // version while the used ones still turn stale indices away
+    let mut skipped = VersionedNoVec::new();
+    assert!(skipped.try_insert(VersionedIndex { index: 3, version: 2 }, "e") == Ok(None));
+    let f = VersionedIndex { index: 1, version: 5 };
+    assert!(skipped.try_insert(f, "f") == Ok(None));
+    assert!(skipped.get(f) == Some(&"f"));
+    assert!(skipped.remove(f) == Some("f"));
+    assert!(skipped.try_insert(f, "g") == Err("g"));
+    assert!(skipped.try_insert(VersionedIndex { index: 3, version: 0 }, "h") == Err("h"));
+
+    let mut mapped: MappedVersioned<&str, u32> = MappedStorage::new();
+    let (one, _) = mapped.insert("one", 1).unwrap();
+    assert!(mapped.remove(&KeyIdx::new("one")) == Some(1));
+    assert!(mapped.get_by_key(&"one").is_none());
+    assert!(mapped.get_by_index(&one).is_none());
+}
+
+crate::newtype_counter!(struct Ticks(u16));
+
+#[test]
+fn counter_types_test() {
+    use manager::Counter;
+
+    let mut small = u8::zero();
+    small.increment(&200);
+    small.increment(&200);
+    assert!(small == u8::MAX);
+
+    let mut ticks = Ticks::zero();
+    ticks.increment(&Ticks(3));
+    assert!(ticks == Ticks(3));
+    assert!(ticks.is_valid(&Ticks(4)));
+    ticks.reset();
+    assert!(ticks == Ticks(0));
+
+    let (sender, receiver) = cbc::unbounded();
+    let test_loader = TestLoader(receiver);
+
+    thread::spawn(move || {
+        test_loader.receive();
+    });
+
+    let mut manager = ManagedGen::<String, f32, Ticks>::new(GenLoader::new_with_loader(sender), Ticks(2));
+
+    let mut a = KeyIdx::new("a".to_string());
+    manager.load(&mut a);
+    manager.update_loaded_blocking();
+    manager.increment(&Ticks(1));
+    manager.remove_out_of_date();
+    assert!(manager.get(&a) == Some(&101.1_f32));
+
+    manager.increment(&Ticks(1));
+    manager.remove_out_of_date();
+    assert!(manager.get(&a).is_none());
+
+    let _: ManagedNoVec<String, f32, f64> =
+        ManagedNoVec::new(NoVecLoader::new_with_loader(cbc::unbounded().0), 1.0);
+}
+
+#[test]
+fn builder_retry_test() {
+    use std::io;
+
+    let (mut system, receiver): (NoVecLoader<String, f32>, _) = StorageSystem::builder()
+        .channel_capacity(4)
+        .capacity(8)
+        .duplicate_policy(DuplicatePolicy::ReplaceKeepIndex)
+        .retry_policy(RetryPolicy::Times(2))
+        .build_channel();
+
+    assert!(system.duplicate_policy() == DuplicatePolicy::ReplaceKeepIndex);
+
+    let fail = |receiver: &GenericReceiver<String>| {
+        let (_, into) = receiver.try_recv().unwrap();
+        let error = io::Error::other("flaky");
+        into.send(GenericResult::new_error(error)).unwrap();
+    };
+
+    // Two failures are retried and the third attempt succeeds
+    let mut a = KeyIdx::new("a".to_string());
+    system.load(&mut a);
+    fail(&receiver);
+    system.update_loaded_blocking();
+    fail(&receiver);
+    system.update_loaded_blocking();
+
+    let (key, into) = receiver.try_recv().unwrap();
+    assert!(key == "a");
+    into.send(GenericResult::new(1.5_f32)).unwrap();
+    system.update_loaded_blocking();
+
+    assert!(!system.were_errors());
+    assert!(system.get(&a) == Some(&1.5));
+
+    // Once the retries run out the error is reported
+    let mut b = KeyIdx::new("b".to_string());
+    system.load(&mut b);
+
+    for _ in 0..3 {
+        fail(&receiver);
+        system.update_loaded_blocking();
+    }
+
+    assert!(receiver.try_recv().is_err());
+    assert!(system.remove_failed().count() == 1);
+    assert!(system.get_status(&b).is_none());
+
+    let (mut manager, receiver): (ManagedNoVec<String, f32, u32>, _) = ManagedStorage::builder(5)
+        .max_entries(1)
+        .eviction_policy(EvictionPolicy::Lru)
+        .build_channel();
+
+    thread::spawn(move || TestLoader(receiver).receive());
+
+    let mut c = KeyIdx::new("c".to_string());
+    manager.load(&mut c);
+    manager.update_loaded_blocking();
+
+    let mut d = KeyIdx::new("d".to_string());
+    manager.load(&mut d);
+    manager.update_loaded_blocking();
+
+    assert!(manager.get(&d) == Some(&101.1));
+    assert!(manager.storage().get(&KeyIdx::new("c".to_string())).is_none());
+}
+
+#[test]
+fn streaming_load_test() {
+    let (sender, receiver) = cbc::unbounded();
+    let mut system: NoVecLoader<String, Vec<u8>> = StorageSystem::new_with_loader(sender);
+
+    let mut a = KeyIdx::new("a".to_string());
+    assert!(system.load_streaming(&mut a) == LoadStatus::Loading);
+
+    let (_, into) = receiver.try_recv().unwrap();
+    assert!(into.is_streaming());
+    into.send_chunk(GenericResult::new_sized(vec![1_u8, 2], 2)).ok().unwrap();
+
+    // Regular updates leave streams alone rather than waiting on them
+    system.update_loaded_blocking();
+    system.update_streaming();
+
+    assert!(system.get(&a).is_none());
+    assert!(system.get_partial(&a) == Some(&vec![1, 2]));
+    assert!(system.stream_status(&a) == Some(StreamStatus { chunks: 1, bytes: 2 }));
+
+    into.send_chunk(GenericResult::new_sized(vec![3_u8], 1)).ok().unwrap();
+    assert!(into.finish());
+    system.update_streaming();
+
+    assert!(system.get(&a) == Some(&vec![1, 2, 3]));
+    assert!(system.stream_status(&a).is_none());
+    assert!(system.reported_size(&a) == Some(3));
+
+    // A loader that gives up part way fails the load
+    let mut b = KeyIdx::new("b".to_string());
+    system.load_streaming(&mut b);
+
+    let (_, into) = receiver.try_recv().unwrap();
+    into.send_chunk(GenericResult::new(vec![4_u8])).ok().unwrap();
+    drop(into);
+    system.update_streaming();
+
+    assert!(system.were_errors());
+    assert!(system.remove_failed().count() == 1);
+    assert!(system.get_partial(&b).is_none());
+
+    // Non streaming loads can't be sent in pieces
+    let mut c = KeyIdx::new("c".to_string());
+    system.load(&mut c);
+
+    let (_, into) = receiver.try_recv().unwrap();
+    assert!(!into.is_streaming());
+    assert!(into.send_chunk(GenericResult::new(vec![5_u8])).is_err());
+}
+
+#[test]
+fn load_group_test() {
+    use std::io;
+
+    let (sender, receiver) = cbc::unbounded();
+    let mut floats: NoVecLoader<String, f32> = StorageSystem::new_with_loader(sender.clone());
+    let mut ints: GenLoader<String, u32> = StorageSystem::new_with_loader(sender);
+
+    let mut a = KeyIdx::new("a".to_string());
+    floats.load(&mut a);
+
+    let group = floats.begin_group("level_3");
+    floats.load(&mut a);
+    floats.load(&mut KeyIdx::new("b".to_string()));
+    floats.end_group();
+
+    // Loads outside the group aren't counted
+    floats.load(&mut KeyIdx::new("c".to_string()));
+
+    ints.join_group(&group);
+    ints.load(&mut KeyIdx::new("d".to_string()));
+
+    let status = floats.group_status("level_3").unwrap();
+    assert!(status == GroupStatus { loaded: 0, pending: 3, failed: 0 });
+    assert!(ints.group_status("level_3") == Some(status));
+
+    for _ in 0..4 {
+        let (key, into) = receiver.try_recv().unwrap();
+
+        let item = match key.as_str() {
+            "b" => GenericResult::new_error(io::Error::other("missing")),
+            "d" => GenericResult::new(7_u32),
+            _ => GenericResult::new(1.0_f32),
+        };
+
+        into.send(item).unwrap();
+    }
+
+    floats.update_loaded();
+    assert!(!group.is_complete());
+    assert!(group.status() == GroupStatus { loaded: 1, pending: 1, failed: 1 });
+
+    ints.update_loaded();
+    assert!(group.is_complete());
+    assert!(group.status().progress() == 1.0);
+
+    // Already loaded keys are done straight away
+    let next = floats.begin_group("level_4");
+    floats.load(&mut a);
+    assert!(next.status() == GroupStatus { loaded: 1, pending: 0, failed: 0 });
+
+    // Removing a pending load fails it
+    let mut e = KeyIdx::new("e".to_string());
+    floats.load(&mut e);
+    floats.remove(&e);
+    assert!(next.status().failed == 1);
+    assert!(floats.remove_group("level_4").is_some());
+    assert!(floats.active_group().is_none());
+}
+
+#[test]
+fn file_mapper_fallback_test() {
+    use file_mapper::{FileMapper, MapError};
+
+    let dir = std::env::temp_dir().join(format!("novec_fallback_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("level.json"), "json").unwrap();
+    std::fs::write(dir.join("level.bin"), "bin").unwrap();
+    std::fs::write(dir.join("mesh.ron"), "ron").unwrap();
+
+    let mappings = vec![
+        ("level".to_string(), dir.join("level.ron")),
+        ("mesh".to_string(), dir.join("mesh.ron")),
+        ("missing".to_string(), dir.join("missing.ron")),
+    ];
+
+    let (sender, receiver) = cbc::unbounded();
+    let mapper = FileMapper::from_mappings(receiver, None, dir.clone(), mappings.into_iter())
+        .with_fallback_extensions(vec![".ron", "json", "bin"]);
+
+    assert!(mapper.fallback_extensions() == ["ron", "json", "bin"]);
+
+    let mut extensions: GenLoader<String, String> = GenLoader::new_with_loader(sender);
+    let mut level = KeyIdx::new("level".to_string());
+    let mut mesh = KeyIdx::new("mesh".to_string());
+    let mut missing = KeyIdx::new("missing".to_string());
+
+    let mut failed = vec![];
+
+    for ki in [&mut level, &mut mesh, &mut missing] {
+        extensions.load(ki);
+
+        mapper
+            .receive_non_blocking(
+                |object| {
+                    let extension = object.path.extension().unwrap().to_str().unwrap();
+                    GenericResult::new(extension.to_string())
+                },
+                |key, error| match error {
+                    MapError::FileError(path, _) => failed.push((key, path)),
+                    MapError::MissingMapping => panic!("Every key is mapped"),
+                },
+            )
+            .unwrap();
+    }
+
+    extensions.update_loaded();
+
+    // The first fallback that exists wins, and a mapped path that exists is used as is
+    assert!(extensions.get(&level) == Some(&"json".to_string()));
+    assert!(extensions.get(&mesh) == Some(&"ron".to_string()));
+    assert!(failed == vec![("missing".to_string(), dir.join("missing.ron"))]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn generation_reserve_id_test() {
+    let mut storage = GenerationStorage::new();
+    let a = storage.push(1u32);
+    storage.remove_id(a);
+
+    // Reservations take the same ids pushes would have
+    let next = storage.nth_available(0);
+    let reserved = storage.reserve_id();
+    assert!(reserved == next);
+    assert!(reserved.index == a.index && reserved.generation.get() == a.generation.get() + 1);
+
+    let fresh = storage.reserve_id();
+    assert!(fresh == StorageId::first(1));
+
+    assert!(storage.get(reserved).is_none());
+    assert!(storage.is_reserved(reserved));
+    assert!(storage.values().count() == 0);
+
+    // Pushes skip reserved slots
+    let b = storage.push(2);
+    assert!(b.index == 2);
+
+    assert!(storage.insert_reserved(a, 3) == Err(3));
+    assert!(storage.insert_reserved(reserved, 4) == Ok(()));
+    assert!(storage.get(reserved) == Some(&4));
+    assert!(!storage.is_reserved(reserved));
+    assert!(storage.insert_reserved(reserved, 5) == Err(5));
+
+    // Reservations survive a snapshot
+    use std::io::{Read, Write};
+
+    let read_item = |reader: &mut &[u8]| {
+        let mut bytes = [0; 4];
+        reader.read_exact(&mut bytes).map(|_| u32::from_le_bytes(bytes))
+    };
+
+    let mut bytes = vec![];
+    storage.write_snapshot(&mut bytes, |writer, item| writer.write_all(&item.to_le_bytes())).unwrap();
+    let mut restored = GenerationStorage::read_snapshot(&bytes[..], read_item).unwrap();
+    assert!(restored.is_reserved(fresh));
+    assert!(restored.insert_reserved(fresh, 6) == Ok(()));
+
+    // A cancelled id is never handed out again
+    assert!(storage.cancel_reservation(fresh));
+    assert!(!storage.cancel_reservation(fresh));
+
+    let c = storage.push(7);
+    assert!(c.index == fresh.index && c.generation.get() == fresh.generation.get() + 1);
+}
+
+#[test]
+fn generation_reserve_ids_test() {
+    let mut storage = GenerationStorage::new();
+    let ids: Vec<_> = (0..4).map(|i| storage.push(i)).collect();
+    storage.remove_id(ids[1]);
+    storage.remove_id(ids[3]);
+
+    let predicted: Vec<_> = (0..5).map(|n| storage.nth_available(n)).collect();
+    let reserved = storage.reserve_ids(5);
+    assert!(reserved == predicted);
+
+    // Recycled slots, most recently freed first, then fresh ones
+    let indices: Vec<_> = reserved.iter().map(|id| id.index).collect();
+    assert!(indices == vec![3, 1, 4, 5, 6]);
+    assert!(reserved.iter().all(|id| storage.is_reserved(*id) && storage.get(*id).is_none()));
+
+    assert!(storage.push(10).index == 7);
+    assert!(storage.reserve_ids(0).is_empty());
+
+    for (value, id) in reserved.iter().enumerate() {
+        storage.insert_reserved(*id, value as i32).unwrap();
+    }
+
+    assert!(storage.get(reserved[4]) == Some(&4));
+    assert!(storage.values().count() == 8);
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn testing_module_test() {
+    use testing::*;
+
+    let counter = DropCounter::new();
+    let mut storage = GenerationStorage::new();
+    let a = storage.push(counter.track(1));
+    storage.push(counter.track(2));
+    let reserved = storage.reserve_id();
+    assert!(counter.live() == 2);
+
+    storage.remove_id(a);
+    assert!(counter.live() == 1 && counter.dropped() == 1);
+    assert_invariants(&storage);
+
+    storage.clear();
+    assert!(counter.live() == 0);
+    assert_invariants(&storage);
+    assert!(storage.insert_reserved(reserved, counter.track(3)).is_ok());
+    assert_invariants(&storage);
+
+    let mut novec = NoVec::new();
+    let ids: Vec<_> = (0..6).map(|i| novec.push(i)).collect();
+    novec.remove(ids[1]);
+    novec.remove(ids[4]);
+    novec.insert_at(9, 9);
+    assert_invariants(&novec);
+
+    let mut mapped: MappedNoVec<String, u32> = MappedStorage::new();
+    mapped.insert("a".to_string(), 1).unwrap();
+    mapped.insert("b".to_string(), 2).unwrap();
+    mapped.remove(&KeyIdx::new("a".to_string()));
+    assert_invariants(&mapped);
+    assert_invariants(&VersionedNoVec::<u32>::new());
+
+    let loader = ScriptedLoader::new()
+        .with_value("a", 1.5_f32)
+        .with_failures("a", 1)
+        .with_error("b", "corrupt")
+        .with_value("slow", 2.5_f32)
+        .with_latency("slow", Duration::from_millis(10));
+
+    let mut scripted: GenSystem<String, ScriptedLoader<String>, f32> = StorageSystem::builder()
+        .loader(loader.clone())
+        .retry_policy(RetryPolicy::Times(1))
+        .build();
+
+    let mut a = KeyIdx::new("a".to_string());
+    let mut b = KeyIdx::new("b".to_string());
+    let mut slow = KeyIdx::new("slow".to_string());
+    let mut unscripted = KeyIdx::new("c".to_string());
+
+    scripted.load(&mut a);
+    scripted.load(&mut b);
+    scripted.load(&mut slow);
+    scripted.load(&mut unscripted);
+    scripted.update_loaded_blocking();
+    scripted.update_loaded_blocking();
+
+    assert!(scripted.get(&a) == Some(&1.5));
+    assert!(scripted.get(&slow) == Some(&2.5));
+    assert!(loader.request_count(&"a".to_string()) == 2);
+    assert!(loader.request_count(&"b".to_string()) == 2);
+
+    let failed: Vec<_> = scripted.remove_failed().map(|(key, _, _)| key).collect();
+    assert!(failed.len() == 2 && failed.contains(&"b".to_string()));
+    assert_invariants(&scripted.storage);
+}
+
+#[test]
+fn novec_drain_test() {
+    let mut novec = NoVec::with_capacity(8);
+    let ids: Vec<_> = ["a", "b", "c", "d"].iter().map(|value| novec.push(value.to_string())).collect();
+    novec.remove(ids[1]);
+
+    let drained: Vec<_> = novec.drain().collect();
+    assert!(drained == vec![(0, "a".to_string()), (2, "c".to_string()), (3, "d".to_string())]);
+    assert!(novec.iter().next().is_none());
+    assert!(novec.next_id() == 0);
+
+    // Stopping early still empties it
+    novec.push("e".to_string());
+    novec.push("f".to_string());
+    assert!(novec.drain().next() == Some((0, "e".to_string())));
+    assert!(novec.values().next().is_none());
+    assert!(novec.push("g".to_string()) == 0);
+}
+
+#[test]
+fn novec_len_test() {
+    let mut novec = NoVec::with_capacity(4);
+    assert!(novec.is_empty());
+    assert!(novec.capacity() >= 4);
+
+    let a = novec.push(1);
+    novec.push(2);
+    assert!(novec.len() == 2);
+
+    // Replacing a value doesn't change the count, filling past the end does
+    novec.insert_at(a, 3);
+    novec.insert_at(5, 4);
+    assert!(novec.len() == 3);
+
+    novec.remove(a);
+    novec.remove(a);
+    novec.remove(9);
+    assert!(novec.len() == 2);
+
+    // The gap left before index 5 is filled without growing the count twice
+    novec.push(5);
+    novec.insert_at(3, 6);
+    assert!(novec.len() == 4);
+    assert!(novec.len() == novec.iter().count());
+
+    assert!(novec.drain().count() == 4);
+    assert!(novec.is_empty());
+    assert!(novec.capacity() >= 6);
+}
+
+#[test]
+fn novec_iterator_traits_test() {
+    let mut novec: NoVec<_> = vec!["a", "b", "c"].into_iter().collect();
+    assert!(novec.len() == 3);
+
+    // Extending reuses the entry freed by the remove first
+    novec.remove(1);
+    novec.extend(vec!["d", "e"]);
+
+    let mut borrowed = vec![];
+    for (index, value) in &novec {
+        borrowed.push((index, *value));
+    }
+    assert!(borrowed == vec![(0, "a"), (1, "d"), (2, "c"), (3, "e")]);
+
+    novec.remove(2);
+    let owned: Vec<_> = novec.into_iter().collect();
+    assert!(owned == vec![(0, "a"), (1, "d"), (3, "e")]);
+}
+
+#[test]
+fn novec_clear_test() {
+    let mut novec = NoVec::with_capacity(8);
+    for value in 0..6 {
+        novec.push(value);
+    }
+    novec.remove(2);
+    novec.remove(4);
+
+    let capacity = novec.capacity();
+    novec.clear();
+    assert!(novec.is_empty());
+    assert!(novec.get(0).is_none());
+    assert!(novec.capacity() == capacity);
+
+    // The old free list is gone so pushes start from the front again
+    assert!(novec.push(10) == 0);
+    assert!(novec.push(11) == 1);
+    assert!(novec.len() == 2);
+}
+
+#[test]
+fn novec_get_many_mut_test() {
+    let mut novec: NoVec<_> = vec![1, 2, 3, 4].into_iter().collect();
+    novec.remove(3);
+
+    if let Some([a, b]) = novec.get_many_mut([2, 0]) {
+        std::mem::swap(a, b);
+    }
+    assert!(novec.get(0) == Some(&3));
+    assert!(novec.get(2) == Some(&1));
+
+    // Repeated, empty or out of bounds indices give nothing
+    assert!(novec.get_many_mut([1, 1]).is_none());
+    assert!(novec.get_many_mut([0, 3]).is_none());
+    assert!(novec.get_many_mut([0, 7]).is_none());
+    assert!(novec.get_many_mut([0, 1, 2]).map(|values| values.len()) == Some(3));
+}
+
+#[test]
+fn densevec_test() {
+    let mut dense = DenseVec::new();
+    let a = dense.push("a");
+    let b = dense.push("b");
+    let c = dense.push("c");
+
+    // Removing from the front moves the last value into the gap without touching its index
+    assert!(dense.remove(a) == Some("a"));
+    assert!(dense.values() == ["c", "b"]);
+    assert!(dense.get(c) == Some(&"c"));
+    assert!(dense.get(a).is_none());
+    assert!(dense.remove(a).is_none());
+
+    // Freed indices are reused
+    assert!(dense.push("d") == a);
+    assert!(dense.insert_at(b, "e") == Some("b"));
+    assert!(dense.insert_at(6, "f").is_none());
+    assert!(dense.len() == 4);
+
+    let mut pairs: Vec<_> = dense.iter().map(|(index, value)| (index, *value)).collect();
+    pairs.sort();
+    assert!(pairs == vec![(a, "d"), (b, "e"), (c, "c"), (6, "f")]);
+
+    let mut storage: MappedDenseVec<String, u32> = MappedDenseVec::new();
+    let x = storage.insert("x".to_string(), 1).unwrap().0;
+    storage.insert("y".to_string(), 2).unwrap();
+    storage.remove_with_index(&x);
+    assert!(storage.get_by_index(&x).is_none());
+    assert!(storage.get_by_key("y") == Some(&2));
+
+    let (mut system, receiver): (DenseVecLoader<String, f32>, _) =
+        StorageSystem::builder().build_channel();
+    let mut key = KeyIdx::new("z".to_string());
+    system.load(&mut key);
+    let (_, into) = receiver.try_recv().unwrap();
+    into.send(GenericResult::new(2.5_f32)).unwrap();
+    system.update_loaded_blocking();
+    assert!(system.get(&key) == Some(&2.5));
+}
+
+#[test]
+fn novec_capacity_test() {
+    let mut novec = NoVec::new();
+    novec.reserve(16);
+    assert!(novec.capacity() >= 16);
+
+    for value in 0..16 {
+        novec.push(value);
+    }
+
+    for index in (1..16).filter(|index| index % 4 != 0) {
+        novec.remove(index);
+    }
+    novec.remove(0);
+
+    // Only the empty entries past the last value are dropped
+    novec.shrink_to_fit();
+    assert!(novec.capacity() < 16);
+    assert!(novec.get(12) == Some(&12));
+    assert!(novec.len() == 3);
+
+    let mut pushed: Vec<_> = (0..10).map(|value| novec.push(value)).collect();
+    pushed.sort();
+    assert!(pushed == vec![0, 1, 2, 3, 5, 6, 7, 9, 10, 11]);
+    assert!(novec.push(100) == 13);
+}
+
+#[test]
+fn novec_exact_size_test() {
+    let mut novec: NoVec<_> = (0..6).collect();
+    novec.remove(1);
+    novec.remove(4);
+
+    let mut iter = novec.iter();
+    assert!(iter.len() == 4);
+    iter.next();
+    assert!(iter.size_hint() == (3, Some(3)));
+
+    assert!(novec.values().len() == 4);
+    assert!(novec.values_mut().len() == 4);
+
+    let mut values = novec.iter_mut();
+    values.next();
+    values.next();
+    assert!(values.len() == 2);
+
+    let mut owned = novec.into_iter();
+    assert!(owned.len() == 4);
+    assert!(owned.by_ref().count() == 4);
+    assert!(owned.len() == 0);
+}
+
+#[test]
+fn novec_truncate_test() {
+    let mut novec: NoVec<_> = (0..8).collect();
+    novec.remove(2);
+    novec.remove(6);
+
+    novec.truncate(5);
+    assert!(novec.len() == 4);
+    assert!(novec.get(5).is_none());
+
+    // Only the empty entry below the cut is left to reuse
+    assert!(novec.push(10) == 2);
+    assert!(novec.push(11) == 5);
+
+    novec.remove(3);
+    novec.remove(5);
+    assert!(novec.pop_last() == Some((4, 4)));
+    assert!(novec.next_id() == 3);
+    assert!(novec.pop_last() == Some((2, 10)));
+    assert!(novec.len() == 2);
+    assert!(novec.pop_last() == Some((1, 1)));
+    assert!(novec.pop_last() == Some((0, 0)));
+    assert!(novec.pop_last().is_none());
+    assert!(novec.push(12) == 0);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn novec_par_iter_test() {
+    use rayon::prelude::*;
+
+    let mut novec: NoVec<u64> = (0..1000).collect();
+    for index in (0..1000).step_by(3) {
+        novec.remove(index);
+    }
+
+    novec.par_iter_mut().for_each(|(index, value)| *value += index as u64);
+    assert!(novec.par_iter().all(|(index, value)| *value == index as u64 * 2));
+
+    novec.par_values_mut().for_each(|value| *value /= 2);
+    assert!(novec.par_values().sum::<u64>() == novec.values().sum::<u64>());
+    assert!(novec.par_values().count() == novec.len());
+}
+
+#[test]
+fn novec_entry_test() {
+    let mut novec: NoVec<_> = vec![1, 2].into_iter().collect();
+    novec.remove(0);
+
+    *novec.entry(1).and_modify(|value| *value *= 10).or_insert(0) += 1;
+    assert!(novec.get(1) == Some(&21));
+
+    // Vacant entries can be in the free list or past the end
+    assert!(*novec.entry(0).or_insert_with(|| 5) == 5);
+    assert!(*novec.entry(4).or_default() == 0);
+    assert!(novec.len() == 3);
+    assert!(novec.push(6) == 2);
+
+    match novec.entry(3) {
+        IndexEntry::Vacant(vacant) => assert!(vacant.index() == 3),
+        IndexEntry::Occupied(_) => panic!("Entry 3 should be vacant"),
+    }
+
+    if let IndexEntry::Occupied(mut occupied) = novec.entry(2) {
+        assert!(occupied.insert(7) == 6);
+        assert!(occupied.remove() == 7);
+    }
+    assert!(novec.get(2).is_none());
+    assert!(novec.len() == 3);
+}
+
+#[test]
+fn novec32_test() {
+    assert!(std::mem::size_of::<novec32::Entry32<u32>>() < std::mem::size_of::<novec::Entry<u32>>());
+
+    let mut novec = NoVec32::new();
+    let a = novec.push("a");
+    let b = novec.push("b");
+    assert!(novec.remove(a) == Some("a"));
+    assert!(novec.remove(a).is_none());
+    assert!(novec.push("c") == a);
+
+    // Skipped entries are used lowest first
+    assert!(novec.insert_at(5, "d").is_none());
+    assert!(novec.insert_at(b, "e") == Some("b"));
+    assert!(novec.push("f") == 2);
+    assert!(novec.push("g") == 3);
+    novec.remove(3);
+    assert!(novec.push("h") == 3);
+    assert!(novec.push("i") == 4);
+    assert!(novec.push("j") == 6);
+    assert!(novec.len() == 7);
+
+    let values: Vec<_> = novec.values().copied().collect();
+    assert!(values == vec!["c", "e", "f", "h", "i", "d", "j"]);
+
+    let mut storage: MappedNoVec32<String, u32> = MappedNoVec32::new();
+    let x = storage.insert("x".to_string(), 1).unwrap().0;
+    storage.insert("y".to_string(), 2).unwrap();
+    assert!(storage.get_by_index(&x) == Some(&1));
+    assert!(storage.get_by_key("y") == Some(&2));
+}
+
+#[test]
+fn generation_len_test() {
+    let mut storage = GenerationStorage::new();
+    assert!(storage.is_empty());
+
+    let ids: Vec<_> = (0..5).map(|i| storage.push(i)).collect();
+    storage.remove_id(ids[1]);
+    storage.remove_id(ids[1]);
+    assert!(storage.len() == 4);
+    assert!(storage.free_slots() == 1);
+
+    // Replacing keeps the count, filling past the end leaves free slots behind
+    storage.insert(ids[0], 10);
+    storage.insert(StorageId::first(7), 11);
+    assert!(storage.len() == 5);
+    assert!(storage.free_slots() == 3);
+
+    let reserved = storage.reserve_id();
+    assert!(storage.len() == 5 && storage.free_slots() == 2);
+    storage.insert_reserved(reserved, 12).unwrap();
+    assert!(storage.len() == 6);
+
+    storage.retain(|value| *value > 3);
+    assert!(storage.len() == storage.values().count());
+    assert!(storage.len() == 4);
+
+    storage.clear();
+    assert!(storage.is_empty());
+    assert!(storage.free_slots() == 8);
+}
+
+#[test]
+fn generation_nonzero_test() {
+    use std::mem::size_of;
+
+    assert!(size_of::<Option<StorageId>>() == size_of::<StorageId>());
+
+    let mut storage = GenerationStorage::new();
+    let a = storage.push(1);
+    assert!(a == StorageId::first(0));
+    storage.remove_id(a);
+    assert!(storage.push(2).generation.get() == 2);
+
+    // Only version 1 snapshots are read and a zero generation is turned away
+    let snapshot = |version: u32, generation: u64| {
+        let mut snapshot = b"GENS".to_vec();
+        snapshot.extend_from_slice(&version.to_le_bytes());
+        snapshot.extend_from_slice(&1_u64.to_le_bytes());
+        snapshot.extend_from_slice(&1_u64.to_le_bytes());
+        snapshot.extend_from_slice(&1_u64.to_le_bytes());
+        snapshot.extend_from_slice(&0_u64.to_le_bytes());
+        snapshot.extend_from_slice(&generation.to_le_bytes());
+        snapshot.push(0);
+        snapshot
+    };
+    let read = |bytes: Vec<u8>| {
+        GenerationStorage::<u32>::read_snapshot(&bytes[..], |_| unreachable!()).map_err(|e| e.kind())
+    };
+
+    let restored = read(snapshot(1, 2)).unwrap();
+    assert!(restored.free_slots() == 1);
+    assert!(restored.is_stale(StorageId::first(0)));
+    assert!(!restored.is_stale(StorageId { index: 0, generation: NonZeroU64::new(2).unwrap() }));
+    assert!(read(snapshot(1, 0)).err() == Some(std::io::ErrorKind::InvalidData));
+    assert!(read(snapshot(2, 2)).err() == Some(std::io::ErrorKind::InvalidData));
+
+    // A header claiming far more slots than the input holds runs out of input instead of
+    // allocating for all of them
+    let mut huge = b"GENS".to_vec();
+    huge.extend_from_slice(&1_u32.to_le_bytes());
+    huge.extend_from_slice(&1_u64.to_le_bytes());
+    huge.extend_from_slice(&(u64::MAX / 2).to_le_bytes());
+    huge.extend_from_slice(&(u64::MAX / 2).to_le_bytes());
+    assert!(read(huge.clone()).err() == Some(std::io::ErrorKind::UnexpectedEof));
+
+    huge.truncate(16);
+    huge.extend_from_slice(&(u64::MAX / 2).to_le_bytes());
+    huge.extend_from_slice(&0_u64.to_le_bytes());
+    assert!(read(huge).err() == Some(std::io::ErrorKind::UnexpectedEof));
+}
+
+#[test]
+fn generation_get_disjoint_mut_test() {
+    let mut storage = GenerationStorage::new();
+    let a = storage.push(1);
+    let b = storage.push(2);
+    let c = storage.push(3);
+
+    if let Some([a, c]) = storage.get_disjoint_mut([a, c]) {
+        std::mem::swap(a, c);
+    }
+    assert!(storage.get(a) == Some(&3) && storage.get(c) == Some(&1));
+
+    assert!(storage.get_disjoint_mut([a, a]).is_none());
+
+    // A stale id is refused even though its slot holds something
+    storage.remove_id(b);
+    let d = storage.push(4);
+    assert!(storage.get_disjoint_mut([a, b]).is_none());
+    assert!(storage.get_disjoint_mut([d, a, c]).map(|items| *items[0]) == Some(4));
+}
+
+#[test]
+fn generation_retain_with_ids_test() {
+    let mut storage = GenerationStorage::new();
+    let ids: Vec<_> = (0..6).map(|i| storage.push(i)).collect();
+    storage.remove_id(ids[2]);
+
+    let mut evicted = vec![];
+    storage.retain_with_ids(|id, value| {
+        *value *= 10;
+
+        if *value >= 30 {
+            evicted.push(id);
+            return false;
+        }
+
+        true
+    });
+
+    assert!(evicted == ids[3..].to_vec());
+    assert!(storage.values().copied().collect::<Vec<_>>() == vec![0, 10]);
+    assert!(storage.len() == 2);
+
+    // The evicted ids go stale straight away, they can't be used to put a value back
+    assert!(evicted.iter().all(|id| storage.get(*id).is_none()));
+    assert!(storage.insert(evicted[0], 70) == Some(70));
+    assert!(storage.try_insert(evicted[1], 80) == Err(80));
+    assert!(storage.len() == 2);
+
+    // And stay stale once their slots are reused
+    let reused = storage.push(7);
+    assert!(evicted.iter().all(|id| storage.get(*id).is_none()));
+    assert!(evicted.iter().any(|id| id.index == reused.index));
+}
+
+#[test]
+fn generation_into_iter_test() {
+    let mut storage = GenerationStorage::new();
+    let ids: Vec<_> = ["a", "b", "c"].iter().map(|value| storage.push(value.to_string())).collect();
+    storage.remove_id(ids[0]);
+    let d = storage.push("d".to_string());
+
+    let mut migrated: MappedNoVec<StorageId, String> = MappedNoVec::new();
+    let items = storage.into_iter();
+    assert!(items.len() == 3);
+
+    for (id, value) in items {
+        migrated.insert(id, value).unwrap();
+    }
+
+    assert!(migrated.get_by_key(&d).map(|value| &value[..]) == Some("d"));
+    assert!(migrated.get_by_key(&ids[0]).is_none());
+    assert!(migrated.get_by_key(&ids[2]).map(|value| &value[..]) == Some("c"));
+}
+
+#[test]
+fn generation_compact_test() {
+    let mut storage = GenerationStorage::new();
+    let ids: Vec<_> = (0..8).map(|i| storage.push(i)).collect();
+    for id in &[ids[0], ids[2], ids[3], ids[6]] {
+        storage.remove_id(*id);
+    }
+    let reserved = storage.reserve_id();
+
+    let mut moves = vec![];
+    storage.compact(|from, to| moves.push((from, to)));
+
+    // The reserved slot stays put, the other gaps are filled from the back
+    let moved: Vec<_> = moves.iter().map(|(from, to)| (from.index, to.index)).collect();
+    assert!(moved == vec![(7, 0), (5, 2), (4, 3)]);
+    assert!(moves.iter().all(|(from, to)| storage.get(*from).is_none() && storage.get(*to).is_some()));
+    assert!(storage.get(moves[0].1) == Some(&7));
+    assert!(storage.is_reserved(reserved));
+
+    assert!(storage.len() == 4);
+    assert!(storage.push(10).index == 4);
+
+    let mut mapped: MappedGeneration<&str, u32> = MappedGeneration::new();
+    mapped.insert("a", 1).unwrap();
+    mapped.insert("b", 2).unwrap();
+    mapped.insert("c", 3).unwrap();
+    mapped.remove(&KeyIdx::new("a"));
+
+    let mut count = 0;
+    mapped.compact(|_, _| count += 1);
+    assert!(count == 1);
+    assert!(mapped.get_index("c").map(|id| id.index) == Some(0));
+    assert!(mapped.get_by_key("c") == Some(&3));
+    assert!(mapped.get_key(mapped.get_index("c").unwrap()) == Some(&"c"));
+}
+
+#[test]
+fn generation_shrink_test() {
+    let mut storage = GenerationStorage::new();
+    storage.reserve(32);
+    let ids: Vec<_> = (0..32_i32).map(|i| storage.push(i)).collect();
+
+    for id in &ids[4..] {
+        storage.remove_id(*id);
+    }
+    storage.remove_id(ids[1]);
+
+    storage.shrink_to_fit();
+    assert!(storage.free_slots() == 1);
+    assert!(storage.len() == 3);
+    assert!(storage.get(ids[3]) == Some(&3));
+
+    // Recreated slots start past the generations of the dropped ones
+    assert!(storage.push(40).index == 1);
+    let recreated = storage.push(41);
+    assert!(recreated.index == 4 && recreated.generation > ids[4].generation);
+    assert!(storage.get(ids[4]).is_none());
+    assert!(storage.nth_available(0).generation == recreated.generation);
+
+    // The ids of dropped slots stay stale even once the slots are gone
+    let mut shrunk = GenerationStorage::new();
+    let a = shrunk.push(1);
+    let b = shrunk.push(2);
+    shrunk.remove_id(b);
+    shrunk.shrink_to_fit();
+    assert!(shrunk.try_insert(b, 3) == Err(3));
+    assert!(shrunk.get(b).is_none() && shrunk.len() == 1 && shrunk.get(a) == Some(&1));
+
+    use std::io::{Read, Write};
+
+    let read_item = |reader: &mut &[u8]| {
+        let mut bytes = [0; 4];
+        reader.read_exact(&mut bytes).map(|_| i32::from_le_bytes(bytes))
+    };
+
+    let mut bytes = vec![];
+    storage.write_snapshot(&mut bytes, |writer, item| writer.write_all(&item.to_le_bytes())).unwrap();
+    let mut read = GenerationStorage::read_snapshot(&bytes[..], read_item).unwrap();
+    assert!(read.push(42) == StorageId { index: 5, generation: recreated.generation });
+}
+
+#[test]
+fn packed_storage_id_test() {
+    use std::{convert::TryFrom, mem::size_of, num::NonZeroU32};
+
+    assert!(size_of::<Option<PackedStorageId>>() == 8);
+
+    let id = PackedStorageId::new(7, NonZeroU32::new(3).unwrap());
+    assert!(id.index() == 7 && id.generation().get() == 3);
+    assert!(id.to_bits() == (3 << 32) | 7);
+    assert!(PackedStorageId::from_bits(id.to_bits()) == Some(id));
+    assert!(PackedStorageId::from_bits(7).is_none());
+
+    let wide = StorageId { index: 7, generation: NonZeroU64::new(3).unwrap() };
+    assert!(StorageId::from(id) == wide);
+    assert!(PackedStorageId::try_from(wide) == Ok(id));
+    let too_old = StorageId { index: 7, generation: NonZeroU64::new(1 << 40).unwrap() };
+    assert!(PackedStorageId::try_from(too_old).is_err());
+
+    let mut storage = PackedGenerationStorage::new();
+    let a = storage.push("a");
+    storage.remove_id(a);
+    let b = storage.push("b");
+    assert!(b.index() == a.index() && storage.get(a).is_none());
+    assert!(storage.get(b) == Some(&"b") && storage.len() == 1);
+
+    let mut mapped: MappedPackedGeneration<String, u32> = MappedPackedGeneration::new();
+    let (x, _) = mapped.insert("x".to_string(), 1).unwrap();
+    assert!(mapped.get_by_index(&x) == Some(&1));
+    assert!(mapped.get_key(&x).map(|key| &key[..]) == Some("x"));
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn generation_par_iter_test() {
+    use rayon::prelude::*;
+
+    let mut storage = GenerationStorage::new();
+    let ids: Vec<_> = (0..1000_u64).map(|i| storage.push(i)).collect();
+    for id in ids.iter().step_by(4) {
+        storage.remove_id(*id);
+    }
+    storage.push(0);
+
+    storage.par_values_mut().for_each(|value| *value *= 2);
+    assert!(storage.par_values().sum::<u64>() == storage.values().sum::<u64>());
+    assert!(storage.par_iter_with_ids().count() == storage.len());
+    assert!(storage.par_iter_with_ids().all(|(id, value)| storage.get(id) == Some(value)));
+
+    storage.par_iter_with_ids_mut().for_each(|(id, value)| *value = id.index as u64);
+    assert!(storage.iter_with_ids().all(|(id, value)| *value == id.index as u64));
+}
+
+#[test]
+fn generation_stats_test() {
+    let mut storage: GenerationStorage<u64> = GenerationStorage::new();
+    let empty = storage.stats();
+    assert!(empty.live == 0 && empty.tombstones == 0 && empty.heap_bytes == 0);
+    assert!(empty.highest_generation.is_none());
+
+    let ids: Vec<_> = (0..10).map(|i| storage.push(i)).collect();
+    storage.remove_id(ids[3]);
+    storage.push(10);
+    storage.remove_id(ids[5]);
+    storage.remove_id(ids[6]);
+    storage.reserve_id();
+
+    let stats = storage.stats();
+    assert!(stats.live == 8);
+    assert!(stats.tombstones == 2);
+    assert!(stats.reserved == 1);
+    assert!(stats.highest_generation == NonZeroU64::new(2));
+    assert!(stats.heap_bytes >= 11 * std::mem::size_of::<u64>());
+}
+
+#[test]
+fn generation_free_list_test() {
+    let mut storage = GenerationStorage::new();
+    let ids: Vec<_> = (0..6).map(|i| storage.push(i)).collect();
+    for id in &ids {
+        storage.remove_id(*id);
+    }
+
+    // Inserting into free slots in the middle of the list leaves the rest in order, the slots
+    // moved on a generation when they were emptied
+    let next = |id: StorageId| StorageId {
+        index: id.index,
+        generation: NonZeroU64::new(id.generation.get() + 1).unwrap(),
+    };
+    storage.insert(next(ids[2]), 20);
+    storage.insert(next(ids[4]), 40);
+    assert!(storage.free_slots() == 4);
+
+    let mut pushed: Vec<_> = (0..4).map(|i| storage.push(i).index).collect();
+    pushed.sort();
+    assert!(pushed == vec![0, 1, 3, 5]);
+    assert!(storage.push(6).index == 6);
+
+    // Inserting far past the end only claims the target slot
+    storage.insert(StorageId::first(10), 100);
+    assert!(storage.free_slots() == 3);
+    assert!((0..3).map(|_| storage.push(0).index).all(|index| (7..10).contains(&index)));
+    assert!(storage.len() == 11);
+}
+
+#[test]
+fn idvec_len_test() {
+    let mut ids = idvec::IdVec::with_capacity(4);
+    assert!(ids.is_empty() && ids.capacity() >= 4);
+
+    ids.insert(0, "a");
+    ids.insert(5, "b");
+    ids.insert(5, "c");
+    assert!(ids.len() == 2);
+
+    ids.remove(5);
+    ids.remove(5);
+    ids.remove(9);
+    assert!(ids.len() == 1);
+
+    ids.clear();
+    assert!(ids.is_empty());
+}
+
+#[test]
+fn idvec_iterator_traits_test() {
+    let manifest = vec![(2, "b"), (0, "a"), (5, "c")];
+    let mut ids: idvec::IdVec<_> = manifest.into_iter().collect();
+    assert!(ids.len() == 3);
+    assert!(ids.get(5) == Some(&"c"));
+
+    // Migrating from another storage keeps the indices
+    let mut novec = NoVec::new();
+    novec.push("d");
+    novec.insert_at(5, "e");
+    ids.extend(novec);
+
+    let pairs: Vec<_> = ids.iter().map(|(index, value)| (index, *value)).collect();
+    assert!(pairs == vec![(0, "d"), (2, "b"), (5, "e")]);
+    assert!(ids.len() == 3);
+}
+
+#[test]
+fn idvec_shrink_test() {
+    let mut ids: idvec::IdVec<_> = (0..64).map(|index| (index, index)).collect();
+    for index in 10..64 {
+        ids.remove(index);
+    }
+    ids.remove(3);
+
+    let capacity = ids.capacity();
+    ids.shrink();
+    assert!(ids.capacity() == capacity);
+    assert!(ids.get(9) == Some(&9) && ids.len() == 9);
+
+    ids.shrink_to_fit();
+    assert!(ids.capacity() < capacity);
+
+    ids.clear();
+    ids.shrink_to_fit();
+    assert!(ids.is_empty());
+    ids.insert(2, 2);
+    assert!(ids.get(2) == Some(&2));
+
+    let mut storage: MappedNoVec<u32, u32> = MappedNoVec::new();
+    for i in 0..32 {
+        storage.insert(i, i).unwrap();
+    }
+    for i in 1..32_u32 {
+        storage.remove(&KeyIdx::new(i));
+    }
+    storage.shrink_keys();
+    assert!(storage.get_by_key(&0) == Some(&0));
+    let (index, _) = storage.insert(40, 40).unwrap();
+    assert!(storage.get_key(&index) == Some(&40));
+}
+
+#[test]
+fn idvec_take_replace_swap_test() {
+    let mut ids: idvec::IdVec<_> = vec![(0, "a"), (2, "c")].into_iter().collect();
+
+    assert!(ids.take(0) == Some("a"));
+    assert!(ids.take(9).is_none());
+    assert!(ids.capacity() < 9);
+
+    // In range, occupied or not, the slot takes the value. Past the end it's handed back
+    assert!(ids.replace(2, "d") == Ok(Some("c")));
+    assert!(ids.replace(1, "b") == Ok(None));
+    assert!(ids.replace(9, "e") == Err("e"));
+    assert!(ids.get(9).is_none() && ids.len() == 2);
+
+    ids.swap(1, 2);
+    assert!(ids.get(1) == Some(&"d") && ids.get(2) == Some(&"b"));
+
+    // Moving a value past the end grows the container, swapping two empty slots doesn't
+    ids.swap(2, 6);
+    assert!(ids.get(6) == Some(&"b") && ids.get(2).is_none());
+    ids.swap(8, 12);
+    assert!(ids.iter().count() == 2 && ids.len() == 2);
+}
+
+#[test]
+fn idvec_sparse_iter_test() {
+    let mut ids = idvec::IdVec::new();
+
+    for index in (0..10_000).step_by(997) {
+        ids.insert(index, index);
+    }
+    ids.insert(63, 63);
+    ids.insert(64, 64);
+
+    let mut expected: Vec<usize> = (0..10_000).step_by(997).chain(vec![63, 64]).collect();
+    expected.sort_unstable();
+
+    assert!(ids.iter().map(|(index, _)| index).collect::<Vec<_>>() == expected);
+    assert!(ids.iter().all(|(index, value)| index == *value));
+
+    ids.remove(63);
+    ids.swap(64, 5000);
+    for (_, value) in ids.iter_mut() {
+        *value += 1;
+    }
+
+    expected.retain(|index| *index != 63 && *index != 64);
+    expected.push(5000);
+    expected.sort_unstable();
+    assert!(ids.iter().map(|(index, _)| index).collect::<Vec<_>>() == expected);
+    assert!(ids.get(5000) == Some(&65));
+
+    ids.clear();
+    assert!(ids.iter().next().is_none());
+}
+
+#[test]
+fn paged_idvec_test() {
+    use crate::paged_idvec::{PagedIdVec, PAGE_SIZE};
+
+    let mut ids = PagedIdVec::new();
+
+    assert!(ids.insert(10_000_000, "far").is_none());
+    assert!(ids.insert(3, "near").is_none());
+    assert!(ids.insert(3, "nearer") == Some("near"));
+    assert!(ids.page_count() == 2 && ids.len() == 2);
+
+    assert!(ids.get(10_000_000) == Some(&"far"));
+    assert!(ids.get(10_000_001).is_none() && ids.get(20_000_000).is_none());
+    assert!(ids.iter().map(|(index, _)| index).collect::<Vec<_>>() == vec![3, 10_000_000]);
+
+    // Emptying a page frees it
+    assert!(ids.remove(10_000_000) == Some("far"));
+    assert!(ids.remove(10_000_000).is_none());
+    assert!(ids.page_count() == 1 && ids.len() == 1);
+
+    ids.shrink_to_fit();
+    ids.insert(PAGE_SIZE, "second page");
+    for (_, value) in ids.iter_mut() {
+        *value = "changed";
+    }
+    assert!(ids.iter().all(|(_, value)| *value == "changed"));
+    assert!(crate::join::JoinStorage::indices(&ids).len() == 2);
+
+    #[cfg(feature = "testing")]
+    crate::testing::assert_invariants(&ids);
+}
+
+#[test]
+fn one_way_map_get_key_test() {
+    let mut map = OneWayMap::new();
+    let albedo = map.push("albedo", 0);
+    let normal = map.entry("normal").or_insert(1);
+
+    assert!(map.get_key(albedo) == Some(&"albedo"));
+    assert!(map.get_key(normal) == Some(&"normal"));
+    assert!(map.get_key(7).is_none());
+
+    map.remove(&"albedo");
+    assert!(map.get_key(albedo).is_none());
+
+    // The key follows the slot when it's claimed again
+    let roughness = map.push("roughness", 2);
+    assert!(roughness == albedo && map.get_key(roughness) == Some(&"roughness"));
+
+    assert!(map.remove_idx(normal) == Some(1));
+    assert!(map.get_key(normal).is_none() && !map.contains_key(&"normal"));
+}
+
+#[test]
+fn one_way_map_storage_test() {
+    use crate::hasher::BuildIdentityHasher;
+
+    let mut map: OneWayMap<u64, &str, BuildIdentityHasher> = OneWayMap::default();
+    let keyed = map.push(7, "keyed");
+    let unkeyed = ExpandableStorage::push(&mut map, "unkeyed");
+
+    assert!(map.len() == 2 && map.get_key(unkeyed).is_none());
+    assert!(UnorderedStorage::get(&map, &unkeyed) == Some(&"unkeyed"));
+
+    // Skipped slots are claimed by later pushes, lowest first
+    assert!(UnorderedStorage::insert(&mut map, 4, "far").is_none());
+    assert!(map.vacant_count() == 2);
+    assert!(map.push_value("fills 2") == 2);
+    assert!(UnorderedStorage::insert(&mut map, 3, "fills 3").is_none());
+    assert!(map.vacant_count() == 0 && map.len() == 5);
+
+    // Replacing a keyed slot through the traits keeps its key
+    assert!(UnorderedStorage::insert(&mut map, keyed, "rekeyed") == Some("keyed"));
+    assert!(map.get_by_key(&7) == Some(&"rekeyed"));
+
+    assert!(UnorderedStorage::remove(&mut map, &keyed) == Some("rekeyed"));
+    assert!(!map.contains_key(&7) && map.len() == 4);
+
+    let mut mapped: MappedStorage<idvec::IdVec<String>, OneWayMap<(), u32>> = MappedStorage::new();
+    mapped.insert("a".to_string(), 1).unwrap();
+    mapped.insert("b".to_string(), 2).unwrap();
+    assert!(mapped.get(&KeyIdx::new("b".to_string())) == Some(&2));
+}
+
+#[test]
+fn one_way_map_get_or_insert_with_test() {
+    let mut map = OneWayMap::new();
+    let a = map.get_or_insert_with("a", || 1);
+
+    assert!(map.get_or_insert_with("a", || panic!("already in the map")) == a);
+    assert!(map.get(a) == Some(&1));
+
+    let b = map.get_or_insert_with("b", || 2);
+    assert!(b != a && map.get_by_key("b") == Some(&2));
+
+    match map.entry("a") {
+        one_way_map::Entry::Occupied(mut occupied) => assert!(occupied.insert(3) == 1),
+        one_way_map::Entry::Vacant(_) => panic!("'a' should be occupied"),
+    }
+    assert!(map.get(a) == Some(&3));
+}
+
+#[test]
+fn generation_remove_then_insert_test() {
+    let mut storage = GenerationStorage::new();
+    let a = storage.push(1);
+    storage.remove_id(a);
+
+    // The removed id is stale, it can't bring its slot back to life
+    assert!(storage.try_insert(a, 2) == Err(2));
+    assert!(storage.insert(a, 2) == Some(2));
+    assert!(storage.get(a).is_none() && storage.is_empty());
+
+    let mut ordered = OrderedGenerationStorage::new();
+    let b = ordered.push(1);
+    ordered.remove_id(b);
+
+    assert!(ordered.try_insert(b, 2) == Err(2));
+    assert!(ordered.get(b).is_none());
+    assert!(ordered.iter().next().is_none());
+}
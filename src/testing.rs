@@ -0,0 +1,270 @@
+// Fixtures for testing code built on top of these storages, enabled by the 'testing' feature
+
+use crate::loader::*;
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    hash::Hash,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+// Storages that can check their own bookkeeping, such as the free list of a NoVec or the key to
+// index mapping of a MappedStorage
+pub trait CheckInvariants {
+    fn check_invariants(&self) -> Result<(), String>;
+}
+
+#[track_caller]
+pub fn assert_invariants(storage: &impl CheckInvariants) {
+    if let Err(e) = storage.check_invariants() {
+        panic!("Storage invariant broken: {}", e);
+    }
+}
+
+// Counts the values it's tracking that are still alive, for checking that a storage drops what
+// it holds exactly once
+#[derive(Clone, Debug, Default)]
+pub struct DropCounter {
+    live: Arc<AtomicUsize>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl DropCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn track<T>(&self, value: T) -> Tracked<T> {
+        self.live.fetch_add(1, Ordering::SeqCst);
+        Tracked { value, counter: self.clone() }
+    }
+
+    pub fn live(&self) -> usize {
+        self.live.load(Ordering::SeqCst)
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::SeqCst)
+    }
+}
+
+// A value that lets its DropCounter know when it's dropped
+#[derive(Debug)]
+pub struct Tracked<T> {
+    value: T,
+    counter: DropCounter,
+}
+
+impl<T> Tracked<T> {
+    pub fn counter(&self) -> &DropCounter {
+        &self.counter
+    }
+}
+
+impl<T: Clone> Clone for Tracked<T> {
+    fn clone(&self) -> Self {
+        self.counter.track(self.value.clone())
+    }
+}
+
+impl<T: PartialEq> PartialEq for Tracked<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T> Deref for Tracked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Tracked<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> Drop for Tracked<T> {
+    fn drop(&mut self) {
+        self.counter.live.fetch_sub(1, Ordering::SeqCst);
+        self.counter.dropped.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+// The error every scripted failure is reported with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptedError(pub String);
+
+impl Display for ScriptedError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Scripted failure: {}", self.0)
+    }
+}
+
+impl Error for ScriptedError {}
+
+type Respond = Arc<dyn Fn() -> GenericResult + Send + Sync>;
+
+#[derive(Clone, Default)]
+struct Script {
+    respond: Option<Respond>,
+    latency: Option<Duration>,
+    failures: usize,
+}
+
+struct ScriptState<K> {
+    scripts: HashMap<K, Script>,
+    fallback: Option<Respond>,
+    latency: Duration,
+    requests: Vec<K>,
+}
+
+// A loader that answers each key the way it's been told to. Without latency the answer is sent
+// before load returns, otherwise from a thread after the delay. Keys with no script fail unless
+// a default is given. Clones share their scripts and request log
+//
+// let loader = ScriptedLoader::new().with_value("a", 1.0_f32).with_failures("a", 2);
+pub struct ScriptedLoader<K> {
+    state: Arc<Mutex<ScriptState<K>>>,
+}
+
+impl<K> Clone for ScriptedLoader<K> {
+    fn clone(&self) -> Self {
+        Self { state: self.state.clone() }
+    }
+}
+
+impl<K: Hash + Eq> Default for ScriptedLoader<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq> ScriptedLoader<K> {
+    pub fn new() -> Self {
+        let state = ScriptState {
+            scripts: HashMap::new(),
+            fallback: None,
+            latency: Duration::from_secs(0),
+            requests: vec![],
+        };
+
+        Self { state: Arc::new(Mutex::new(state)) }
+    }
+
+    pub fn with_value<T>(self, key: impl Into<K>, value: T) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        self.with_result(key, move || GenericResult::new(value.clone()))
+    }
+
+    pub fn with_result(
+        self,
+        key: impl Into<K>,
+        respond: impl Fn() -> GenericResult + Send + Sync + 'static,
+    ) -> Self {
+        self.script(key.into(), |script| script.respond = Some(Arc::new(respond)));
+        self
+    }
+
+    // Every load of the key fails
+    pub fn with_error(self, key: impl Into<K>, message: impl Into<String>) -> Self {
+        let message = message.into();
+        self.with_result(key, move || GenericResult::new_error(ScriptedError(message.clone())))
+    }
+
+    // The first attempts loads of the key fail before its result is sent
+    pub fn with_failures(self, key: impl Into<K>, attempts: usize) -> Self {
+        self.script(key.into(), |script| script.failures = attempts);
+        self
+    }
+
+    pub fn with_latency(self, key: impl Into<K>, latency: Duration) -> Self {
+        self.script(key.into(), |script| script.latency = Some(latency));
+        self
+    }
+
+    // Used by keys without a latency of their own
+    pub fn with_default_latency(self, latency: Duration) -> Self {
+        self.state.lock().unwrap().latency = latency;
+        self
+    }
+
+    // The answer for keys without a script
+    pub fn with_default<T>(self, value: T) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        let respond: Respond = Arc::new(move || GenericResult::new(value.clone()));
+        self.state.lock().unwrap().fallback = Some(respond);
+        self
+    }
+
+    // Every key load has been called with, in order
+    pub fn requests(&self) -> Vec<K>
+    where
+        K: Clone,
+    {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn request_count(&self, key: &K) -> usize {
+        self.state.lock().unwrap().requests.iter().filter(|request| *request == key).count()
+    }
+
+    fn script(&self, key: K, f: impl FnOnce(&mut Script)) {
+        f(self.state.lock().unwrap().scripts.entry(key).or_default());
+    }
+}
+
+impl<K> Loader for ScriptedLoader<K>
+where
+    K: Hash + Eq + Clone,
+{
+    type Key = K;
+    type Item = GenericResult;
+    type Meta = TypeId;
+
+    fn load(&self, key: K, into: PromiseSender<GenericResult, TypeId>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.requests.push(key.clone());
+
+        let attempt = state.requests.iter().filter(|request| **request == key).count();
+        let script = state.scripts.get(&key).cloned().unwrap_or_default();
+        let latency = script.latency.unwrap_or(state.latency);
+
+        let respond = match script.respond.or_else(|| state.fallback.clone()) {
+            Some(respond) if attempt > script.failures => respond,
+            Some(_) => Arc::new(move || {
+                let message = format!("attempt {} was scripted to fail", attempt);
+                GenericResult::new_error(ScriptedError(message))
+            }),
+            None => Arc::new(|| GenericResult::new_error(ScriptedError("no script".to_string()))),
+        };
+
+        drop(state);
+
+        if latency == Duration::from_secs(0) {
+            return into.send(respond()).is_ok();
+        }
+
+        thread::spawn(move || {
+            thread::sleep(latency);
+            let _ = into.send(respond());
+        });
+
+        true
+    }
+}
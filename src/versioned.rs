@@ -0,0 +1,213 @@
+use crate::{idvec::IdVecIndex, novec::NoVec, *};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VersionedIndex {
+    pub index: usize,
+    pub version: u32,
+}
+
+impl From<VersionedIndex> for IdVecIndex {
+    fn from(index: VersionedIndex) -> Self {
+        IdVecIndex(index.index)
+    }
+}
+
+// A NoVec whose slots carry a version that is bumped every time they're emptied, so an index
+// that outlived its value is rejected instead of reaching whatever took the slot next
+#[derive(Clone, Debug, Default)]
+pub struct VersionedNoVec<T> {
+    values: NoVec<T>,
+    versions: Vec<u32>,
+}
+
+impl<T> VersionedNoVec<T> {
+    pub fn new() -> Self {
+        Self { values: NoVec::new(), versions: vec![] }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { values: NoVec::with_capacity(capacity), versions: Vec::with_capacity(capacity) }
+    }
+
+    // The index the next push will be given
+    pub fn next_id(&self) -> VersionedIndex {
+        let index = self.values.next_id();
+
+        VersionedIndex { index, version: self.version(index) }
+    }
+
+    pub fn push(&mut self, value: T) -> VersionedIndex {
+        let index = self.values.push(value);
+
+        if index >= self.versions.len() {
+            self.versions.resize(index + 1, 0);
+        }
+
+        VersionedIndex { index, version: self.versions[index] }
+    }
+
+    pub fn contains(&self, index: VersionedIndex) -> bool {
+        self.get(index).is_some()
+    }
+
+    pub fn get(&self, index: VersionedIndex) -> Option<&T> {
+        if !self.is_current(index) {
+            return None;
+        }
+
+        self.values.get(index.index)
+    }
+
+    pub fn get_mut(&mut self, index: VersionedIndex) -> Option<&mut T> {
+        if !self.is_current(index) {
+            return None;
+        }
+
+        self.values.get_mut(index.index)
+    }
+
+    pub fn remove(&mut self, index: VersionedIndex) -> Option<T> {
+        if !self.is_current(index) {
+            return None;
+        }
+
+        let removed = self.values.remove(index.index)?;
+        self.versions[index.index] = self.versions[index.index].wrapping_add(1);
+
+        Some(removed)
+    }
+
+    // Places the value at the index if the index is current for its slot, a slot that has never
+    // been used takes on the index's version. Err hands the value back for a stale index
+    pub fn try_insert(&mut self, index: VersionedIndex, value: T) -> Result<Option<T>, T> {
+        if self.is_stale(index) {
+            return Err(value);
+        }
+
+        if index.index >= self.versions.len() {
+            self.versions.resize(index.index + 1, 0);
+        }
+
+        self.versions[index.index] = index.version;
+
+        Ok(self.values.insert_at(index.index, value))
+    }
+
+    // Exchanges the values of two indices in place, the slots keep their versions. False if
+    // either index isn't current
+    pub fn swap_values(&mut self, a: VersionedIndex, b: VersionedIndex) -> bool {
+        if self.get(a).is_none() || self.get(b).is_none() {
+            return false;
+        }
+
+        if let Some([a, b]) = self.values.get_many_mut([a.index, b.index]) {
+            std::mem::swap(a, b);
+        }
+
+        true
+    }
+
+    // Same as try_insert but a stale index has its value handed straight back
+    pub fn insert(&mut self, index: VersionedIndex, value: T) -> Option<T> {
+        self.try_insert(index, value).unwrap_or_else(Some)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (VersionedIndex, &T)> {
+        let versions = &self.versions;
+
+        self.values
+            .iter()
+            .map(move |(index, value)| (VersionedIndex { index, version: versions[index] }, value))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (VersionedIndex, &mut T)> {
+        let versions = &self.versions;
+
+        self.values
+            .iter_mut()
+            .map(move |(index, value)| (VersionedIndex { index, version: versions[index] }, value))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.values.values()
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.values.values_mut()
+    }
+
+    fn version(&self, index: usize) -> u32 {
+        self.versions.get(index).copied().unwrap_or(0)
+    }
+
+    // Slots that have never been used take any version, including the ones skipped over by an
+    // insert further along
+    fn is_stale(&self, index: VersionedIndex) -> bool {
+        match self.versions.get(index.index) {
+            Some(version) => *version != index.version && !self.is_unused(index.index),
+            None => false,
+        }
+    }
+
+    // Emptying a slot always moves its version on, so an empty slot still at 0 never held anything
+    fn is_unused(&self, index: usize) -> bool {
+        self.version(index) == 0 && self.values.get(index).is_none()
+    }
+
+    fn is_current(&self, index: VersionedIndex) -> bool {
+        self.versions.get(index.index) == Some(&index.version)
+    }
+}
+
+impl<T> UnorderedStorage for VersionedNoVec<T> {
+    type Index = VersionedIndex;
+    type Item = T;
+
+    fn insert(&mut self, index: VersionedIndex, value: T) -> Option<T> {
+        <VersionedNoVec<T>>::insert(self, index, value)
+    }
+
+    fn accepts(&self, index: &VersionedIndex) -> bool {
+        !self.is_stale(*index)
+    }
+
+    fn swap_values(&mut self, a: &VersionedIndex, b: &VersionedIndex) -> bool {
+        <VersionedNoVec<T>>::swap_values(self, *a, *b)
+    }
+
+    fn remove(&mut self, index: &VersionedIndex) -> Option<T> {
+        <VersionedNoVec<T>>::remove(self, *index)
+    }
+
+    fn get(&self, index: &VersionedIndex) -> Option<&T> {
+        <VersionedNoVec<T>>::get(self, *index)
+    }
+
+    fn get_mut(&mut self, index: &VersionedIndex) -> Option<&mut T> {
+        <VersionedNoVec<T>>::get_mut(self, *index)
+    }
+}
+
+impl<T> ExpandableStorage for VersionedNoVec<T> {
+    fn push(&mut self, value: T) -> VersionedIndex {
+        self.push(value)
+    }
+}
+
+#[cfg(feature = "testing")]
+impl<T> crate::testing::CheckInvariants for VersionedNoVec<T> {
+    fn check_invariants(&self) -> Result<(), String> {
+        self.values.check_invariants()?;
+
+        match self.values.iter().find(|(index, _)| *index >= self.versions.len()) {
+            Some((index, _)) => Err(format!("Entry {} has no version", index)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T> WithCapacity for VersionedNoVec<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        VersionedNoVec::with_capacity(capacity)
+    }
+}